@@ -10,11 +10,11 @@ use std::fmt;
 
 use cairo::{BorrowError, Context, Filter, Format, ImageSurface, Matrix, Status, SurfacePattern};
 
-use piet::kurbo::{Affine, PathEl, Point, QuadBez, Rect, Shape};
+use piet::kurbo::{Affine, PathEl, Point, QuadBez, Rect, Shape, Size};
 
 use piet::{
-    new_error, Color, Error, ErrorKind, FixedGradient, ImageFormat, InterpolationMode, IntoBrush,
-    LineCap, LineJoin, RenderContext, StrokeStyle,
+    new_error, BlendMode, Color, Error, ErrorKind, ExtendMode, FixedGradient, FixedSweepGradient,
+    ImageFormat, InterpolationMode, IntoBrush, LineCap, LineJoin, RenderContext, StrokeStyle,
 };
 
 pub use crate::text::{
@@ -26,27 +26,45 @@ pub struct CairoRenderContext<'a> {
     // concurrency problems.
     ctx: &'a mut Context,
     text: CairoText<'a>,
+    /// The layers and masks currently being drawn, innermost last.
+    layers: Vec<Layer>,
+    /// An error reported by `report_error`, which cairo doesn't keep.
+    err: Result<(), Error>,
+}
+
+/// A group pushed onto the cairo context.
+enum Layer {
+    /// A layer composited with a uniform opacity.
+    Opacity(f64),
+    /// A layer composited through the alpha channel of a pattern.
+    Mask(cairo::Pattern),
+    /// The content of a mask, which becomes a `Mask` layer when popped.
+    MaskContent,
 }
 
 impl<'a> CairoRenderContext<'a> {
     /// Create a new Cairo back-end.
     ///
-    /// At the moment, it uses the "toy text API" for text layout, but when
-    /// we change to a more sophisticated text layout approach, we'll probably
-    /// need a factory for that as an additional argument.
+    /// Its text factory starts with no fonts loaded, and finds installed
+    /// fonts with fontconfig, or with cairo's toy font API without the
+    /// `fontconfig` feature.
     pub fn new(ctx: &mut Context) -> CairoRenderContext {
         CairoRenderContext {
             ctx,
             text: CairoText::new(),
+            layers: Vec::new(),
+            err: Ok(()),
         }
     }
 }
 
 #[derive(Clone)]
 pub enum Brush {
-    Solid(u32),
+    Solid(Color),
     Linear(cairo::LinearGradient),
     Radial(cairo::RadialGradient),
+    Sweep(cairo::Mesh),
+    Image(SurfacePattern),
 }
 
 #[derive(Debug)]
@@ -69,7 +87,7 @@ trait WrapError<T> {
 impl<T> WrapError<T> for Result<T, BorrowError> {
     fn wrap(self) -> Result<T, Error> {
         self.map_err(|e| {
-            let e: Box<dyn std::error::Error> = Box::new(e);
+            let e: Box<dyn std::error::Error + Send + Sync> = Box::new(e);
             e.into()
         })
     }
@@ -78,7 +96,7 @@ impl<T> WrapError<T> for Result<T, BorrowError> {
 impl<T> WrapError<T> for Result<T, Status> {
     fn wrap(self) -> Result<T, Error> {
         self.map_err(|e| {
-            let e: Box<dyn std::error::Error> = Box::new(WrappedStatus(e));
+            let e: Box<dyn std::error::Error + Send + Sync> = Box::new(WrappedStatus(e));
             e.into()
         })
     }
@@ -89,14 +107,8 @@ impl<T> WrapError<T> for Result<T, Status> {
 macro_rules! set_gradient_stops {
     ($dst: expr, $stops: expr) => {
         for stop in $stops {
-            let rgba = stop.color.as_rgba_u32();
-            $dst.add_color_stop_rgba(
-                stop.pos as f64,
-                byte_to_frac(rgba >> 24),
-                byte_to_frac(rgba >> 16),
-                byte_to_frac(rgba >> 8),
-                byte_to_frac(rgba),
-            );
+            let (r, g, b, a) = stop.color.as_rgba();
+            $dst.add_color_stop_rgba(stop.pos as f64, r, g, b, a);
         }
     };
 }
@@ -110,36 +122,45 @@ impl<'a> RenderContext for CairoRenderContext<'a> {
     type Image = ImageSurface;
 
     fn status(&mut self) -> Result<(), Error> {
+        std::mem::replace(&mut self.err, Ok(()))?;
         let status = self.ctx.status();
         if status == Status::Success {
             Ok(())
         } else {
-            let e: Box<dyn std::error::Error> = Box::new(WrappedStatus(status));
+            let e: Box<dyn std::error::Error + Send + Sync> = Box::new(WrappedStatus(status));
             Err(e.into())
         }
     }
 
+    fn report_error(&mut self, err: Error) {
+        self.err = Err(err);
+    }
+
     fn clear(&mut self, color: Color) {
-        let rgba = color.as_rgba_u32();
-        self.ctx.set_source_rgb(
-            byte_to_frac(rgba >> 24),
-            byte_to_frac(rgba >> 16),
-            byte_to_frac(rgba >> 8),
-        );
+        let (r, g, b, _) = color.as_rgba();
+        self.ctx.save();
+        self.ctx.set_operator(cairo::Operator::Over);
+        self.ctx.set_source_rgb(r, g, b);
         self.ctx.paint();
+        self.ctx.restore();
     }
 
     fn solid_brush(&mut self, color: Color) -> Brush {
-        Brush::Solid(color.as_rgba_u32())
+        Brush::Solid(color)
     }
 
     fn gradient(&mut self, gradient: impl Into<FixedGradient>) -> Result<Brush, Error> {
-        match gradient.into() {
+        let gradient = gradient.into();
+        gradient
+            .validate()
+            .map_err(|e| e.with_context("gradient"))?;
+        match gradient {
             FixedGradient::Linear(linear) => {
                 let (x0, y0) = (linear.start.x, linear.start.y);
                 let (x1, y1) = (linear.end.x, linear.end.y);
                 let lg = cairo::LinearGradient::new(x0, y0, x1, y1);
                 set_gradient_stops!(&lg, &linear.stops);
+                lg.set_extend(convert_extend_mode(linear.extend));
                 Ok(Brush::Linear(lg))
             }
             FixedGradient::Radial(radial) => {
@@ -148,11 +169,35 @@ impl<'a> RenderContext for CairoRenderContext<'a> {
                 let r = radial.radius;
                 let rg = cairo::RadialGradient::new(xc + xo, yc + yo, 0.0, xc, yc, r);
                 set_gradient_stops!(&rg, &radial.stops);
+                rg.set_extend(convert_extend_mode(radial.extend));
                 Ok(Brush::Radial(rg))
             }
+            FixedGradient::Sweep(sweep) => {
+                // Mesh patterns are transparent outside their patches, so the
+                // mesh has to reach the edges of everything we might draw.
+                let (x0, y0, x1, y1) = self.ctx.clip_extents();
+                let radius = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)]
+                    .iter()
+                    .map(|&corner| sweep.center.distance(corner.into()))
+                    .fold(1.0, f64::max);
+                Ok(Brush::Sweep(sweep_mesh(&sweep, radius)))
+            }
         }
     }
 
+    fn image_pattern(
+        &mut self,
+        image: &Self::Image,
+        transform: Affine,
+        extend: ExtendMode,
+    ) -> Result<Brush, Error> {
+        let pattern = SurfacePattern::create(image);
+        // The pattern matrix maps from user space to image space.
+        pattern.set_matrix(affine_to_matrix(transform.inverse()));
+        pattern.set_extend(convert_extend_mode(extend));
+        Ok(Brush::Image(pattern))
+    }
+
     fn fill(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
         let brush = brush.make_brush(self, || shape.bounding_box());
         self.set_path(shape);
@@ -169,6 +214,15 @@ impl<'a> RenderContext for CairoRenderContext<'a> {
         self.ctx.fill();
     }
 
+    fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &impl IntoBrush<Self>) {
+        let brush = brush.make_brush(self, || rect);
+        // The only failure here is allocating the mask, which leaves nothing to draw.
+        if let Ok((image, origin)) = compute_blurred_rect(rect, blur_radius) {
+            self.set_brush(&*brush);
+            self.ctx.mask_surface(&image, origin.x, origin.y);
+        }
+    }
+
     fn clip(&mut self, shape: impl Shape) {
         self.set_path(shape);
         self.ctx.set_fill_rule(cairo::FillRule::Winding);
@@ -209,11 +263,39 @@ impl<'a> RenderContext for CairoRenderContext<'a> {
     ) {
         // TODO: bounding box for text
         let brush = brush.make_brush(self, || Rect::ZERO);
-        self.ctx.set_scaled_font(&layout.font);
-        self.set_brush(&*brush);
         let pos = pos.into();
-        self.ctx.move_to(pos.x, pos.y);
-        self.ctx.show_text(&layout.text);
+        for line in &layout.lines {
+            let y = pos.y + line.baseline;
+            for glyph_run in layout.line_glyphs(line, pos) {
+                let run = glyph_run.run;
+                match &run.color {
+                    Some(color) => {
+                        let (r, g, b, a) = color.as_rgba();
+                        self.ctx.set_source_rgba(r, g, b, a);
+                    }
+                    None => self.set_brush(&brush),
+                }
+                self.ctx.set_scaled_font(&run.font);
+                self.ctx.show_glyphs(&glyph_run.glyphs);
+                if run.underline || run.strikethrough {
+                    // The underline is placed as the font says, and the
+                    // strikethrough at a typical proportion of the size.
+                    let x = glyph_run.x0;
+                    let width = glyph_run.x1 - glyph_run.x0;
+                    let (position, thickness) = run.underline_metrics;
+                    let thickness = thickness.max(1.0);
+                    if run.underline {
+                        let y = y + position;
+                        self.ctx.rectangle(x, y, width, thickness);
+                    }
+                    if run.strikethrough {
+                        let y = y - run.size * 0.3;
+                        self.ctx.rectangle(x, y, width, thickness);
+                    }
+                    self.ctx.fill();
+                }
+            }
+        }
     }
 
     fn save(&mut self) -> Result<(), Error> {
@@ -226,6 +308,73 @@ impl<'a> RenderContext for CairoRenderContext<'a> {
         self.status()
     }
 
+    fn push_layer(&mut self, opacity: f64, clip: Option<Rect>) -> Result<(), Error> {
+        self.ctx.save();
+        if let Some(rect) = clip {
+            self.clip(rect);
+        }
+        // Cairo also saves the state when pushing a group, and refuses to
+        // restore across it, so unbalanced restores are reported by `status`.
+        self.ctx.push_group();
+        self.layers.push(Layer::Opacity(opacity));
+        self.status()
+    }
+
+    fn pop_layer(&mut self) -> Result<(), Error> {
+        // A mask has to be finished with `pop_mask` first.
+        if let Some(Layer::MaskContent) = self.layers.last() {
+            return Err(new_error(ErrorKind::StackUnbalance));
+        }
+        match self.layers.pop() {
+            Some(Layer::Opacity(opacity)) => {
+                self.ctx.pop_group_to_source();
+                self.ctx.paint_with_alpha(opacity);
+            }
+            Some(Layer::Mask(mask)) => {
+                self.ctx.pop_group_to_source();
+                self.ctx.mask(&mask);
+            }
+            Some(Layer::MaskContent) => unreachable!(),
+            None => return Err(new_error(ErrorKind::StackUnbalance)),
+        }
+        self.ctx.restore();
+        self.status()
+    }
+
+    fn push_image_mask(&mut self, image: &Self::Image, dst_rect: Rect) -> Result<(), Error> {
+        let mask = SurfacePattern::create(image);
+        // The pattern matrix maps user space to image space. Outside of the
+        // image, the pattern is transparent, so nothing is drawn there.
+        let scale_x = image.get_width() as f64 / dst_rect.width();
+        let scale_y = image.get_height() as f64 / dst_rect.height();
+        let xf = Affine::scale_non_uniform(scale_x, scale_y)
+            * Affine::translate((-dst_rect.x0, -dst_rect.y0));
+        mask.set_matrix(affine_to_matrix(xf));
+        self.ctx.save();
+        self.ctx.push_group();
+        self.layers.push(Layer::Mask((*mask).clone()));
+        self.status()
+    }
+
+    fn push_mask(&mut self) -> Result<(), Error> {
+        self.ctx.push_group();
+        self.layers.push(Layer::MaskContent);
+        self.status()
+    }
+
+    fn pop_mask(&mut self) -> Result<(), Error> {
+        match self.layers.last() {
+            Some(Layer::MaskContent) => (),
+            _ => return Err(new_error(ErrorKind::StackUnbalance)),
+        }
+        self.layers.pop();
+        let mask = self.ctx.pop_group();
+        self.ctx.save();
+        self.ctx.push_group();
+        self.layers.push(Layer::Mask(mask));
+        self.status()
+    }
+
     fn finish(&mut self) -> Result<(), Error> {
         self.status()
     }
@@ -234,6 +383,10 @@ impl<'a> RenderContext for CairoRenderContext<'a> {
         self.ctx.transform(affine_to_matrix(transform));
     }
 
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.ctx.set_operator(convert_blend_mode(mode));
+    }
+
     fn current_transform(&self) -> Affine {
         matrix_to_affine(self.ctx.get_matrix())
     }
@@ -248,8 +401,24 @@ impl<'a> RenderContext for CairoRenderContext<'a> {
         let cairo_fmt = match format {
             ImageFormat::Rgb => Format::Rgb24,
             ImageFormat::RgbaSeparate | ImageFormat::RgbaPremul => Format::ARgb32,
-            _ => return Err(new_error(ErrorKind::NotSupported)),
+            _ => {
+                let err = new_error(ErrorKind::UnsupportedFormat(format));
+                return Err(err.with_context("make_image"));
+            }
         };
+        // The largest size cairo supports for image surfaces.
+        if width > 32767 || height > 32767 {
+            let err = new_error(ErrorKind::InvalidImageSize { width, height });
+            return Err(err.with_context("make_image"));
+        }
+        let expected = width * height * format.bytes_per_pixel();
+        if buf.len() < expected {
+            let kind = ErrorKind::InvalidBufferLength {
+                expected,
+                found: buf.len(),
+            };
+            return Err(new_error(kind).with_context("make_image"));
+        }
         let mut image = ImageSurface::create(cairo_fmt, width as i32, height as i32).wrap()?;
         // Confident no borrow errors because we just created it.
         let bytes_per_pixel = format.bytes_per_pixel();
@@ -299,6 +468,79 @@ impl<'a> RenderContext for CairoRenderContext<'a> {
         Ok(image)
     }
 
+    fn create_offscreen(
+        &mut self,
+        size: Size,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<Self::Image, Error> {
+        let width = size.width.ceil() as i32;
+        let height = size.height.ceil() as i32;
+        let image = ImageSurface::create(Format::ARgb32, width, height).wrap()?;
+        let ctx = std::mem::replace(self.ctx, Context::new(&image));
+        let layers = std::mem::take(&mut self.layers);
+        let result = f(self).and_then(|()| self.status());
+        // Cairo doesn't expose the depth of its state stack, so unmatched
+        // saves go unnoticed; they are dropped with the offscreen context.
+        let balanced = self.layers.is_empty();
+        *self.ctx = ctx;
+        self.layers = layers;
+        result?;
+        if !balanced {
+            return Err(new_error(ErrorKind::StackUnbalance));
+        }
+        Ok(image)
+    }
+
+    fn capture_image_area(&mut self, src_rect: impl Into<Rect>) -> Result<Self::Image, Error> {
+        let rect = self
+            .current_transform()
+            .transform_rect_bbox(src_rect.into())
+            .expand();
+        copy_surface(&self.ctx.get_target(), rect)
+    }
+
+    fn read_image(
+        &mut self,
+        image: &Self::Image,
+        format: ImageFormat,
+    ) -> Result<(usize, usize, Vec<u8>), Error> {
+        let width = image.get_width() as usize;
+        let height = image.get_height() as usize;
+        // The data of a surface can only be borrowed while it isn't shared,
+        // which the image may be, so read from a copy.
+        let mut copy = copy_surface(image, Rect::new(0.0, 0.0, width as f64, height as f64))?;
+        let stride = copy.get_stride() as usize;
+        let data = copy.get_data().wrap()?;
+        fn unpremul(x: u8, a: u8) -> u8 {
+            if a == 0 {
+                0
+            } else {
+                ((x as u32 * 255 + a as u32 / 2) / a as u32).min(255) as u8
+            }
+        }
+        let mut buf = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let off = y * stride + x * 4;
+                let (b, g, r, a) = (data[off], data[off + 1], data[off + 2], data[off + 3]);
+                match format {
+                    ImageFormat::Rgb => {
+                        buf.extend_from_slice(&[unpremul(r, a), unpremul(g, a), unpremul(b, a)])
+                    }
+                    ImageFormat::RgbaSeparate => {
+                        buf.extend_from_slice(&[unpremul(r, a), unpremul(g, a), unpremul(b, a), a])
+                    }
+                    ImageFormat::RgbaPremul => buf.extend_from_slice(&[r, g, b, a]),
+                    _ => {
+                        let err = new_error(ErrorKind::UnsupportedFormat(format));
+                        return Err(err.with_context("read_image"));
+                    }
+                }
+            }
+        }
+        Ok((width, height, buf))
+    }
+
     #[inline]
     fn draw_image(
         &mut self,
@@ -358,6 +600,20 @@ fn draw_image<'a>(
     });
 }
 
+/// Copy an area of a surface, with whole pixel bounds, into a new image.
+fn copy_surface(source: &cairo::Surface, rect: Rect) -> Result<ImageSurface, Error> {
+    let image =
+        ImageSurface::create(Format::ARgb32, rect.width() as i32, rect.height() as i32).wrap()?;
+    let ctx = Context::new(&image);
+    ctx.set_source_surface(source, -rect.x0, -rect.y0);
+    ctx.set_operator(cairo::Operator::Source);
+    ctx.paint();
+    match ctx.status() {
+        Status::Success => Ok(image),
+        status => Err(status).wrap(),
+    }
+}
+
 impl<'a> IntoBrush<CairoRenderContext<'a>> for Brush {
     fn make_brush<'b>(
         &'b self,
@@ -384,6 +640,145 @@ fn convert_line_join(line_join: LineJoin) -> cairo::LineJoin {
     }
 }
 
+fn convert_extend_mode(extend: ExtendMode) -> cairo::Extend {
+    match extend {
+        ExtendMode::Pad => cairo::Extend::Pad,
+        ExtendMode::Repeat => cairo::Extend::Repeat,
+        ExtendMode::Reflect => cairo::Extend::Reflect,
+    }
+}
+
+fn convert_blend_mode(mode: BlendMode) -> cairo::Operator {
+    match mode {
+        BlendMode::Clear => cairo::Operator::Clear,
+        BlendMode::Copy => cairo::Operator::Source,
+        BlendMode::SourceOver => cairo::Operator::Over,
+        BlendMode::SourceIn => cairo::Operator::In,
+        BlendMode::SourceOut => cairo::Operator::Out,
+        BlendMode::SourceAtop => cairo::Operator::Atop,
+        BlendMode::DestOver => cairo::Operator::DestOver,
+        BlendMode::DestIn => cairo::Operator::DestIn,
+        BlendMode::DestOut => cairo::Operator::DestOut,
+        BlendMode::DestAtop => cairo::Operator::DestAtop,
+        BlendMode::Xor => cairo::Operator::Xor,
+        BlendMode::Add => cairo::Operator::Add,
+        BlendMode::Multiply => cairo::Operator::Multiply,
+        BlendMode::Screen => cairo::Operator::Screen,
+        BlendMode::Overlay => cairo::Operator::Overlay,
+        BlendMode::Darken => cairo::Operator::Darken,
+        BlendMode::Lighten => cairo::Operator::Lighten,
+        BlendMode::ColorDodge => cairo::Operator::ColorDodge,
+        BlendMode::ColorBurn => cairo::Operator::ColorBurn,
+        BlendMode::HardLight => cairo::Operator::HardLight,
+        BlendMode::SoftLight => cairo::Operator::SoftLight,
+        BlendMode::Difference => cairo::Operator::Difference,
+        BlendMode::Exclusion => cairo::Operator::Exclusion,
+        BlendMode::Hue => cairo::Operator::HslHue,
+        BlendMode::Saturation => cairo::Operator::HslSaturation,
+        BlendMode::Color => cairo::Operator::HslColor,
+        BlendMode::Luminosity => cairo::Operator::HslLuminosity,
+    }
+}
+
+/// Approximate a sweep gradient with a mesh of wedges out to `radius`.
+fn sweep_mesh(sweep: &FixedSweepGradient, radius: f64) -> cairo::Mesh {
+    use std::f64::consts::PI;
+    // The largest angle covered by one patch, which keeps the arcs accurate.
+    const MAX_ANGLE: f64 = PI / 8.0;
+
+    let mesh = cairo::Mesh::new();
+    let span = (sweep.end_angle - sweep.start_angle).clamp(-2.0 * PI, 2.0 * PI);
+    let n = (span.abs() / MAX_ANGLE).ceil().max(1.0);
+    let mut positions: Vec<f64> = (0..=n as usize).map(|i| i as f64 / n).collect();
+    positions.extend(
+        sweep
+            .stops
+            .iter()
+            .map(|stop| f64::from(stop.pos))
+            .filter(|&pos| pos > 0.0 && pos < 1.0),
+    );
+    positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    positions.dedup();
+    for pair in positions.windows(2) {
+        let a0 = sweep.start_angle + pair[0] * span;
+        let a1 = sweep.start_angle + pair[1] * span;
+        let c0 = sweep.color_at(pair[0]);
+        let c1 = sweep.color_at(pair[1]);
+        add_wedge_patch(&mesh, sweep.center, radius, a0, a1, &c0, &c1);
+    }
+
+    // Pad the rest of the circle, half with each of the end colors.
+    let rest = 2.0 * PI - span.abs();
+    if rest > 0.0 {
+        let step = rest.copysign(span) / 2.0;
+        let n = (rest / 2.0 / MAX_ANGLE).ceil();
+        let (start, end) = (sweep.color_at(0.0), sweep.color_at(1.0));
+        for i in 0..n as usize {
+            let a0 = sweep.end_angle + step * i as f64 / n;
+            let a1 = sweep.end_angle + step * (i + 1) as f64 / n;
+            add_wedge_patch(&mesh, sweep.center, radius, a0, a1, &end, &end);
+            let a0 = a0 + step;
+            let a1 = a1 + step;
+            add_wedge_patch(&mesh, sweep.center, radius, a0, a1, &start, &start);
+        }
+    }
+    mesh
+}
+
+/// Add a wedge between two angles, shading from `c0` to `c1`.
+fn add_wedge_patch(
+    mesh: &cairo::Mesh,
+    center: Point,
+    radius: f64,
+    a0: f64,
+    a1: f64,
+    c0: &Color,
+    c1: &Color,
+) {
+    use cairo::MeshCorner::*;
+    // The standard cubic Bézier approximation of a circular arc.
+    let k = 4.0 / 3.0 * ((a1 - a0) / 4.0).tan();
+    let point = |a: f64, t: f64| {
+        let (sin, cos) = a.sin_cos();
+        (
+            center.x + radius * (cos - t * sin),
+            center.y + radius * (sin + t * cos),
+        )
+    };
+    let (x0, y0) = point(a0, 0.0);
+    let (x1, y1) = point(a0, k);
+    let (x2, y2) = point(a1, -k);
+    let (x3, y3) = point(a1, 0.0);
+    mesh.begin_patch();
+    mesh.move_to(center.x, center.y);
+    mesh.line_to(x0, y0);
+    mesh.curve_to(x1, y1, x2, y2, x3, y3);
+    mesh.line_to(center.x, center.y);
+    for &(corner, color) in &[
+        (MeshCorner0, c0),
+        (MeshCorner1, c0),
+        (MeshCorner2, c1),
+        (MeshCorner3, c1),
+    ] {
+        let (r, g, b, a) = color.as_rgba();
+        mesh.set_corner_color_rgba(corner, r, g, b, a);
+    }
+    mesh.end_patch();
+}
+
+/// Render the alpha mask of a blurred rectangle, returning it with its origin.
+fn compute_blurred_rect(rect: Rect, radius: f64) -> Result<(ImageSurface, Point), Error> {
+    let size = piet::size_for_blurred_rect(rect, radius);
+    let mut image =
+        ImageSurface::create(Format::A8, size.width as i32, size.height as i32).wrap()?;
+    let stride = image.get_stride() as usize;
+    let bounds = {
+        let mut data = image.get_data().wrap()?;
+        piet::compute_blurred_rect(rect, radius, stride, &mut data)
+    };
+    Ok((image, bounds.origin()))
+}
+
 impl<'a> CairoRenderContext<'a> {
     /// Set the source pattern to the brush.
     ///
@@ -391,14 +786,14 @@ impl<'a> CairoRenderContext<'a> {
     /// This is part of the impedance matching.
     fn set_brush(&mut self, brush: &Brush) {
         match *brush {
-            Brush::Solid(rgba) => self.ctx.set_source_rgba(
-                byte_to_frac(rgba >> 24),
-                byte_to_frac(rgba >> 16),
-                byte_to_frac(rgba >> 8),
-                byte_to_frac(rgba),
-            ),
+            Brush::Solid(ref color) => {
+                let (r, g, b, a) = color.as_rgba();
+                self.ctx.set_source_rgba(r, g, b, a);
+            }
             Brush::Linear(ref linear) => self.ctx.set_source(linear),
             Brush::Radial(ref radial) => self.ctx.set_source(radial),
+            Brush::Sweep(ref sweep) => self.ctx.set_source(sweep),
+            Brush::Image(ref image) => self.ctx.set_source(image),
         }
     }
 
@@ -457,10 +852,6 @@ impl<'a> CairoRenderContext<'a> {
     }
 }
 
-fn byte_to_frac(byte: u32) -> f64 {
-    ((byte & 255) as f64) * (1.0 / 255.0)
-}
-
 /// Can't implement RoundFrom here because both types belong to other crates.
 fn affine_to_matrix(affine: Affine) -> Matrix {
     let a = affine.as_coeffs();
@@ -479,3 +870,213 @@ fn matrix_to_affine(matrix: Matrix) -> Affine {
         matrix.xx, matrix.yx, matrix.xy, matrix.yy, matrix.x0, matrix.y0,
     ])
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use piet::{FixedLinearGradient, GradientStop, RecordingRenderContext};
+    use piet_test::draw_test_picture;
+
+    fn render(draw: impl FnOnce(&mut CairoRenderContext) -> Result<(), Error>) -> Vec<u8> {
+        let mut surface = ImageSurface::create(Format::ARgb32, 200, 100).unwrap();
+        {
+            let mut cr = Context::new(&surface);
+            let mut piet = CairoRenderContext::new(&mut cr);
+            draw(&mut piet).unwrap();
+            piet.finish().unwrap();
+        }
+        surface.flush();
+        let data = surface.get_data().unwrap();
+        data.to_vec()
+    }
+
+    /// The alpha of a pixel of what `render` drew.
+    fn alpha(data: &[u8], x: usize, y: usize) -> u8 {
+        // The pixels are native-endian ARGB words, with no padding at a
+        // width of 200.
+        let off = (y * 200 + x) * 4;
+        let word = [data[off], data[off + 1], data[off + 2], data[off + 3]];
+        (u32::from_ne_bytes(word) >> 24) as u8
+    }
+
+    #[test]
+    fn recording_plays_back_identically() {
+        // Pictures 0, 5 and 16 measure text, which isn't possible while recording.
+        for number in (1..=17).filter(|&n| n != 5 && n != 16) {
+            let direct = render(|rc| draw_test_picture(rc, number));
+            let mut recording = RecordingRenderContext::new();
+            draw_test_picture(&mut recording, number).unwrap();
+            let display_list = recording.into_display_list();
+            let played = render(|rc| display_list.play(rc));
+            assert!(direct == played, "picture {} differs", number);
+        }
+    }
+
+    #[test]
+    fn captured_area_reads_back() {
+        let mut pixels = None;
+        render(|rc| {
+            rc.transform(Affine::scale(2.0));
+            rc.fill(
+                Rect::new(5.0, 5.0, 10.0, 10.0),
+                &Color::rgba8(0xff, 0, 0, 0x80),
+            );
+            let image = rc.capture_image_area(Rect::new(4.0, 4.0, 8.0, 6.0))?;
+            pixels = Some(rc.read_image(&image, ImageFormat::RgbaSeparate)?);
+            Ok(())
+        });
+        let (width, height, buf) = pixels.unwrap();
+        // The area covers device pixels, at twice the size.
+        assert_eq!((width, height), (8, 4));
+        assert_eq!(&buf[..4], &[0, 0, 0, 0]);
+        let inside = (3 * width + 3) * 4;
+        assert_eq!(&buf[inside..inside + 4], &[0xff, 0, 0, 0x80]);
+    }
+
+    #[test]
+    fn errors_describe_the_failure() {
+        render(|rc| {
+            let err = rc
+                .make_image(4, 4, &[0; 40], ImageFormat::Rgb)
+                .err()
+                .unwrap();
+            assert!(matches!(
+                err.kind(),
+                ErrorKind::InvalidBufferLength {
+                    expected: 48,
+                    found: 40
+                }
+            ));
+            assert_eq!(err.context(), Some("make_image"));
+
+            let gradient = FixedLinearGradient {
+                start: Point::ORIGIN,
+                end: Point::new(10.0, 0.0),
+                stops: vec![],
+                extend: ExtendMode::Pad,
+            };
+            let err = rc.gradient(gradient).err().unwrap();
+            assert!(matches!(err.kind(), ErrorKind::InvalidGradient(_)));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn single_stop_gradient_is_solid() {
+        let color = Color::rgba8(0x20, 0x80, 0xc0, 0xa0);
+        let rect = Rect::new(10.0, 10.0, 190.0, 90.0);
+        let solid = render(|rc| {
+            rc.fill(rect, &color);
+            Ok(())
+        });
+        let gradient = render(|rc| {
+            let brush = rc.gradient(FixedLinearGradient {
+                start: Point::new(0.0, 0.0),
+                end: Point::new(200.0, 0.0),
+                stops: vec![GradientStop {
+                    pos: 0.5,
+                    color: color.clone(),
+                }],
+                extend: ExtendMode::Pad,
+            })?;
+            rc.fill(rect, &brush);
+            Ok(())
+        });
+        assert!(solid == gradient);
+    }
+
+    #[test]
+    fn layer_opacity_applies_to_the_whole_layer() {
+        let red = Color::rgb8(0xff, 0, 0);
+        let layer = render(|rc| {
+            rc.push_layer(0.5, None)?;
+            rc.fill(Rect::new(10.0, 10.0, 60.0, 60.0), &red);
+            rc.fill(Rect::new(40.0, 40.0, 90.0, 90.0), &red);
+            rc.pop_layer()
+        });
+        // Where the shapes overlap, the layer is drawn once, at half
+        // opacity, rather than each shape at half opacity, which would be
+        // about 0xc0.
+        assert!((0x7f..=0x80).contains(&alpha(&layer, 50, 50)));
+        assert!((0x7f..=0x80).contains(&alpha(&layer, 20, 20)));
+        assert_eq!(alpha(&layer, 100, 50), 0);
+    }
+
+    #[test]
+    fn offscreen_image_reads_back() {
+        let mut pixels = None;
+        render(|rc| {
+            let image = rc.create_offscreen(Size::new(10.5, 4.0), |rc| {
+                rc.fill(Rect::new(2.0, 1.0, 4.0, 3.0), &Color::rgb8(0, 0, 0xff));
+                Ok(())
+            })?;
+            pixels = Some(rc.read_image(&image, ImageFormat::RgbaSeparate)?);
+            Ok(())
+        });
+        let (width, height, buf) = pixels.unwrap();
+        // The size is rounded up to whole pixels.
+        assert_eq!((width, height), (11, 4));
+        assert_eq!(&buf[..4], &[0, 0, 0, 0]);
+        let inside = (2 * width + 3) * 4;
+        assert_eq!(&buf[inside..inside + 4], &[0, 0, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn unbalanced_offscreen_is_reported() {
+        let data = render(|rc| {
+            let err = rc
+                .create_offscreen(Size::new(10.0, 10.0), |rc| rc.push_layer(0.5, None))
+                .err()
+                .unwrap();
+            assert!(matches!(err.kind(), ErrorKind::StackUnbalance));
+            // The outer context is as it was.
+            rc.fill(Rect::new(0.0, 0.0, 10.0, 10.0), &Color::BLACK);
+            rc.push_layer(0.5, None)?;
+            rc.pop_layer()
+        });
+        assert_eq!(alpha(&data, 5, 5), 0xff);
+    }
+
+    #[test]
+    fn masks_keep_what_is_inside() {
+        let green = Color::rgb8(0, 0x80, 0);
+        let everything = Rect::new(0.0, 0.0, 200.0, 100.0);
+        let data = render(|rc| {
+            rc.push_mask()?;
+            rc.fill(Rect::new(0.0, 0.0, 100.0, 100.0), &Color::BLACK);
+            rc.pop_mask()?;
+            rc.fill(everything, &green);
+            rc.pop_layer()
+        });
+        assert_eq!(alpha(&data, 50, 50), 0xff);
+        assert_eq!(alpha(&data, 150, 50), 0);
+
+        // The left of the image is opaque, and the right transparent.
+        let data = render(|rc| {
+            let mask = rc.make_image(
+                2,
+                1,
+                &[0, 0, 0, 0xff, 0, 0, 0, 0],
+                ImageFormat::RgbaSeparate,
+            )?;
+            rc.push_image_mask(&mask, everything)?;
+            rc.fill(everything, &green);
+            rc.pop_layer()
+        });
+        // The image is scaled smoothly, so look at the middles of its pixels.
+        assert_eq!(alpha(&data, 50, 50), 0xff);
+        assert_eq!(alpha(&data, 150, 50), 0);
+    }
+
+    #[test]
+    fn mask_content_is_popped_with_pop_mask() {
+        render(|rc| {
+            rc.push_mask()?;
+            let err = rc.pop_layer().err().unwrap();
+            assert!(matches!(err.kind(), ErrorKind::StackUnbalance));
+            // The mask can still be finished.
+            rc.pop_mask()?;
+            rc.pop_layer()
+        });
+    }
+}
@@ -1,53 +1,159 @@
 //! Text functionality for Piet cairo backend
 
+#[cfg(feature = "fontconfig")]
+mod fonts;
 mod grapheme;
+mod lines;
+mod shape;
+#[cfg(not(feature = "fontconfig"))]
+mod toy_fonts;
 
+use std::cell::RefCell;
 use std::marker::PhantomData;
+use std::ops::{Range, RangeBounds};
+use std::rc::Rc;
 
-use cairo::{FontFace, FontOptions, FontSlant, FontWeight, Matrix, ScaledFont};
+use cairo::{FontFace, FontOptions, Glyph, Matrix, ScaledFont};
 
 use piet::kurbo::Point;
 
 use piet::{
-    Error, Font, FontBuilder, HitTestMetrics, HitTestPoint, HitTestTextPosition, RoundInto, Text,
-    TextLayout, TextLayoutBuilder,
+    AttributeSpans, Color, Error, Font, FontBuilder, FontMetrics, FontStretch, FontStyle,
+    FontWeight, HitTestMetrics, HitTestPoint, HitTestTextPosition, LineMetric, RoundInto, Text,
+    TextAlignment, TextAttribute, TextLayout, TextLayoutBuilder, TextLayoutMetrics, TextStyle,
 };
 
+use unicode_bidi::BidiInfo;
 use unicode_segmentation::UnicodeSegmentation;
 
-use self::grapheme::point_x_in_grapheme;
+#[cfg(not(feature = "fontconfig"))]
+use self::toy_fonts as fonts;
 
-/// Right now, we don't need any state, as the "toy text API" treats the
-/// access to system font information as a global. This will change.
-// we use a phantom lifetime here to match the API of the d2d backend,
-// and the likely API of something with access to system font information.
-pub struct CairoText<'a>(PhantomData<&'a ()>);
+use self::fonts::{Face, FontCollection};
+use self::grapheme::point_x_in_grapheme;
+use self::lines::{align, count_spaces, is_rtl, line_ranges, trim_end, trim_newline, visual_order};
+use self::shape::{segments, shape, Cluster, Shaped, ShapedGlyph};
+
+/// The text factory for cairo, which keeps the fonts loaded into it.
+///
+/// Installed fonts are found with fontconfig, and fonts are drawn with
+/// FreeType. Without the `fontconfig` feature, fonts are made with cairo's
+/// toy font API instead, which can't load fonts or fall back to other
+/// fonts for missing characters.
+// we use a phantom lifetime here to match the API of the d2d backend.
+pub struct CairoText<'a> {
+    fonts: Rc<RefCell<FontCollection>>,
+    phantom: PhantomData<&'a ()>,
+}
 
-pub struct CairoFont(ScaledFont);
+pub struct CairoFont {
+    style: TextStyle,
+    /// The family of the face, which isn't the family of the style when it
+    /// had to fall back.
+    family: String,
+    face: Face,
+}
 
 pub struct CairoFontBuilder {
+    fonts: Rc<RefCell<FontCollection>>,
+
     family: String,
-    weight: FontWeight,
-    slant: FontSlant,
     size: f64,
+    weight: FontWeight,
+    style: FontStyle,
+    stretch: FontStretch,
 }
 
 pub struct CairoTextLayout {
-    // TODO should these fields be pub(crate)?
-    pub font: ScaledFont,
-    pub text: String,
+    pub(crate) text: String,
+    pub(crate) runs: Vec<CairoRun>,
+    pub(crate) lines: Vec<CairoLine>,
+}
+
+/// A run of text in a single font, style, direction and script, shaped
+/// into glyphs.
+pub(crate) struct CairoRun {
+    pub(crate) range: Range<usize>,
+    pub(crate) font: ScaledFont,
+    /// The offset of the start of the run from the start of the layout, with
+    /// all of the text on one line in text order.
+    pub(crate) x: f64,
+    pub(crate) size: f64,
+    pub(crate) color: Option<Color>,
+    pub(crate) underline: bool,
+    pub(crate) strikethrough: bool,
+    /// The distance from the baseline down to the top of an underline, and
+    /// its thickness.
+    pub(crate) underline_metrics: (f64, f64),
+    /// The bidi embedding level of the run; odd levels are right-to-left.
+    level: u8,
+    glyphs: Vec<ShapedGlyph>,
+    clusters: Vec<Cluster>,
 }
 
-pub struct CairoTextLayoutBuilder(CairoTextLayout);
+/// A line of text, after wrapping.
+pub(crate) struct CairoLine {
+    /// The text of the line, including trailing whitespace and newline.
+    pub(crate) range: Range<usize>,
+    /// The end of the text that counts towards the width of the line.
+    pub(crate) content_end: usize,
+    /// The width of the line, without its newline, or the whitespace where
+    /// it wraps.
+    pub(crate) width: f64,
+    /// The offset of the line from the left of the layout, from its
+    /// alignment.
+    pub(crate) offset: f64,
+    /// The extra width of each space, when the line is justified.
+    pub(crate) space_width: f64,
+    /// The offset of the baseline from the baseline of the first line.
+    pub(crate) baseline: f64,
+    pub(crate) ascent: f64,
+    pub(crate) descent: f64,
+    pub(crate) height: f64,
+    /// The clusters of the line, without its newline, from left to right.
+    clusters: Vec<PlacedCluster>,
+}
+
+/// A cluster of a run, placed on a line.
+struct PlacedCluster {
+    run: usize,
+    /// The index of the cluster in the clusters of its run.
+    cluster: usize,
+    /// The offset of the left edge of the cluster from the left of the
+    /// layout.
+    x: f64,
+    /// The width of the cluster, with the extra width of its spaces when the
+    /// line is justified.
+    width: f64,
+}
+
+/// The glyphs of a line drawn with one run, ready to draw.
+pub(crate) struct GlyphRun<'a> {
+    pub(crate) run: &'a CairoRun,
+    pub(crate) glyphs: Vec<Glyph>,
+    /// The left and right edges of the glyphs.
+    pub(crate) x0: f64,
+    pub(crate) x1: f64,
+}
+
+pub struct CairoTextLayoutBuilder {
+    fonts: Rc<RefCell<FontCollection>>,
+    text: String,
+    defaults: TextStyle,
+    attributes: AttributeSpans,
+    max_width: f64,
+    alignment: TextAlignment,
+}
 
 impl<'a> CairoText<'a> {
-    /// Create a new factory that satisfies the piet `Text` trait.
-    ///
-    /// No state is needed for now because the current implementation is just
-    /// toy text, but that will change when proper text is implemented.
+    /// Create a new factory that satisfies the piet `Text` trait, with no
+    /// fonts loaded.
     #[allow(clippy::new_without_default)]
     pub fn new() -> CairoText<'a> {
-        CairoText(PhantomData)
+        CairoText {
+            fonts: Rc::new(RefCell::new(FontCollection::default())),
+            phantom: PhantomData,
+        }
     }
 }
 
@@ -59,136 +165,484 @@ impl<'a> Text for CairoText<'a> {
 
     fn new_font_by_name(&mut self, name: &str, size: f64) -> Self::FontBuilder {
         CairoFontBuilder {
+            fonts: self.fonts.clone(),
             family: name.to_owned(),
             size: size.round_into(),
-            weight: FontWeight::Normal,
-            slant: FontSlant::Normal,
+            weight: FontWeight::default(),
+            style: FontStyle::default(),
+            stretch: FontStretch::default(),
         }
     }
 
+    fn load_font(&mut self, data: &[u8]) -> Result<String, Error> {
+        self.fonts.borrow_mut().load(data)
+    }
+
+    fn font_families(&mut self) -> Vec<String> {
+        self.fonts.borrow().families()
+    }
+
     fn new_text_layout(&mut self, font: &Self::Font, text: &str) -> Self::TextLayoutBuilder {
-        let text_layout = CairoTextLayout {
-            font: font.0.clone(),
+        CairoTextLayoutBuilder {
+            fonts: self.fonts.clone(),
             text: text.to_owned(),
-        };
-        CairoTextLayoutBuilder(text_layout)
+            defaults: font.style.clone(),
+            attributes: AttributeSpans::new(),
+            max_width: f64::INFINITY,
+            alignment: TextAlignment::default(),
+        }
     }
 }
 
 impl FontBuilder for CairoFontBuilder {
     type Out = CairoFont;
 
+    fn weight(mut self, weight: FontWeight) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    fn style(mut self, style: FontStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    fn stretch(mut self, stretch: FontStretch) -> Self {
+        self.stretch = stretch;
+        self
+    }
+
     fn build(self) -> Result<Self::Out, Error> {
-        let font_face = FontFace::toy_create(&self.family, self.slant, self.weight);
-        let font_matrix = scale_matrix(self.size);
-        let ctm = scale_matrix(1.0);
-        let options = FontOptions::default();
-        let scaled_font = ScaledFont::new(&font_face, &font_matrix, &ctm, &options);
-        Ok(CairoFont(scaled_font))
+        let mut style = TextStyle::new(self.family, self.size);
+        style.weight = self.weight;
+        style.style = self.style;
+        style.stretch = self.stretch;
+        let (family, face) = self.fonts.borrow().resolve(&style)?;
+        Ok(CairoFont {
+            style,
+            family,
+            face,
+        })
     }
 }
 
-impl Font for CairoFont {}
+impl Font for CairoFont {
+    fn metrics(&self) -> FontMetrics {
+        let font = scaled_font(&self.face.cairo, self.style.size);
+        let extents = font.extents();
+        // Cairo doesn't give decoration metrics, so the underline is read
+        // from the font, or approximated if the font has none.
+        let approximate = FontMetrics::approximate(self.style.size);
+        let (underline_position, underline_thickness) =
+            self.face.underline(self.style.size).unwrap_or((
+                approximate.underline_position,
+                approximate.underline_thickness,
+            ));
+        FontMetrics {
+            ascent: extents.ascent,
+            descent: extents.descent,
+            line_gap: (extents.height - extents.ascent - extents.descent).max(0.0),
+            x_height: -font.text_extents("x").y_bearing,
+            cap_height: -font.text_extents("H").y_bearing,
+            underline_position,
+            underline_thickness,
+        }
+    }
+
+    fn family_name(&self) -> &str {
+        &self.family
+    }
+}
 
 impl TextLayoutBuilder for CairoTextLayoutBuilder {
     type Out = CairoTextLayout;
 
+    fn range_attribute(
+        mut self,
+        range: impl RangeBounds<usize>,
+        attribute: impl Into<TextAttribute>,
+    ) -> Self {
+        self.attributes.add(&self.text, range, attribute.into());
+        self
+    }
+
+    fn max_width(mut self, width: f64) -> Self {
+        self.max_width = width;
+        self
+    }
+
+    fn alignment(mut self, alignment: TextAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
     fn build(self) -> Result<Self::Out, Error> {
-        Ok(self.0)
+        let bidi = BidiInfo::new(&self.text, None);
+        let mut x = 0.0;
+        let mut runs = Vec::new();
+        let fonts = self.fonts.borrow();
+        for (range, style) in self.attributes.runs(&self.text, &self.defaults) {
+            // Text the style's face doesn't have is drawn in fallback faces,
+            // and text of each direction and script is shaped on its own.
+            for (item, face) in fonts.itemize(&self.text[range.clone()], &style)? {
+                let item = range.start + item.start..range.start + item.end;
+                for (range, level, script) in segments(&self.text, item, &bidi.levels) {
+                    let text = &self.text[range.clone()];
+                    let Shaped { glyphs, clusters } =
+                        shape(text, range.start, &face, style.size, level, script)?;
+                    let run = CairoRun {
+                        range,
+                        font: scaled_font(&face.cairo, style.size),
+                        x,
+                        size: style.size,
+                        color: style.color.clone(),
+                        underline: style.underline,
+                        strikethrough: style.strikethrough,
+                        underline_metrics: face.underline(style.size).unwrap_or_else(|| {
+                            let approximate = FontMetrics::approximate(style.size);
+                            (
+                                approximate.underline_position,
+                                approximate.underline_thickness,
+                            )
+                        }),
+                        level: level.number(),
+                        glyphs,
+                        clusters,
+                    };
+                    x += run
+                        .clusters
+                        .iter()
+                        .map(|cluster| cluster.advance)
+                        .sum::<f64>();
+                    runs.push(run);
+                }
+            }
+        }
+        drop(fonts);
+        let mut layout = CairoTextLayout {
+            text: self.text,
+            runs,
+            lines: Vec::new(),
+        };
+        layout.lines = layout.break_lines(self.max_width);
+        layout.align_lines(self.max_width, self.alignment);
+        layout.place_clusters();
+        Ok(layout)
     }
 }
 
-impl TextLayout for CairoTextLayout {
-    fn width(&self) -> f64 {
-        self.font.text_extents(&self.text).x_advance
+impl CairoTextLayout {
+    /// The run containing a text position, or the last run.
+    fn run_at(&self, text_position: usize) -> &CairoRun {
+        self.runs
+            .iter()
+            .find(|run| text_position < run.range.end)
+            .unwrap_or_else(|| self.runs.last().unwrap())
     }
 
-    // first assume one line.
-    // TODO do with lines
-    fn hit_test_point(&self, point: Point) -> HitTestPoint {
-        // internal logic is using grapheme clusters, but return the text position associated
-        // with the border of the grapheme cluster.
+    /// The offset of a text position from the start of the layout, with
+    /// all of the text on one line in text order.
+    pub(crate) fn x_at(&self, text_position: usize) -> f64 {
+        let run = self.run_at(text_position);
+        let advance: f64 = run
+            .clusters
+            .iter()
+            .map(|cluster| {
+                if cluster.range.end <= text_position {
+                    cluster.advance
+                } else if cluster.range.start < text_position {
+                    cluster.advance * self.cluster_fraction(cluster, text_position)
+                } else {
+                    0.0
+                }
+            })
+            .sum();
+        run.x + advance
+    }
 
-        // null case
-        if self.text.is_empty() {
-            return HitTestPoint::default();
+    /// How far into a cluster a text position is, as a fraction of its
+    /// grapheme clusters, which split the advance of a ligature evenly.
+    fn cluster_fraction(&self, cluster: &Cluster, text_position: usize) -> f64 {
+        let range = cluster.range.clone();
+        let graphemes = self.text[range.clone()].grapheme_indices(true);
+        let (mut before, mut count) = (0, 0);
+        for (i, _) in graphemes {
+            if range.start + i < text_position {
+                before += 1;
+            }
+            count += 1;
         }
+        f64::from(before) / f64::from(count.max(1))
+    }
 
-        // get bounds
-        // TODO handle if string is not null yet count is 0?
-        let end = UnicodeSegmentation::graphemes(self.text.as_str(), true).count() - 1;
-        let end_bounds = match self.get_grapheme_boundaries(end) {
-            Some(bounds) => bounds,
-            None => return HitTestPoint::default(),
-        };
+    fn break_lines(&self, max_width: f64) -> Vec<CairoLine> {
+        let mut baseline = 0.0;
+        let mut prev: Option<(f64, f64)> = None;
+        line_ranges(&self.text, max_width, |i| self.x_at(i))
+            .into_iter()
+            .map(|range| {
+                // The tallest font of the runs in the line sets its metrics.
+                let (mut ascent, mut descent, mut height) = (0.0f64, 0.0f64, 0.0f64);
+                let mut runs = self
+                    .runs
+                    .iter()
+                    .filter(|run| run.range.start < range.end && range.start < run.range.end)
+                    .peekable();
+                if runs.peek().is_none() {
+                    let extents = self.run_at(range.start).font.extents();
+                    ascent = extents.ascent;
+                    descent = extents.descent;
+                    height = extents.height;
+                }
+                for run in runs {
+                    let extents = run.font.extents();
+                    ascent = ascent.max(extents.ascent);
+                    descent = descent.max(extents.descent);
+                    height = height.max(extents.height);
+                }
+                let height = height.max(ascent + descent);
+                if let Some((prev_ascent, prev_height)) = prev {
+                    baseline += prev_height - prev_ascent + ascent;
+                }
+                prev = Some((ascent, height));
+
+                // Whitespace at a wrapped line break hangs past the end
+                // of the line.
+                let end = trim_newline(&self.text, range.clone());
+                let end = if end == range.end && end < self.text.len() {
+                    trim_end(&self.text, range.clone())
+                } else {
+                    end
+                };
+                let width = self.x_at(end) - self.x_at(range.start);
+                CairoLine {
+                    range,
+                    content_end: end,
+                    width,
+                    offset: 0.0,
+                    space_width: 0.0,
+                    baseline,
+                    ascent,
+                    descent,
+                    height,
+                    clusters: Vec::new(),
+                }
+            })
+            .collect()
+    }
 
-        let start = 0;
-        let start_bounds = match self.get_grapheme_boundaries(start) {
-            Some(bounds) => bounds,
-            None => return HitTestPoint::default(),
+    /// Place the lines within the width of the layout.
+    fn align_lines(&mut self, max_width: f64, alignment: TextAlignment) {
+        let width = if max_width.is_finite() {
+            max_width
+        } else {
+            self.width()
         };
+        let mut rtl = false;
+        let mut paragraph_start = true;
+        for line in &mut self.lines {
+            if paragraph_start {
+                rtl = is_rtl(&self.text[line.range.start..]);
+            }
+            let ends_paragraph = line.range.end == self.text.len()
+                || trim_newline(&self.text, line.range.clone()) < line.range.end;
+            let spaces = count_spaces(&self.text[line.range.start..line.content_end]);
+            let (offset, space_width) =
+                align(alignment, rtl, ends_paragraph, line.width, width, spaces);
+            line.offset = offset;
+            line.space_width = space_width;
+            paragraph_start = ends_paragraph;
+        }
+    }
 
-        // first test beyond ends
-        if point.x > end_bounds.trailing {
-            let mut res = HitTestPoint::default();
-            res.metrics.text_position = self.text.len();
-            return res;
+    /// Place the clusters of each line from left to right, after the line's
+    /// offset, with its runs in the order of their bidi levels.
+    fn place_clusters(&mut self) {
+        let mut lines = std::mem::take(&mut self.lines);
+        for line in &mut lines {
+            let end = trim_newline(&self.text, line.range.clone());
+            let runs: Vec<usize> = (0..self.runs.len())
+                .filter(|&i| {
+                    self.runs[i].range.start < end && line.range.start < self.runs[i].range.end
+                })
+                .collect();
+            let levels: Vec<u8> = runs.iter().map(|&i| self.runs[i].level).collect();
+            let mut x = line.offset;
+            for visual in visual_order(&levels) {
+                let run = &self.runs[runs[visual]];
+                let mut clusters: Vec<usize> = (0..run.clusters.len())
+                    .filter(|&i| (line.range.start..end).contains(&run.clusters[i].range.start))
+                    .collect();
+                if run.is_rtl() {
+                    clusters.reverse();
+                }
+                for cluster in clusters {
+                    let range = run.clusters[cluster].range.clone();
+                    let mut width = run.clusters[cluster].advance;
+                    if range.start < line.content_end {
+                        width += line.space_width * count_spaces(&self.text[range]) as f64;
+                    }
+                    line.clusters.push(PlacedCluster {
+                        run: runs[visual],
+                        cluster,
+                        x,
+                        width,
+                    });
+                    x += width;
+                }
+            }
         }
-        if point.x <= start_bounds.leading {
-            return HitTestPoint::default();
+        self.lines = lines;
+    }
+
+    fn cluster(&self, placed: &PlacedCluster) -> &Cluster {
+        &self.runs[placed.run].clusters[placed.cluster]
+    }
+
+    /// The offset of a text position in a placed cluster from the left of
+    /// the layout.
+    fn cluster_x(&self, placed: &PlacedCluster, text_position: usize) -> f64 {
+        let fraction = self.cluster_fraction(self.cluster(placed), text_position);
+        if self.runs[placed.run].is_rtl() {
+            placed.x + placed.width * (1.0 - fraction)
+        } else {
+            placed.x + placed.width * fraction
         }
+    }
 
-        // then test the beginning and end (common cases)
-        if let Some(hit) = point_x_in_grapheme(point.x, &start_bounds) {
-            return hit;
+    /// The offset of a text position in a line from the left of the layout:
+    /// the leading edge of its grapheme cluster, or the end of the line's
+    /// text for positions after it.
+    pub(crate) fn line_x(&self, line: &CairoLine, text_position: usize) -> f64 {
+        let placed = line
+            .clusters
+            .iter()
+            .find(|placed| self.cluster(placed).range.contains(&text_position));
+        if let Some(placed) = placed {
+            return self.cluster_x(placed, text_position);
         }
-        if let Some(hit) = point_x_in_grapheme(point.x, &end_bounds) {
-            return hit;
+        let last = line
+            .clusters
+            .iter()
+            .max_by_key(|placed| self.cluster(placed).range.start);
+        match last {
+            Some(last) => self.cluster_x(last, self.cluster(last).range.end),
+            None => line.offset,
         }
+    }
 
-        // Now that we know it's not beginning or end, begin binary search.
-        // Iterative style
-        let mut left = start;
-        let mut right = end;
-        loop {
-            // pick halfway point
-            let middle = left + ((right - left) / 2);
-
-            let grapheme_bounds = match self.get_grapheme_boundaries(middle) {
-                Some(bounds) => bounds,
-                None => return HitTestPoint::default(),
-            };
-
-            if let Some(hit) = point_x_in_grapheme(point.x, &grapheme_bounds) {
-                return hit;
-            }
-
-            // since it's not a hit, check if closer to start or finish
-            // and move the appropriate search boundary
-            if point.x < grapheme_bounds.leading {
-                right = middle;
-            } else if point.x > grapheme_bounds.trailing {
-                left = middle + 1;
-            } else {
-                unreachable!("hit_test_point conditional is exhaustive");
+    /// The glyphs of a line of the layout drawn at `pos`, from left to right.
+    pub(crate) fn line_glyphs(&self, line: &CairoLine, pos: Point) -> Vec<GlyphRun<'_>> {
+        let y = pos.y + line.baseline;
+        let mut glyph_runs: Vec<GlyphRun> = Vec::new();
+        for placed in &line.clusters {
+            let run = &self.runs[placed.run];
+            let x0 = pos.x + placed.x;
+            let mut x = x0;
+            let glyphs = run.glyphs[self.cluster(placed).glyphs.clone()]
+                .iter()
+                .map(|glyph| {
+                    let placed = Glyph {
+                        index: glyph.id.into(),
+                        x: x + glyph.x_offset,
+                        y: y + glyph.y_offset,
+                    };
+                    x += glyph.advance;
+                    placed
+                });
+            match glyph_runs.last_mut() {
+                Some(last) if std::ptr::eq(last.run, run) => {
+                    last.glyphs.extend(glyphs);
+                    last.x1 = x0 + placed.width;
+                }
+                _ => glyph_runs.push(GlyphRun {
+                    run,
+                    glyphs: glyphs.collect(),
+                    x0,
+                    x1: x0 + placed.width,
+                }),
             }
         }
+        glyph_runs
     }
 
-    fn hit_test_text_position(&self, text_position: usize) -> Option<HitTestTextPosition> {
-        // Using substrings, but now with unicode grapheme awareness
+    /// The line a text position is on. A position at a line break is on the
+    /// line after it.
+    fn line_at(&self, text_position: usize) -> &CairoLine {
+        self.lines
+            .iter()
+            .rev()
+            .find(|line| line.range.start <= text_position)
+            .unwrap_or(&self.lines[0])
+    }
+}
 
-        let text_len = self.text.len();
+impl TextLayout for CairoTextLayout {
+    fn width(&self) -> f64 {
+        self.lines.iter().map(|line| line.width).fold(0.0, f64::max)
+    }
 
-        if text_position == 0 {
-            return Some(HitTestTextPosition::default());
+    fn metrics(&self) -> TextLayoutMetrics {
+        let first = &self.lines[0];
+        let last = self.lines.last().unwrap();
+        TextLayoutMetrics {
+            height: self.lines.iter().map(|line| line.height).sum(),
+            ascent: first.ascent,
+            descent: last.descent,
+            line_gap: first.height - first.ascent - first.descent,
+            line_count: self.lines.len(),
         }
+    }
+
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn line_metric(&self, line_number: usize) -> Option<LineMetric> {
+        let line = self.lines.get(line_number)?;
+        Some(LineMetric {
+            start_offset: line.range.start,
+            end_offset: line.range.end,
+            baseline: line.baseline,
+            ascent: line.ascent,
+            height: line.height,
+        })
+    }
+
+    fn line_text(&self, line_number: usize) -> Option<&str> {
+        let line = self.lines.get(line_number)?;
+        Some(&self.text[line.range.clone()])
+    }
+
+    fn hit_test_point(&self, point: Point) -> HitTestPoint {
+        // internal logic is using grapheme clusters, but return the text position associated
+        // with the border of the grapheme cluster.
+
+        // find the line, by the bottom of each line
+        let first = &self.lines[0];
+        let is_inside_y = point.y >= first.baseline - first.ascent;
+        let line = self
+            .lines
+            .iter()
+            .find(|line| point.y < line.baseline - line.ascent + line.height);
+        let (line, is_inside_y) = match line {
+            Some(line) => (line, is_inside_y),
+            None => (self.lines.last().unwrap(), false),
+        };
+        let mut hit = self.hit_test_line(line, point.x);
+        hit.is_inside &= is_inside_y;
+        hit
+    }
+
+    fn hit_test_text_position(&self, text_position: usize) -> Option<HitTestTextPosition> {
+        let text_len = self.text.len();
 
         if text_position as usize >= text_len {
+            let line = self.lines.last().unwrap();
             return Some(HitTestTextPosition {
                 point: Point {
-                    x: self.font.text_extents(&self.text).x_advance,
-                    y: 0.0,
+                    x: self.line_x(line, text_len),
+                    y: line.baseline,
                 },
                 metrics: HitTestMetrics {
                     text_position: text_len,
@@ -196,32 +650,88 @@ impl TextLayout for CairoTextLayout {
             });
         }
 
-        // Already checked that text_position > 0 and text_position < count.
         // If text position is not at a grapheme boundary, use the text position of current
         // grapheme cluster. But return the original text position
         // Use the indices (byte offset, which for our purposes = utf8 code units).
         let grapheme_indices = UnicodeSegmentation::grapheme_indices(self.text.as_str(), true)
             .take_while(|(byte_idx, _s)| text_position >= *byte_idx);
+        let byte_idx = grapheme_indices
+            .last()
+            .map(|(byte_idx, _s)| byte_idx)
+            .unwrap_or(0);
+        let line = self.line_at(byte_idx);
+
+        Some(HitTestTextPosition {
+            point: Point {
+                x: self.line_x(line, byte_idx),
+                y: line.baseline,
+            },
+            metrics: HitTestMetrics { text_position },
+        })
+    }
+}
 
-        if let Some((byte_idx, _s)) = grapheme_indices.last() {
-            let point_x = self.font.text_extents(&self.text[0..byte_idx]).x_advance;
+impl CairoTextLayout {
+    /// Find the text position in a line closest to an x offset.
+    fn hit_test_line(&self, line: &CairoLine, x: f64) -> HitTestPoint {
+        let graphemes = self.line_graphemes(line);
+        let mut res = HitTestPoint::default();
+        let (first, last) = match (graphemes.first(), graphemes.last()) {
+            (Some(first), Some(last)) => (first, last),
+            // null case
+            _ => {
+                res.metrics.text_position = line.range.start;
+                return res;
+            }
+        };
 
-            Some(HitTestTextPosition {
-                point: Point { x: point_x, y: 0.0 },
-                metrics: HitTestMetrics { text_position },
-            })
-        } else {
-            // iterated to end boundary
-            Some(HitTestTextPosition {
-                point: Point {
-                    x: self.font.text_extents(&self.text).x_advance,
-                    y: 0.0,
-                },
-                metrics: HitTestMetrics {
-                    text_position: text_len,
-                },
-            })
+        // first test beyond ends, which are the positions at the edges
+        if x <= first.leading.min(first.trailing) {
+            res.metrics.text_position = if first.leading <= first.trailing {
+                first.curr_idx
+            } else {
+                first.next_idx
+            };
+            return res;
+        }
+        if x > last.leading.max(last.trailing) {
+            res.metrics.text_position = if last.leading <= last.trailing {
+                last.next_idx
+            } else {
+                last.curr_idx
+            };
+            return res;
         }
+
+        graphemes
+            .iter()
+            .find_map(|bounds| point_x_in_grapheme(x, bounds))
+            .unwrap_or(res)
+    }
+}
+
+impl CairoRun {
+    fn is_rtl(&self) -> bool {
+        self.level % 2 == 1
+    }
+}
+
+/// Scale a face to a font size.
+fn scaled_font(face: &FontFace, size: f64) -> ScaledFont {
+    let font_matrix = scale_matrix(size);
+    let ctm = scale_matrix(1.0);
+    let options = FontOptions::default();
+    ScaledFont::new(face, &font_matrix, &ctm, &options)
+}
+
+/// Another reference to a face.
+///
+/// Without glib, cloning a `FontFace` doesn't add a reference to it, so the
+/// face would be destroyed once for each clone.
+fn share(face: &FontFace) -> FontFace {
+    unsafe {
+        let raw = cairo_sys::cairo_font_face_reference(face.to_raw_none());
+        FontFace::from_raw_full(raw)
     }
 }
 
@@ -494,7 +1004,7 @@ mod test {
     }
 
     #[test]
-    #[cfg(target_os = "linux")]
+    #[cfg(all(target_os = "linux", feature = "fontconfig"))]
     fn test_hit_test_point_basic_0() {
         let mut text_layout = CairoText::new();
 
@@ -506,8 +1016,8 @@ mod test {
             .new_text_layout(&font, "piet text!")
             .build()
             .unwrap();
-        println!("text pos 4: {:?}", layout.hit_test_text_position(4)); // 23.0
-        println!("text pos 5: {:?}", layout.hit_test_text_position(5)); // 27.0
+        println!("text pos 4: {:?}", layout.hit_test_text_position(4)); // 23.04
+        println!("text pos 5: {:?}", layout.hit_test_text_position(5)); // 26.85
 
         // test hit test point
         // all inside
@@ -525,13 +1035,13 @@ mod test {
         assert_eq!(pt.metrics.text_position, 5);
 
         // outside
-        println!("layout_width: {:?}", layout.width()); // 56.0
+        println!("layout_width: {:?}", layout.width()); // 55.35
 
-        let pt = layout.hit_test_point(Point::new(56.0, 0.0));
+        let pt = layout.hit_test_point(Point::new(55.0, 0.0));
         assert_eq!(pt.metrics.text_position, 10); // last text position
         assert_eq!(pt.is_inside, true);
 
-        let pt = layout.hit_test_point(Point::new(57.0, 0.0));
+        let pt = layout.hit_test_point(Point::new(56.0, 0.0));
         assert_eq!(pt.metrics.text_position, 10); // last text position
         assert_eq!(pt.is_inside, false);
 
@@ -650,7 +1160,7 @@ mod test {
     }
 
     #[test]
-    #[cfg(target_os = "linux")]
+    #[cfg(all(target_os = "linux", feature = "fontconfig"))]
     fn test_hit_test_point_complex_0() {
         // Notes on this input:
         // 6 code points
@@ -668,7 +1178,7 @@ mod test {
         //println!("text pos 2: {:?}", layout.hit_test_text_position(2)); // 6.99999999
         //println!("text pos 9: {:?}", layout.hit_test_text_position(9)); // 24.0
         //println!("text pos 10: {:?}", layout.hit_test_text_position(10)); // 32.0
-        //println!("text pos 14: {:?}", layout.hit_test_text_position(14)); // 39.0, line width
+        //println!("text pos 14: {:?}", layout.hit_test_text_position(14)); // 44.0, line width
 
         let pt = layout.hit_test_point(Point::new(2.0, 0.0));
         assert_eq!(pt.metrics.text_position, 0);
@@ -690,12 +1200,13 @@ mod test {
         assert_eq!(pt.metrics.text_position, 10);
         let pt = layout.hit_test_point(Point::new(32.0, 0.0));
         assert_eq!(pt.metrics.text_position, 10);
+        // 𝐇 falls back to a math font
         let pt = layout.hit_test_point(Point::new(35.5, 0.0));
-        assert_eq!(pt.metrics.text_position, 14);
-        let pt = layout.hit_test_point(Point::new(38.0, 0.0));
-        assert_eq!(pt.metrics.text_position, 14);
+        assert_eq!(pt.metrics.text_position, 10);
         let pt = layout.hit_test_point(Point::new(40.0, 0.0));
         assert_eq!(pt.metrics.text_position, 14);
+        let pt = layout.hit_test_point(Point::new(43.0, 0.0));
+        assert_eq!(pt.metrics.text_position, 14);
     }
 
     #[test]
@@ -806,4 +1317,366 @@ mod test {
         let pt = layout.hit_test_point(Point::new(27.0, 0.0));
         assert_eq!(pt.metrics.text_position, 6);
     }
+
+    #[test]
+    fn test_hit_test_across_attribute_runs() {
+        let mut text = CairoText::new();
+        let font = text.new_font_by_name("sans-serif", 12.0).build().unwrap();
+
+        let plain_head = text.new_text_layout(&font, "piet ").build().unwrap();
+        let bold_head = text
+            .new_text_layout(&font, "piet ")
+            .default_attribute(FontWeight::BOLD)
+            .build()
+            .unwrap();
+        let tail = text.new_text_layout(&font, "text!").build().unwrap();
+        let layout = text
+            .new_text_layout(&font, "piet text!")
+            .range_attribute(..5, FontWeight::BOLD)
+            .build()
+            .unwrap();
+        assert!(bold_head.width() > plain_head.width());
+
+        assert_close_to(
+            layout.hit_test_text_position(5).unwrap().point.x,
+            bold_head.width(),
+            1e-6,
+        );
+        assert_close_to(layout.width(), bold_head.width() + tail.width(), 1e-6);
+
+        // A point just after position 7 is in the second run.
+        let x = layout.hit_test_text_position(7).unwrap().point.x;
+        let hit = layout.hit_test_point(Point::new(x + 0.1, 0.0));
+        assert_eq!(hit.metrics.text_position, 7);
+        assert!(hit.is_inside);
+    }
+
+    #[test]
+    fn test_wrapped_lines() {
+        let mut text = CairoText::new();
+        let font = text.new_font_by_name("sans-serif", 12.0).build().unwrap();
+
+        let piet = text.new_text_layout(&font, "piet").build().unwrap();
+        let max_width = piet.width() * 1.5;
+        let layout = text
+            .new_text_layout(&font, "piet text\nmost best")
+            .max_width(max_width)
+            .build()
+            .unwrap();
+
+        assert_eq!(layout.line_count(), 4);
+        assert_eq!(layout.line_text(0), Some("piet "));
+        assert_eq!(layout.line_text(1), Some("text\n"));
+        assert_eq!(layout.line_text(2), Some("most "));
+        assert_eq!(layout.line_text(3), Some("best"));
+        assert_eq!(layout.line_text(4), None);
+        assert!(layout.width() >= piet.width() && layout.width() <= max_width);
+
+        let unwrapped = text
+            .new_text_layout(&font, "piet text most best")
+            .build()
+            .unwrap();
+        assert_eq!(unwrapped.line_count(), 1);
+    }
+
+    #[test]
+    fn test_hit_test_lines() {
+        let mut text = CairoText::new();
+        let font = text.new_font_by_name("sans-serif", 12.0).build().unwrap();
+
+        let layout = text.new_text_layout(&font, "piet\ntext").build().unwrap();
+        assert_eq!(layout.line_count(), 2);
+
+        // the second line starts at the left edge, one line down
+        let first = layout.hit_test_text_position(0).unwrap().point;
+        let second = layout.hit_test_text_position(5).unwrap().point;
+        assert_eq!(second.x, 0.0);
+        assert!(second.y > first.y);
+        assert_eq!(first.y, 0.0);
+        let end = layout.hit_test_text_position(9).unwrap();
+        assert_eq!(end.point.y, second.y);
+
+        // a point past the end of the first line is before its newline
+        let hit = layout.hit_test_point(Point::new(1000.0, 0.0));
+        assert_eq!(hit.metrics.text_position, 4);
+        assert!(!hit.is_inside);
+
+        let hit = layout.hit_test_point(Point::new(0.1, second.y));
+        assert_eq!(hit.metrics.text_position, 5);
+        assert!(hit.is_inside);
+
+        // points above and below the text are on the first and last lines
+        let hit = layout.hit_test_point(Point::new(0.0, -1000.0));
+        assert_eq!(hit.metrics.text_position, 0);
+        assert!(!hit.is_inside);
+        let hit = layout.hit_test_point(Point::new(1000.0, 1000.0));
+        assert_eq!(hit.metrics.text_position, 9);
+        assert!(!hit.is_inside);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut text = CairoText::new();
+        let font = text.new_font_by_name("sans-serif", 12.0).build().unwrap();
+
+        let font_metrics = font.metrics();
+        assert!(font_metrics.ascent > 0.0 && font_metrics.descent > 0.0);
+        assert!(font_metrics.cap_height > font_metrics.x_height);
+        assert!(font_metrics.x_height > 0.0);
+
+        let layout = text.new_text_layout(&font, "piet\ntext").build().unwrap();
+        let metrics = layout.metrics();
+        assert_eq!(metrics.line_count, 2);
+        assert_eq!(metrics.ascent, font_metrics.ascent);
+        assert_eq!(metrics.descent, font_metrics.descent);
+        assert_close_to(metrics.line_gap, font_metrics.line_gap, 1e-6);
+        assert_close_to(metrics.height, font_metrics.line_height() * 2.0, 1e-6);
+
+        let first = layout.line_metric(0).unwrap();
+        let second = layout.line_metric(1).unwrap();
+        assert_eq!((first.start_offset, first.end_offset), (0, 5));
+        assert_eq!((second.start_offset, second.end_offset), (5, 9));
+        assert_eq!(first.baseline, 0.0);
+        assert_close_to(second.baseline, first.height, 1e-6);
+        assert_eq!(layout.line_metric(2), None);
+    }
+
+    #[test]
+    #[cfg(feature = "fontconfig")]
+    fn test_underline_metrics() {
+        let mut text = CairoText::new();
+        let family = text.load_font(piet_test::SANS_SERIF_FONT).unwrap();
+        // At a size of one unit per em, the metrics are those of the `post`
+        // table.
+        let font = text.new_font_by_name(&family, 2048.0).build().unwrap();
+        let metrics = font.metrics();
+        assert_close_to(metrics.underline_position, 40.0, 1e-6);
+        assert_close_to(metrics.underline_thickness, 90.0, 1e-6);
+    }
+
+    #[test]
+    fn test_font_properties() {
+        let mut text = CairoText::new();
+        let font = text
+            .new_font_by_name("sans-serif", 12.0)
+            .weight(FontWeight::BOLD)
+            .style(FontStyle::Oblique)
+            .stretch(FontStretch::Condensed)
+            .build()
+            .unwrap();
+        assert_eq!(font.style.weight, FontWeight::BOLD);
+        assert_eq!(font.style.style, FontStyle::Oblique);
+        assert_eq!(font.style.stretch, FontStretch::Condensed);
+
+        // bold glyphs are wider, even when they're synthesized
+        let regular = text.new_font_by_name("sans-serif", 12.0).build().unwrap();
+        let regular = text.new_text_layout(&regular, "piet text").build().unwrap();
+        let bold = text.new_text_layout(&font, "piet text").build().unwrap();
+        assert!(bold.width() > regular.width());
+    }
+
+    #[test]
+    #[cfg(feature = "fontconfig")]
+    fn test_load_font() {
+        let mut text = CairoText::new();
+        let family = text.load_font(piet_test::SANS_SERIF_FONT).unwrap();
+        assert_eq!(family, "DejaVu Sans");
+
+        // family names match case-insensitively, and bold is synthesized
+        let regular = text.new_font_by_name("dejavu sans", 12.0).build().unwrap();
+        let bold = text
+            .new_font_by_name(&family, 12.0)
+            .weight(FontWeight::BOLD)
+            .build()
+            .unwrap();
+        let regular = text.new_text_layout(&regular, "piet text").build().unwrap();
+        let bold = text.new_text_layout(&bold, "piet text").build().unwrap();
+        assert!(bold.width() > regular.width());
+
+        let err = text.load_font(b"not a font").unwrap_err();
+        assert!(matches!(err.kind(), piet::ErrorKind::InvalidFont(_)));
+        // a collection without fonts
+        let err = text.load_font(b"ttcf\0\x01\0\0\0\0\0\0").unwrap_err();
+        assert!(matches!(err.kind(), piet::ErrorKind::InvalidFont(_)));
+
+        // loading a font again gives the same family
+        let again = text.load_font(piet_test::SANS_SERIF_FONT).unwrap();
+        assert_eq!(again, family);
+        assert_eq!(text.fonts.borrow().loaded_count(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "fontconfig")]
+    fn test_font_families() {
+        let mut text = CairoText::new();
+        let family = text.load_font(piet_test::SERIF_FONT).unwrap();
+        assert!(text.font_families().contains(&family));
+        assert!(text.has_font_family("dejavu serif"));
+        assert!(text.has_font_family("monospace"));
+        assert!(!text.has_font_family("No Such Family"));
+
+        let font = text.new_font_by_name(&family, 12.0).build().unwrap();
+        assert_eq!(font.family_name(), family);
+        // generic and missing families resolve to installed families
+        for name in &["sans-serif", "system-ui", "No Such Family"] {
+            let font = text.new_font_by_name(name, 12.0).build().unwrap();
+            assert!(text.has_font_family(font.family_name()));
+            assert_ne!(font.family_name(), *name);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "fontconfig")]
+    fn test_font_fallback() {
+        let mut text = CairoText::new();
+        let serif = text.load_font(piet_test::SERIF_FONT).unwrap();
+        let sans = text.load_font(piet_test::SANS_SERIF_FONT).unwrap();
+        let font = text.new_font_by_name(&serif, 12.0).build().unwrap();
+
+        // DejaVu Serif has no Arabic, which falls back to DejaVu Sans, along
+        // with the space after it, which is left-to-right, so it's shaped in
+        // a run of its own.
+        let input = "abc مرحبا def";
+        let layout = text.new_text_layout(&font, input).build().unwrap();
+        let ranges: Vec<_> = layout.runs.iter().map(|run| run.range.clone()).collect();
+        assert_eq!(ranges, [0..4, 4..14, 14..15, 15..18]);
+
+        // it's measured as if the font had been asked for
+        let explicit = text
+            .new_text_layout(&font, input)
+            .range_attribute(4..15, TextAttribute::FontFamily(sans))
+            .build()
+            .unwrap();
+        assert_close_to(layout.width(), explicit.width(), 1e-6);
+        for &position in &[4, 10, 15, 18] {
+            let x = layout.hit_test_text_position(position).unwrap().point.x;
+            let expected = explicit.hit_test_text_position(position).unwrap().point.x;
+            assert_close_to(x, expected, 1e-6);
+        }
+
+        // nothing has a glyph for a private use character
+        let layout = text.new_text_layout(&font, "a\u{10fffd}b").build().unwrap();
+        assert_eq!(layout.runs.len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "fontconfig")]
+    fn test_shaping() {
+        let mut text = CairoText::new();
+        let sans = text.load_font(piet_test::SANS_SERIF_FONT).unwrap();
+        let font = text.new_font_by_name(&sans, 12.0).build().unwrap();
+        let width =
+            |text: &mut CairoText, s| text.new_text_layout(&font, s).build().unwrap().width();
+
+        // pairs are kerned
+        let kerned = width(&mut text, "AV");
+        assert!(kerned < width(&mut text, "A") + width(&mut text, "V"));
+
+        // a ligature is one glyph, with positions inside it spread evenly
+        let layout = text.new_text_layout(&font, "fi").build().unwrap();
+        assert_eq!(layout.runs[0].glyphs.len(), 1);
+        let middle = layout.hit_test_text_position(1).unwrap().point.x;
+        assert_close_to(middle, layout.width() / 2.0, 1e-6);
+        let hit = layout.hit_test_point(Point::new(middle - 0.1, 0.0));
+        assert_eq!(hit.metrics.text_position, 1);
+
+        // Arabic letters join, so a letter in a word isn't in its isolated form
+        let isolated = text.new_text_layout(&font, "\u{645}").build().unwrap();
+        let word = text.new_text_layout(&font, "مرحبا").build().unwrap();
+        let first = word.runs[0].glyphs.last().unwrap();
+        assert_ne!(first.id, isolated.runs[0].glyphs[0].id);
+
+        // right-to-left text runs from right to left between the words
+        // around it
+        let layout = text
+            .new_text_layout(&font, "abc مرحبا def")
+            .build()
+            .unwrap();
+        let x = |position| layout.hit_test_text_position(position).unwrap().point.x;
+        assert!(x(3) < x(12) && x(12) < x(8) && x(8) < x(4));
+        assert_close_to(x(4), x(14), 1e-6);
+        for &position in &[6, 8, 10, 12] {
+            let hit = layout.hit_test_point(Point::new(x(position) + 0.1, 0.0));
+            assert_eq!(hit.metrics.text_position, position);
+            assert!(hit.is_inside);
+        }
+    }
+
+    #[test]
+    fn test_alignment() {
+        let mut text = CairoText::new();
+        let font = text.new_font_by_name("sans-serif", 12.0).build().unwrap();
+
+        let short = text.new_text_layout(&font, "pi").build().unwrap();
+        let build = |text: &mut CairoText, alignment| {
+            text.new_text_layout(&font, "piet text\npi")
+                .alignment(alignment)
+                .build()
+                .unwrap()
+        };
+
+        let layout = build(&mut text, TextAlignment::End);
+        let width = layout.width();
+        let x = layout.hit_test_text_position(10).unwrap().point.x;
+        assert_close_to(x, width - short.width(), 1e-6);
+        let hit = layout.hit_test_point(Point::new(x + 0.1, 20.0));
+        assert_eq!(hit.metrics.text_position, 10);
+        assert_eq!(layout.hit_test_text_position(0).unwrap().point.x, 0.0);
+
+        let layout = build(&mut text, TextAlignment::Center);
+        let x = layout.hit_test_text_position(10).unwrap().point.x;
+        assert_close_to(x, (width - short.width()) / 2.0, 1e-6);
+
+        // right-to-left paragraphs start on the right, and run to the left
+        let layout = text
+            .new_text_layout(&font, "\u{5e9}\u{5dc}\u{5d5}\u{5dd}")
+            .max_width(100.0)
+            .build()
+            .unwrap();
+        assert_close_to(
+            layout.hit_test_text_position(0).unwrap().point.x,
+            100.0,
+            1e-6,
+        );
+        assert_close_to(
+            layout.hit_test_text_position(8).unwrap().point.x,
+            100.0 - layout.width(),
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn test_justified() {
+        let mut text = CairoText::new();
+        let font = text.new_font_by_name("sans-serif", 12.0).build().unwrap();
+
+        let layout = text
+            .new_text_layout(&font, "a b c d e f")
+            .max_width(40.0)
+            .alignment(TextAlignment::Justified)
+            .build()
+            .unwrap();
+        assert!(layout.line_count() > 1);
+
+        // full lines reach the width, at the start of their last word
+        let first_end = layout.line_metric(0).unwrap().end_offset;
+        let last_word = layout.hit_test_text_position(first_end - 2).unwrap();
+        let last_letter = &layout.text[first_end - 2..first_end - 1];
+        let end = last_word.point.x
+            + text
+                .new_text_layout(&font, last_letter)
+                .build()
+                .unwrap()
+                .width();
+        assert_close_to(end, 40.0, 1e-6);
+
+        // the last line isn't stretched
+        let last = layout.line_metric(layout.line_count() - 1).unwrap();
+        let plain = text
+            .new_text_layout(&font, &layout.text[last.start_offset..])
+            .build()
+            .unwrap();
+        let end = layout.hit_test_text_position(layout.text.len()).unwrap();
+        assert_close_to(end.point.x, plain.width(), 1e-6);
+    }
 }
@@ -0,0 +1,640 @@
+//! Finding font faces, among loaded fonts and installed ones.
+//!
+//! Faces are made with FreeType from the data of font files, so that fonts
+//! loaded from memory and installed fonts are drawn the same way. Installed
+//! fonts are found with fontconfig, which also picks the fallback faces for
+//! characters a face doesn't have.
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::os::raw::{c_int, c_void};
+use std::ptr::null_mut;
+use std::rc::Rc;
+
+use cairo::{FontFace, FtSynthesize};
+use fontconfig_sys::constants::{
+    FC_CHARSET, FC_FAMILY, FC_FILE, FC_INDEX, FC_SLANT, FC_SLANT_ITALIC, FC_SLANT_OBLIQUE,
+    FC_SLANT_ROMAN, FC_WEIGHT, FC_WIDTH,
+};
+use fontconfig_sys::{
+    FcChar32, FcChar8, FcCharSetAddChar, FcCharSetCreate, FcCharSetDestroy, FcConfigSubstitute,
+    FcDefaultSubstitute, FcFontList, FcFontMatch, FcFontSetDestroy, FcMatchPattern, FcObjectSetAdd,
+    FcObjectSetCreate, FcObjectSetDestroy, FcPattern, FcPatternAddCharSet, FcPatternAddInteger,
+    FcPatternAddString, FcPatternCreate, FcPatternDestroy, FcPatternGetInteger, FcPatternGetString,
+    FcResultMatch, FcResultNoMatch,
+};
+use piet::{
+    font_family_name, Error, ErrorKind, FontStretch, FontStyle, FontWeight, GenericFamily,
+    TextStyle,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::share;
+
+/// The fonts loaded into a `CairoText`.
+#[derive(Default)]
+pub(crate) struct FontCollection {
+    loaded: Vec<LoadedFace>,
+    /// The loaded font files, by their data.
+    files: HashMap<DataKey, Vec<LoadedFile>>,
+}
+
+/// A loaded font file, and the family name of its first face.
+struct LoadedFile {
+    data: Rc<Vec<u8>>,
+    family: String,
+}
+
+/// A face to draw text with, and the font data to shape text with.
+pub(crate) struct Face {
+    pub(crate) cairo: FontFace,
+    pub(crate) font: Rc<FontData>,
+    /// Whether cairo draws the face bolder than it is, which also makes its
+    /// glyphs wider.
+    pub(crate) embolden: bool,
+}
+
+/// A face from loaded font data.
+struct LoadedFace {
+    family: String,
+    properties: Properties,
+    font: Rc<FontData>,
+    /// The cairo faces made so far, by the styles they synthesize.
+    faces: RefCell<Vec<(Synthesis, FontFace)>>,
+}
+
+/// The data of a face in a font file, parsed once.
+pub(crate) struct FontData {
+    /// The parsed face, which borrows `data`. It's declared first, so it's
+    /// dropped before the data.
+    face: rustybuzz::Face<'static>,
+    data: Rc<Vec<u8>>,
+    index: u32,
+}
+
+/// The weight, slant and width of a face, or those asked for.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Properties {
+    weight: FontWeight,
+    style: FontStyle,
+    stretch: FontStretch,
+}
+
+/// Styles a face doesn't have, which cairo draws by changing its glyphs.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Synthesis {
+    bold: bool,
+    oblique: bool,
+}
+
+/// A key for the faces of installed fonts: a family, its properties, and
+/// for fallback faces, a character the face should have.
+type SystemKey = (String, Properties, Option<char>);
+
+/// A key for font data: the hash and length of the data.
+type DataKey = (u64, usize);
+
+/// A key for the faces of font files: a path, and the index of the face in
+/// the file.
+type FileKey = (String, u32);
+
+thread_local! {
+    static FREETYPE: freetype::Library =
+        freetype::Library::init().expect("FreeType failed to initialize");
+    /// Faces of installed fonts, with their families, which are slow to
+    /// find.
+    static SYSTEM_FACES: RefCell<HashMap<SystemKey, (String, Face)>> =
+        RefCell::new(HashMap::new());
+    /// Faces of installed font files, which are slow to load.
+    static FILE_FACES: RefCell<HashMap<FileKey, Rc<LoadedFace>>> = RefCell::new(HashMap::new());
+}
+
+/// The key for the FreeType face of a cairo face, in its user data.
+static FT_FACE_KEY: cairo_sys::cairo_user_data_key_t =
+    cairo_sys::cairo_user_data_key_t { unused: 0 };
+
+// cairo-sys doesn't bind cairo's FreeType font backend.
+extern "C" {
+    fn cairo_ft_font_face_create_for_ft_face(
+        face: freetype::ffi::FT_Face,
+        load_flags: c_int,
+    ) -> *mut cairo_sys::cairo_font_face_t;
+}
+
+impl FontCollection {
+    /// Load the faces in the data of a font file, returning the family name
+    /// of the first.
+    ///
+    /// Loading the same data again only returns the family name.
+    pub(crate) fn load(&mut self, data: &[u8]) -> Result<String, Error> {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        let key = (hasher.finish(), data.len());
+        if let Some(files) = self.files.get(&key) {
+            if let Some(file) = files.iter().find(|file| file.data[..] == *data) {
+                return Ok(file.family.clone());
+            }
+        }
+
+        let data = Rc::new(data.to_vec());
+        let count = match ttf_parser::fonts_in_collection(&data) {
+            Some(0) => {
+                let reason = "the collection has no fonts".into();
+                return Err(Error::new(ErrorKind::InvalidFont(reason)));
+            }
+            Some(count) => count,
+            None => 1,
+        };
+        let mut faces = Vec::new();
+        for index in 0..count {
+            let family = font_family_name(&data, index)?;
+            let font = Rc::new(FontData::new(data.clone(), index)?);
+            faces.push(LoadedFace {
+                family,
+                properties: Properties::of_face(font.face()),
+                font,
+                faces: RefCell::new(Vec::new()),
+            });
+        }
+        let family = faces[0].family.clone();
+        self.files.entry(key).or_default().push(LoadedFile {
+            data,
+            family: family.clone(),
+        });
+        // Later fonts are found first, so loading a font replaces an
+        // earlier one with the same name and properties.
+        self.loaded.splice(0..0, faces.into_iter().rev());
+        Ok(family)
+    }
+
+    /// The number of loaded faces.
+    #[cfg(test)]
+    pub(crate) fn loaded_count(&self) -> usize {
+        self.loaded.len()
+    }
+
+    /// The names of the loaded and installed families, sorted.
+    pub(crate) fn families(&self) -> Vec<String> {
+        let mut families = system_families();
+        families.extend(self.loaded.iter().map(|face| face.family.clone()));
+        families.sort();
+        families.dedup();
+        families
+    }
+
+    /// The face for a style.
+    pub(crate) fn face(&self, style: &TextStyle) -> Result<Face, Error> {
+        self.resolve(style).map(|(_, face)| face)
+    }
+
+    /// The face for a style, with the name of its family: the closest match
+    /// among loaded fonts of its family, or else the installed font that
+    /// fontconfig picks.
+    pub(crate) fn resolve(&self, style: &TextStyle) -> Result<(String, Face), Error> {
+        let wanted = Properties::of_style(style);
+        let best = self
+            .loaded
+            .iter()
+            .filter(|face| face.family.eq_ignore_ascii_case(&style.family))
+            .min_by_key(|face| face.properties.distance(wanted));
+        match best {
+            Some(loaded) => Ok((loaded.family.clone(), loaded.face(wanted)?)),
+            None => system_face(&style.family, wanted, None),
+        }
+    }
+
+    /// Split text in a style into ranges drawn with the same face.
+    ///
+    /// Text is drawn with the face for the style where it has glyphs for
+    /// the text, and with fallback faces elsewhere: loaded faces of any
+    /// family, and then the installed face fontconfig picks for the missing
+    /// character. A grapheme cluster is kept in one face. Spaces and
+    /// punctuation stay in the face of the text before them, so they don't
+    /// split runs of fallback text.
+    pub(crate) fn itemize(
+        &self,
+        text: &str,
+        style: &TextStyle,
+    ) -> Result<Vec<(Range<usize>, Face)>, Error> {
+        let wanted = Properties::of_style(style);
+        let mut faces = vec![self.face(style)?];
+        let mut items: Vec<(Range<usize>, usize)> = Vec::new();
+        for (start, grapheme) in text.grapheme_indices(true) {
+            let covers = |face: &Face| {
+                grapheme
+                    .chars()
+                    .all(|c| is_ignorable(c) || has_glyph(&face.cairo, c))
+            };
+            let current = items.last().map(|&(_, face)| face).unwrap_or(0);
+            let neutral = !grapheme.chars().any(char::is_alphanumeric);
+            let face = if neutral && covers(&faces[current]) {
+                current
+            } else if let Some(face) = faces.iter().position(covers) {
+                face
+            } else {
+                match self.fallback(grapheme, &style.family, wanted) {
+                    Some(face) if covers(&face) => {
+                        faces.push(face);
+                        faces.len() - 1
+                    }
+                    // Nothing has the glyphs, so the text is drawn with
+                    // the missing glyph of the style's face.
+                    _ => 0,
+                }
+            };
+            let end = start + grapheme.len();
+            match items.last_mut() {
+                Some((range, last)) if *last == face => range.end = end,
+                _ => items.push((start..end, face)),
+            }
+        }
+        if items.is_empty() {
+            items.push((0..0, 0));
+        }
+        Ok(items
+            .into_iter()
+            .map(|(range, face)| (range, faces[face].clone()))
+            .collect())
+    }
+
+    /// A face for a grapheme cluster the face for a style doesn't have.
+    fn fallback(&self, grapheme: &str, family: &str, wanted: Properties) -> Option<Face> {
+        let best = self
+            .loaded
+            .iter()
+            .filter(|face| {
+                grapheme
+                    .chars()
+                    .all(|c| is_ignorable(c) || face.has_glyph(c))
+            })
+            .min_by_key(|face| face.properties.distance(wanted));
+        if let Some(loaded) = best {
+            return loaded.face(wanted).ok();
+        }
+        let c = grapheme.chars().find(|&c| !is_ignorable(c))?;
+        system_face(family, wanted, Some(c))
+            .ok()
+            .map(|(_, face)| face)
+    }
+}
+
+impl Clone for Face {
+    fn clone(&self) -> Face {
+        Face {
+            cairo: share(&self.cairo),
+            font: self.font.clone(),
+            embolden: self.embolden,
+        }
+    }
+}
+
+impl Face {
+    /// The distance from the baseline down to the top of an underline, and
+    /// its thickness, at a size, from the `post` table of the face.
+    pub(crate) fn underline(&self, size: f64) -> Option<(f64, f64)> {
+        let face = self.font.face();
+        let metrics = face.underline_metrics()?;
+        let scale = size / f64::from(face.units_per_em());
+        Some((
+            -f64::from(metrics.position) * scale,
+            f64::from(metrics.thickness) * scale,
+        ))
+    }
+}
+
+impl LoadedFace {
+    fn face(&self, wanted: Properties) -> Result<Face, Error> {
+        let synthesis = Synthesis::new(self.properties, wanted);
+        let mut faces = self.faces.borrow_mut();
+        let cairo = match faces.iter().find(|(s, _)| *s == synthesis) {
+            Some((_, face)) => share(face),
+            None => {
+                let face = cairo_face(self.font.data.clone(), self.font.index, synthesis)?;
+                faces.push((synthesis, share(&face)));
+                face
+            }
+        };
+        Ok(Face {
+            cairo,
+            font: self.font.clone(),
+            embolden: synthesis.bold,
+        })
+    }
+
+    fn has_glyph(&self, c: char) -> bool {
+        self.font.face().glyph_index(c).is_some()
+    }
+}
+
+impl FontData {
+    fn new(data: Rc<Vec<u8>>, index: u32) -> Result<FontData, Error> {
+        let face = ttf_parser::Face::from_slice(&data, index)
+            .map_err(|e| Error::new(ErrorKind::InvalidFont(e.to_string())))?;
+        let face = rustybuzz::Face::from_face(face)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidFont("the face can't be shaped".into())))?;
+        // The face borrows the data, which is on the heap, is never changed,
+        // and is kept alive by the `Rc` until after the face is dropped.
+        let face =
+            unsafe { std::mem::transmute::<rustybuzz::Face, rustybuzz::Face<'static>>(face) };
+        Ok(FontData { face, data, index })
+    }
+
+    /// The parsed face, to shape text with and to read its tables.
+    pub(crate) fn face(&self) -> &rustybuzz::Face<'_> {
+        &self.face
+    }
+}
+
+impl Properties {
+    fn of_style(style: &TextStyle) -> Properties {
+        Properties {
+            weight: style.weight,
+            style: style.style,
+            stretch: style.stretch,
+        }
+    }
+
+    /// The properties of a face, from its OS/2 table.
+    fn of_face(face: &ttf_parser::Face) -> Properties {
+        use ttf_parser::{Style, Width};
+
+        let style = match face.style() {
+            Style::Normal => FontStyle::Regular,
+            Style::Italic => FontStyle::Italic,
+            Style::Oblique => FontStyle::Oblique,
+        };
+        let stretch = match face.width() {
+            Width::UltraCondensed => FontStretch::UltraCondensed,
+            Width::ExtraCondensed => FontStretch::ExtraCondensed,
+            Width::Condensed => FontStretch::Condensed,
+            Width::SemiCondensed => FontStretch::SemiCondensed,
+            Width::Normal => FontStretch::Normal,
+            Width::SemiExpanded => FontStretch::SemiExpanded,
+            Width::Expanded => FontStretch::Expanded,
+            Width::ExtraExpanded => FontStretch::ExtraExpanded,
+            Width::UltraExpanded => FontStretch::UltraExpanded,
+        };
+        Properties {
+            weight: FontWeight::new(face.weight().to_number()),
+            style,
+            stretch,
+        }
+    }
+
+    /// How far a face is from the properties wanted, as a key to sort by.
+    ///
+    /// As in CSS font matching, the width matters most, then the slant,
+    /// then the weight.
+    fn distance(self, wanted: Properties) -> (u32, u8, u16) {
+        let stretch = (self.stretch.to_percentage() - wanted.stretch.to_percentage()).abs();
+        let style = match (self.style, wanted.style) {
+            (a, b) if a == b => 0,
+            (FontStyle::Italic, FontStyle::Oblique) | (FontStyle::Oblique, FontStyle::Italic) => 1,
+            _ => 2,
+        };
+        let weight = (self.weight.to_raw() as i32 - wanted.weight.to_raw() as i32).abs();
+        ((stretch * 10.0) as u32, style, weight as u16)
+    }
+}
+
+impl Synthesis {
+    /// The styles to synthesize when using a face with some properties for
+    /// the ones wanted.
+    fn new(face: Properties, wanted: Properties) -> Synthesis {
+        Synthesis {
+            bold: wanted.weight >= FontWeight::SEMI_BOLD && face.weight < FontWeight::MEDIUM,
+            oblique: wanted.style != FontStyle::Regular && face.style == FontStyle::Regular,
+        }
+    }
+}
+
+/// The face of the installed font fontconfig picks for a family, with the
+/// name of its family, which is a default family if the one asked for isn't
+/// installed.
+///
+/// With a character, fontconfig prefers faces that have it over faces of
+/// the family, though it may still pick a face without it.
+fn system_face(family: &str, wanted: Properties, c: Option<char>) -> Result<(String, Face), Error> {
+    let key = (family.to_owned(), wanted, c);
+    let cached = SYSTEM_FACES.with(|faces| {
+        let faces = faces.borrow();
+        let (family, face) = faces.get(&key)?;
+        Some((family.clone(), face.clone()))
+    });
+    if let Some(found) = cached {
+        return Ok(found);
+    }
+    let not_found = || Error::new(ErrorKind::FontNotFound(family.to_owned()));
+    let (path, index, resolved) = match_system_font(family, wanted, c).ok_or_else(not_found)?;
+    let face = file_face(path, index, &resolved)
+        .and_then(|loaded| loaded.face(wanted))
+        .map_err(|_| not_found())?;
+    SYSTEM_FACES.with(|faces| {
+        faces
+            .borrow_mut()
+            .insert(key, (resolved.clone(), face.clone()))
+    });
+    Ok((resolved, face))
+}
+
+/// A face in an installed font file, which is read the first time.
+fn file_face(path: String, index: u32, family: &str) -> Result<Rc<LoadedFace>, Error> {
+    let key = (path, index);
+    if let Some(loaded) = FILE_FACES.with(|faces| faces.borrow().get(&key).cloned()) {
+        return Ok(loaded);
+    }
+    let data = std::fs::read(&key.0).map_err(|e| Error::new(ErrorKind::Io(e)))?;
+    let font = Rc::new(FontData::new(Rc::new(data), index)?);
+    let loaded = Rc::new(LoadedFace {
+        family: family.to_owned(),
+        properties: Properties::of_face(font.face()),
+        font,
+        faces: RefCell::new(Vec::new()),
+    });
+    FILE_FACES.with(|faces| faces.borrow_mut().insert(key, loaded.clone()));
+    Ok(loaded)
+}
+
+/// The family names of the installed fonts, as fontconfig lists them.
+fn system_families() -> Vec<String> {
+    let mut families = Vec::new();
+    unsafe {
+        let pattern = FcPatternCreate();
+        let objects = FcObjectSetCreate();
+        FcObjectSetAdd(objects, FC_FAMILY.as_ptr());
+        let set = FcFontList(null_mut(), pattern, objects);
+        FcObjectSetDestroy(objects);
+        FcPatternDestroy(pattern);
+        if set.is_null() {
+            return families;
+        }
+        for i in 0..(*set).nfont as usize {
+            families.extend(pattern_family(*(*set).fonts.add(i)));
+        }
+        FcFontSetDestroy(set);
+    }
+    families
+}
+
+/// The file, the index in it, and the family of the installed face that
+/// best matches a family, properties and a character.
+fn match_system_font(
+    family: &str,
+    wanted: Properties,
+    c: Option<char>,
+) -> Option<(String, u32, String)> {
+    // fontconfig has no alias for the user interface family.
+    let family = match GenericFamily::from_name(family) {
+        Some(GenericFamily::SystemUi) => GenericFamily::SansSerif.name(),
+        _ => family,
+    };
+    let family = CString::new(family).ok()?;
+    let slant = match wanted.style {
+        FontStyle::Regular => FC_SLANT_ROMAN,
+        FontStyle::Italic => FC_SLANT_ITALIC,
+        FontStyle::Oblique => FC_SLANT_OBLIQUE,
+    };
+    unsafe {
+        let pattern = FcPatternCreate();
+        FcPatternAddString(
+            pattern,
+            FC_FAMILY.as_ptr(),
+            family.as_ptr() as *const FcChar8,
+        );
+        FcPatternAddInteger(pattern, FC_WEIGHT.as_ptr(), fc_weight(wanted.weight));
+        FcPatternAddInteger(pattern, FC_SLANT.as_ptr(), slant);
+        FcPatternAddInteger(
+            pattern,
+            FC_WIDTH.as_ptr(),
+            wanted.stretch.to_percentage() as c_int,
+        );
+        if let Some(c) = c {
+            let charset = FcCharSetCreate();
+            FcCharSetAddChar(charset, c as FcChar32);
+            FcPatternAddCharSet(pattern, FC_CHARSET.as_ptr(), charset);
+            FcCharSetDestroy(charset);
+        }
+        FcConfigSubstitute(null_mut(), pattern, FcMatchPattern);
+        FcDefaultSubstitute(pattern);
+        let mut result = FcResultNoMatch;
+        let matched = FcFontMatch(null_mut(), pattern, &mut result);
+        FcPatternDestroy(pattern);
+        if matched.is_null() {
+            return None;
+        }
+        let mut file: *mut FcChar8 = null_mut();
+        let mut index: c_int = 0;
+        let found = if FcPatternGetString(matched, FC_FILE.as_ptr(), 0, &mut file) == FcResultMatch
+        {
+            FcPatternGetInteger(matched, FC_INDEX.as_ptr(), 0, &mut index);
+            let family = pattern_family(matched).unwrap_or_default();
+            CStr::from_ptr(file as *const _)
+                .to_str()
+                .ok()
+                .map(|path| (path.to_owned(), index as u32, family))
+        } else {
+            None
+        };
+        FcPatternDestroy(matched);
+        found
+    }
+}
+
+/// Whether a face has a glyph for a character.
+fn has_glyph(face: &FontFace, c: char) -> bool {
+    unsafe {
+        let ft_face = cairo_sys::cairo_font_face_get_user_data(face.to_raw_none(), &FT_FACE_KEY)
+            as *const freetype::Face;
+        ft_face
+            .as_ref()
+            .is_some_and(|ft_face| ft_face.get_char_index(c as usize) != 0)
+    }
+}
+
+/// Whether a character is drawn without a glyph, or only changes how the
+/// characters around it are drawn, so no face needs a glyph for it.
+fn is_ignorable(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{200b}'..='\u{200f}'
+                | '\u{2028}'..='\u{202e}'
+                | '\u{2060}'..='\u{206f}'
+                | '\u{fe00}'..='\u{fe0f}'
+                | '\u{feff}'
+                | '\u{e0000}'..='\u{e0fff}'
+        )
+}
+
+/// The first family name of a fontconfig pattern.
+unsafe fn pattern_family(pattern: *mut FcPattern) -> Option<String> {
+    let mut family: *mut FcChar8 = null_mut();
+    if FcPatternGetString(pattern, FC_FAMILY.as_ptr(), 0, &mut family) != FcResultMatch {
+        return None;
+    }
+    CStr::from_ptr(family as *const _)
+        .to_str()
+        .ok()
+        .map(str::to_owned)
+}
+
+/// Convert an OpenType weight to a fontconfig one, as `FcWeightFromOpenType`
+/// does, interpolating between the named weights.
+fn fc_weight(weight: FontWeight) -> c_int {
+    const MAP: [(f64, f64); 11] = [
+        (100.0, 0.0),
+        (200.0, 40.0),
+        (300.0, 50.0),
+        (350.0, 55.0),
+        (380.0, 75.0),
+        (400.0, 80.0),
+        (500.0, 100.0),
+        (600.0, 180.0),
+        (700.0, 200.0),
+        (800.0, 205.0),
+        (900.0, 210.0),
+    ];
+    let weight = f64::from(weight.to_raw()).clamp(100.0, 900.0);
+    let i = MAP.iter().position(|&(ot, _)| ot >= weight).unwrap_or(0);
+    if i == 0 {
+        return MAP[0].1 as c_int;
+    }
+    let (ot0, fc0) = MAP[i - 1];
+    let (ot1, fc1) = MAP[i];
+    (fc0 + (fc1 - fc0) * (weight - ot0) / (ot1 - ot0)).round() as c_int
+}
+
+/// Make a cairo face from a face in the data of a font file.
+fn cairo_face(data: Rc<Vec<u8>>, index: u32, synthesis: Synthesis) -> Result<FontFace, Error> {
+    let ft_face = FREETYPE
+        .with(|library| library.new_memory_face(data, index as isize))
+        .map_err(|e| Error::new(ErrorKind::InvalidFont(e.to_string())))?;
+    let mut ft_face = Box::new(ft_face);
+    let face = unsafe {
+        let raw = cairo_ft_font_face_create_for_ft_face(ft_face.raw_mut(), 0);
+        // The FreeType face has to live as long as the cairo face, so the
+        // cairo face owns it.
+        cairo_sys::cairo_font_face_set_user_data(
+            raw,
+            &FT_FACE_KEY,
+            Box::into_raw(ft_face) as *mut c_void,
+            Some(drop_ft_face),
+        );
+        FontFace::from_raw_full(raw)
+    };
+    if synthesis.bold {
+        face.set_synthesize(FtSynthesize::Bold);
+    }
+    if synthesis.oblique {
+        face.set_synthesize(FtSynthesize::Oblique);
+    }
+    Ok(face)
+}
+
+unsafe extern "C" fn drop_ft_face(ft_face: *mut c_void) {
+    drop(Box::from_raw(ft_face as *mut freetype::Face));
+}
@@ -1,31 +1,37 @@
-use piet::{HitTestPoint, TextLayout};
+use piet::HitTestPoint;
 use unicode_segmentation::UnicodeSegmentation;
 
+use super::CairoLine;
 use crate::CairoTextLayout;
 
 impl CairoTextLayout {
-    pub(crate) fn get_grapheme_boundaries(
-        &self,
-        grapheme_position: usize,
-    ) -> Option<GraphemeBoundaries> {
-        let mut graphemes = UnicodeSegmentation::grapheme_indices(self.text.as_str(), true);
-        let (text_position, _) = graphemes.nth(grapheme_position)?;
-        let (next_text_position, _) = graphemes.next().unwrap_or_else(|| (self.text.len(), ""));
-
-        let curr_edge = self.hit_test_text_position(text_position)?;
-        let next_edge = self.hit_test_text_position(next_text_position)?;
-
-        let res = GraphemeBoundaries {
-            curr_idx: curr_edge.metrics.text_position,
-            next_idx: next_edge.metrics.text_position,
-            leading: curr_edge.point.x,
-            trailing: next_edge.point.x,
-        };
-
-        Some(res)
+    /// The boundaries of the grapheme clusters of a line, not counting the
+    /// line's newline, from left to right.
+    pub(crate) fn line_graphemes(&self, line: &CairoLine) -> Vec<GraphemeBoundaries> {
+        let mut graphemes = Vec::new();
+        for placed in &line.clusters {
+            let range = self.cluster(placed).range.clone();
+            let start = graphemes.len();
+            for (i, grapheme) in self.text[range.clone()].grapheme_indices(true) {
+                let curr_idx = range.start + i;
+                let next_idx = curr_idx + grapheme.len();
+                graphemes.push(GraphemeBoundaries {
+                    curr_idx,
+                    next_idx,
+                    leading: self.cluster_x(placed, curr_idx),
+                    trailing: self.cluster_x(placed, next_idx),
+                });
+            }
+            if self.runs[placed.run].is_rtl() {
+                graphemes[start..].reverse();
+            }
+        }
+        graphemes
     }
 }
 
+/// Hit test a grapheme cluster, which is left-to-right when its leading edge
+/// is left of its trailing edge, and right-to-left otherwise.
 pub(crate) fn point_x_in_grapheme(
     point_x: f64,
     grapheme_boundaries: &GraphemeBoundaries,
@@ -36,11 +42,10 @@ pub(crate) fn point_x_in_grapheme(
     let curr_idx = grapheme_boundaries.curr_idx;
     let next_idx = grapheme_boundaries.next_idx;
 
-    if point_x >= leading && point_x <= trailing {
+    if point_x >= leading.min(trailing) && point_x <= leading.max(trailing) {
         // Check which boundary it's closer to.
-        // Round up to next grapheme boundary if
-        let midpoint = leading + ((trailing - leading) / 2.0);
-        if point_x >= midpoint {
+        // Round up to next grapheme boundary if at the midpoint
+        if (point_x - trailing).abs() <= (point_x - leading).abs() {
             res.metrics.text_position = next_idx;
         } else {
             res.metrics.text_position = curr_idx;
@@ -74,25 +79,25 @@ mod test {
             .new_font_by_name("sans-serif", 12.0)
             .build()
             .unwrap();
-        let layout = text_layout.new_text_layout(&font, "piet").build().unwrap();
-
-        let expected_3 = GraphemeBoundaries {
-            curr_idx: 3,
-            next_idx: 4,
-            leading: 0.0,  // not testing x advance
-            trailing: 0.0, // not testing x advance
-        };
+        let layout = text_layout
+            .new_text_layout(&font, "piet\ntext")
+            .build()
+            .unwrap();
 
         // test grapheme boundaries
-        assert_eq!(
-            layout.get_grapheme_boundaries(3).unwrap().curr_idx,
-            expected_3.curr_idx
-        );
-        assert_eq!(
-            layout.get_grapheme_boundaries(3).unwrap().next_idx,
-            expected_3.next_idx
-        );
-        assert_eq!(layout.get_grapheme_boundaries(4), None);
+        let line = &layout.lines[0];
+        let graphemes = layout.line_graphemes(line);
+        // the newline isn't part of the line's graphemes
+        assert_eq!(graphemes.len(), 4);
+        assert_eq!(graphemes[3].curr_idx, 3);
+        assert_eq!(graphemes[3].next_idx, 4);
+        assert_eq!(graphemes[3].leading, graphemes[2].trailing);
+        assert!(graphemes[3].trailing > graphemes[3].leading);
+
+        let line = &layout.lines[1];
+        let bounds = &layout.line_graphemes(line)[0];
+        assert_eq!(bounds.curr_idx, 5);
+        assert_eq!(bounds.leading, 0.0);
     }
 
     #[test]
@@ -125,5 +130,15 @@ mod test {
         assert_eq!(point_x_in_grapheme(11.0, &bounds), expected_curr);
         assert_eq!(point_x_in_grapheme(12.0, &bounds), expected_next);
         assert_eq!(point_x_in_grapheme(13.0, &bounds), expected_next);
+
+        // right-to-left
+        let bounds = GraphemeBoundaries {
+            leading: 14.0,
+            trailing: 10.0,
+            ..bounds
+        };
+        assert_eq!(point_x_in_grapheme(13.0, &bounds), expected_curr);
+        assert_eq!(point_x_in_grapheme(11.0, &bounds), expected_next);
+        assert_eq!(point_x_in_grapheme(15.0, &bounds), None);
     }
 }
@@ -0,0 +1,218 @@
+//! Breaking text into lines.
+
+use std::ops::Range;
+
+use piet::TextAlignment;
+use unicode_bidi::{get_base_direction, Direction};
+use xi_unicode::LineBreakIterator;
+
+/// Split text into lines, at explicit newlines and wherever the text would
+/// be wider than `max_width`.
+///
+/// `x_at` gives the offset of a text position with all of the text laid out
+/// on one line. Lines break at the line break opportunities of UAX #14;
+/// trailing whitespace doesn't count towards the width of a line. A word
+/// wider than `max_width` gets a line of its own, and overflows it.
+///
+/// The ranges include trailing whitespace and newlines, so they cover all
+/// of the text. Text ending in a newline has a last line that is empty.
+pub(crate) fn line_ranges(
+    text: &str,
+    max_width: f64,
+    x_at: impl Fn(usize) -> f64,
+) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut prev_break = 0;
+    for (offset, _) in LineBreakIterator::new(text) {
+        let end = trim_end(text, line_start..offset);
+        if prev_break > line_start && x_at(end) - x_at(line_start) > max_width {
+            lines.push(line_start..prev_break);
+            line_start = prev_break;
+        }
+        // The iterator also reports the end of the text as a hard break, so
+        // check for the newline itself.
+        if text[..offset].ends_with(is_newline) {
+            lines.push(line_start..offset);
+            line_start = offset;
+        }
+        prev_break = offset;
+    }
+    if line_start < text.len() || lines.is_empty() || text.ends_with(is_newline) {
+        lines.push(line_start..text.len());
+    }
+    lines
+}
+
+/// Place a line of text within the width of a layout.
+///
+/// Returns the offset of the line from the left of the layout, and the
+/// extra width given to each of its spaces when it's justified.
+pub(crate) fn align(
+    alignment: TextAlignment,
+    rtl: bool,
+    ends_paragraph: bool,
+    line_width: f64,
+    width: f64,
+    spaces: usize,
+) -> (f64, f64) {
+    let extra = (width - line_width).max(0.0);
+    let alignment = match alignment {
+        TextAlignment::Justified if !ends_paragraph && spaces > 0 => {
+            return (0.0, extra / spaces as f64)
+        }
+        TextAlignment::Justified => TextAlignment::Start,
+        alignment => alignment,
+    };
+    let offset = match (alignment, rtl) {
+        (TextAlignment::Center, _) => extra / 2.0,
+        (TextAlignment::Start, false) | (TextAlignment::End, true) => 0.0,
+        _ => extra,
+    };
+    (offset, 0.0)
+}
+
+/// Whether a paragraph is right-to-left, from its first strong character.
+///
+/// Only the first paragraph of `text` is considered.
+pub(crate) fn is_rtl(text: &str) -> bool {
+    get_base_direction(text) == Direction::Rtl
+}
+
+/// The order to show runs of text in from left to right, given their bidi
+/// levels in text order.
+///
+/// This is rule L2 of the Unicode Bidirectional Algorithm: from the highest
+/// level to the lowest odd level, each sequence of runs at that level or
+/// higher is reversed.
+pub(crate) fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    let lowest_odd = levels.iter().map(|&level| level | 1).min().unwrap_or(1);
+    for level in (lowest_odd..=highest).rev() {
+        let mut start = 0;
+        while start < order.len() {
+            if levels[order[start]] < level {
+                start += 1;
+                continue;
+            }
+            let mut end = start;
+            while end < order.len() && levels[order[end]] >= level {
+                end += 1;
+            }
+            order[start..end].reverse();
+            start = end;
+        }
+    }
+    order
+}
+
+/// The number of spaces in some text, which widen when it's justified.
+pub(crate) fn count_spaces(text: &str) -> usize {
+    text.matches(' ').count()
+}
+
+/// The end of a line without its trailing whitespace and newline.
+pub(crate) fn trim_end(text: &str, range: Range<usize>) -> usize {
+    range.start + text[range].trim_end().len()
+}
+
+/// The end of a line without its newline.
+pub(crate) fn trim_newline(text: &str, range: Range<usize>) -> usize {
+    range.start + text[range].trim_end_matches(is_newline).len()
+}
+
+fn is_newline(c: char) -> bool {
+    matches!(
+        c,
+        '\n' | '\r' | '\u{0b}' | '\u{0c}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Each char is 1 unit wide.
+    fn lines(text: &str, max_width: f64) -> Vec<&str> {
+        line_ranges(text, max_width, |i| i as f64)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_wrap_at_spaces() {
+        assert_eq!(
+            lines("piet text most best", 9.0),
+            vec!["piet text ", "most best"]
+        );
+        assert_eq!(
+            lines("piet text most best", 4.0),
+            vec!["piet ", "text ", "most ", "best"]
+        );
+        assert_eq!(lines("piet text", f64::INFINITY), vec!["piet text"]);
+    }
+
+    #[test]
+    fn test_overflowing_word() {
+        assert_eq!(
+            lines("a overflowing b", 3.0),
+            vec!["a ", "overflowing ", "b"]
+        );
+    }
+
+    #[test]
+    fn test_newlines() {
+        assert_eq!(lines("piet\ntext", f64::INFINITY), vec!["piet\n", "text"]);
+        assert_eq!(
+            lines("piet\r\n\ntext\n", f64::INFINITY),
+            vec!["piet\r\n", "\n", "text\n", ""]
+        );
+        assert_eq!(lines("", 10.0), vec![""]);
+        assert_eq!(
+            lines("piet text\nmost best", 6.0),
+            vec!["piet ", "text\n", "most ", "best"]
+        );
+    }
+
+    #[test]
+    fn test_align() {
+        use TextAlignment::*;
+        assert_eq!(align(Start, false, false, 6.0, 10.0, 1), (0.0, 0.0));
+        assert_eq!(align(Start, true, false, 6.0, 10.0, 1), (4.0, 0.0));
+        assert_eq!(align(End, false, false, 6.0, 10.0, 1), (4.0, 0.0));
+        assert_eq!(align(End, true, false, 6.0, 10.0, 1), (0.0, 0.0));
+        assert_eq!(align(Center, true, false, 6.0, 10.0, 1), (2.0, 0.0));
+        assert_eq!(align(Justified, false, false, 6.0, 10.0, 2), (0.0, 2.0));
+        // the last line of a paragraph, and lines without spaces, aren't stretched
+        assert_eq!(align(Justified, true, true, 6.0, 10.0, 2), (4.0, 0.0));
+        assert_eq!(align(Justified, false, false, 6.0, 10.0, 0), (0.0, 0.0));
+        // lines wider than the layout overflow at the end
+        assert_eq!(align(End, false, false, 12.0, 10.0, 1), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_direction() {
+        assert!(!is_rtl("piet"));
+        assert!(is_rtl("\u{5e9}\u{5dc}\u{5d5}\u{5dd} piet"));
+        assert!(!is_rtl("123 piet \u{5e9}"));
+        assert!(!is_rtl("piet\n\u{5e9}"));
+        assert!(!is_rtl(""));
+    }
+
+    #[test]
+    fn test_visual_order() {
+        assert_eq!(visual_order(&[0, 0, 0]), vec![0, 1, 2]);
+        assert_eq!(visual_order(&[0, 1, 1, 0]), vec![0, 2, 1, 3]);
+        assert_eq!(visual_order(&[1, 2, 1]), vec![2, 1, 0]);
+        assert_eq!(visual_order(&[1, 2, 2, 1, 3]), vec![4, 3, 1, 2, 0]);
+        assert_eq!(visual_order(&[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_trim() {
+        assert_eq!(trim_end("ab  \n", 0..5), 2);
+        assert_eq!(trim_newline("ab  \r\n", 0..6), 4);
+    }
+}
@@ -0,0 +1,249 @@
+//! Shaping text into glyphs.
+//!
+//! Text is shaped with rustybuzz, a port of HarfBuzz, from the data of the
+//! face it's drawn with. Shaping picks the glyphs for the text, with
+//! ligatures, marks and the forms of complex scripts, and positions them
+//! with the kerning of the face. Each glyph belongs to a cluster, the text
+//! it's drawn for, which is what text positions are mapped through.
+//!
+//! Without the `fontconfig` feature there is no font data, and cairo maps
+//! characters to glyphs itself.
+
+use std::ops::Range;
+
+use piet::Error;
+#[cfg(feature = "fontconfig")]
+use rustybuzz::{Direction, Tag, UnicodeBuffer};
+use unicode_bidi::Level;
+use unicode_script::{Script, UnicodeScript};
+
+use super::fonts::Face;
+
+/// Shaped text: its glyphs, and the clusters of text they are drawn for.
+pub(crate) struct Shaped {
+    /// The glyphs, from left to right.
+    pub(crate) glyphs: Vec<ShapedGlyph>,
+    /// The clusters, in text order.
+    pub(crate) clusters: Vec<Cluster>,
+}
+
+/// A glyph, placed by the advances of the glyphs before it.
+pub(crate) struct ShapedGlyph {
+    pub(crate) id: u32,
+    pub(crate) advance: f64,
+    /// The offset of the glyph from where the advances place it, with y
+    /// pointing down.
+    pub(crate) x_offset: f64,
+    pub(crate) y_offset: f64,
+}
+
+/// Text whose glyphs are drawn together: a character with its marks, or
+/// the characters of a ligature.
+pub(crate) struct Cluster {
+    pub(crate) range: Range<usize>,
+    /// The glyphs of the cluster, in the glyphs of its text.
+    pub(crate) glyphs: Range<usize>,
+    pub(crate) advance: f64,
+}
+
+/// Split a range of text into segments to shape on their own: text of one
+/// bidi level, and of one script.
+///
+/// Characters used by many scripts, like spaces and punctuation, and marks
+/// belong to the script of the text before them. `levels` has the level of
+/// each byte of the text.
+pub(crate) fn segments(
+    text: &str,
+    range: Range<usize>,
+    levels: &[Level],
+) -> Vec<(Range<usize>, Level, Script)> {
+    let mut segments: Vec<(Range<usize>, Level, Script)> = Vec::new();
+    for (i, c) in text[range.clone()].char_indices() {
+        let start = range.start + i;
+        let end = start + c.len_utf8();
+        let level = levels[start];
+        let script = c.script();
+        match segments.last_mut() {
+            Some((range, last_level, last_script))
+                if *last_level == level
+                    && (is_common(script) || is_common(*last_script) || *last_script == script) =>
+            {
+                range.end = end;
+                if !is_common(script) {
+                    *last_script = script;
+                }
+            }
+            _ => segments.push((start..end, level, script)),
+        }
+    }
+    if segments.is_empty() {
+        segments.push((range, Level::ltr(), Script::Common));
+    }
+    segments
+}
+
+/// Shape a segment of text, at `offset` in a layout, in a face at a size.
+#[cfg(feature = "fontconfig")]
+pub(crate) fn shape(
+    text: &str,
+    offset: usize,
+    face: &Face,
+    size: f64,
+    level: Level,
+    script: Script,
+) -> Result<Shaped, Error> {
+    let font = face.font.face();
+    let scale = size / f64::from(font.units_per_em());
+    // FreeType widens emboldened glyphs by a 24th of the size.
+    let embolden = if face.embolden { size / 24.0 } else { 0.0 };
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.set_direction(if level.is_rtl() {
+        Direction::RightToLeft
+    } else {
+        Direction::LeftToRight
+    });
+    if !is_common(script) {
+        let tag = Tag(script.as_iso15924_tag());
+        if let Some(script) = rustybuzz::Script::from_iso15924_tag(tag) {
+            buffer.set_script(script);
+        }
+    }
+    buffer.guess_segment_properties();
+    let output = rustybuzz::shape(font, &[], buffer);
+
+    let mut glyphs = Vec::with_capacity(output.len());
+    let mut clusters: Vec<Cluster> = Vec::new();
+    let positions = output.glyph_positions();
+    for (i, (info, position)) in output.glyph_infos().iter().zip(positions).enumerate() {
+        let mut advance = f64::from(position.x_advance) * scale;
+        if advance != 0.0 {
+            advance += embolden;
+        }
+        glyphs.push(ShapedGlyph {
+            id: info.glyph_id,
+            advance,
+            x_offset: f64::from(position.x_offset) * scale,
+            y_offset: -f64::from(position.y_offset) * scale,
+        });
+        // The glyphs of a cluster are next to each other, and clusters are
+        // in text order in left-to-right text, and in reverse otherwise.
+        let start = offset + info.cluster as usize;
+        match clusters.last_mut() {
+            Some(cluster) if cluster.range.start == start => {
+                cluster.glyphs.end = i + 1;
+                cluster.advance += advance;
+            }
+            _ => clusters.push(Cluster {
+                range: start..start,
+                glyphs: i..i + 1,
+                advance,
+            }),
+        }
+    }
+    clusters.sort_by_key(|cluster| cluster.range.start);
+    // A cluster's text ends where the next cluster's starts.
+    let mut end = offset + text.len();
+    for cluster in clusters.iter_mut().rev() {
+        cluster.range.end = end;
+        end = cluster.range.start;
+    }
+    Ok(Shaped { glyphs, clusters })
+}
+
+/// Shape a segment of text, at `offset` in a layout, in a face at a size.
+///
+/// Cairo's toy font API maps characters to glyphs one by one, without
+/// ligatures or kerning.
+#[cfg(not(feature = "fontconfig"))]
+pub(crate) fn shape(
+    text: &str,
+    offset: usize,
+    face: &Face,
+    size: f64,
+    level: Level,
+    _script: Script,
+) -> Result<Shaped, Error> {
+    let font = super::scaled_font(&face.cairo, size);
+    // Cairo takes the text as a C string, which can't have nul characters.
+    let text = text.replace('\0', " ");
+    let (cairo_glyphs, cairo_clusters) = font.text_to_glyphs(0.0, 0.0, &text);
+    let mut glyphs: Vec<ShapedGlyph> = cairo_glyphs
+        .iter()
+        .map(|glyph| ShapedGlyph {
+            id: glyph.index as u32,
+            advance: font.glyph_extents(&[*glyph]).x_advance,
+            x_offset: 0.0,
+            y_offset: 0.0,
+        })
+        .collect();
+    let mut clusters = Vec::with_capacity(cairo_clusters.len());
+    let (mut start, mut glyph) = (offset, 0);
+    for cluster in &cairo_clusters {
+        let end = start + cluster.num_bytes as usize;
+        let cluster_glyphs = glyph..glyph + cluster.num_glyphs as usize;
+        clusters.push(Cluster {
+            range: start..end,
+            advance: glyphs[cluster_glyphs.clone()]
+                .iter()
+                .map(|glyph| glyph.advance)
+                .sum(),
+            glyphs: cluster_glyphs.clone(),
+        });
+        start = end;
+        glyph = cluster_glyphs.end;
+    }
+    // The glyphs are in text order, and go from left to right.
+    if level.is_rtl() {
+        let count = glyphs.len();
+        glyphs.reverse();
+        for cluster in &mut clusters {
+            cluster.glyphs = count - cluster.glyphs.end..count - cluster.glyphs.start;
+        }
+    }
+    Ok(Shaped { glyphs, clusters })
+}
+
+fn is_common(script: Script) -> bool {
+    matches!(script, Script::Common | Script::Inherited | Script::Unknown)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use unicode_bidi::BidiInfo;
+
+    fn scripts(text: &str) -> Vec<(&str, bool, Script)> {
+        let bidi = BidiInfo::new(text, None);
+        segments(text, 0..text.len(), &bidi.levels)
+            .into_iter()
+            .map(|(range, level, script)| (&text[range], level.is_rtl(), script))
+            .collect()
+    }
+
+    #[test]
+    fn test_segments() {
+        assert_eq!(
+            scripts("one, two"),
+            vec![("one, two", false, Script::Latin)]
+        );
+        assert_eq!(
+            scripts("abc नमस्ते, def"),
+            vec![
+                ("abc ", false, Script::Latin),
+                ("नमस्ते, ", false, Script::Devanagari),
+                ("def", false, Script::Latin),
+            ]
+        );
+        assert_eq!(
+            scripts("abc مرحبا def"),
+            vec![
+                ("abc ", false, Script::Latin),
+                ("مرحبا", true, Script::Arabic),
+                (" def", false, Script::Latin),
+            ]
+        );
+        assert_eq!(scripts(""), vec![("", false, Script::Common)]);
+    }
+}
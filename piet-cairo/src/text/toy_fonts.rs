@@ -0,0 +1,90 @@
+//! Finding font faces with cairo's toy font API.
+//!
+//! This is used without the `fontconfig` feature, where cairo may not have
+//! FreeType. The toy API picks an installed face for a family, slant and
+//! weight, and draws text in it alone. Fonts can't be loaded from data,
+//! and installed fonts can't be listed.
+
+use std::ops::Range;
+
+use cairo::{FontFace, FontSlant};
+use piet::{new_error, Error, ErrorKind, FontStyle, FontWeight, GenericFamily, TextStyle};
+
+use super::share;
+
+/// The fonts loaded into a `CairoText`, which are none with the toy API.
+#[derive(Default)]
+pub(crate) struct FontCollection {}
+
+/// A face to draw and shape text with.
+pub(crate) struct Face {
+    pub(crate) cairo: FontFace,
+}
+
+impl FontCollection {
+    /// Fonts can't be loaded from data with the toy API.
+    pub(crate) fn load(&mut self, _data: &[u8]) -> Result<String, Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    /// The toy API can't list installed families.
+    pub(crate) fn families(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The face for a style.
+    pub(crate) fn face(&self, style: &TextStyle) -> Result<Face, Error> {
+        self.resolve(style).map(|(_, face)| face)
+    }
+
+    /// The face for a style, with the name of its family, which is the one
+    /// asked for, as the toy API doesn't say which family it found.
+    pub(crate) fn resolve(&self, style: &TextStyle) -> Result<(String, Face), Error> {
+        // The toy API knows the CSS generic families, except for the user
+        // interface one.
+        let family = match GenericFamily::from_name(&style.family) {
+            Some(GenericFamily::SystemUi) => GenericFamily::SansSerif.name(),
+            _ => &style.family,
+        };
+        let slant = match style.style {
+            FontStyle::Regular => FontSlant::Normal,
+            FontStyle::Italic => FontSlant::Italic,
+            FontStyle::Oblique => FontSlant::Oblique,
+        };
+        // The toy API only has two weights, and no widths.
+        let weight = if style.weight >= FontWeight::SEMI_BOLD {
+            cairo::FontWeight::Bold
+        } else {
+            cairo::FontWeight::Normal
+        };
+        let face = Face {
+            cairo: FontFace::toy_create(family, slant, weight),
+        };
+        Ok((style.family.clone(), face))
+    }
+
+    /// Split text in a style into ranges drawn with the same face, which is
+    /// all of it with the toy API.
+    pub(crate) fn itemize(
+        &self,
+        text: &str,
+        style: &TextStyle,
+    ) -> Result<Vec<(Range<usize>, Face)>, Error> {
+        Ok(vec![(0..text.len(), self.face(style)?)])
+    }
+}
+
+impl Face {
+    /// The toy API has no decoration metrics.
+    pub(crate) fn underline(&self, _size: f64) -> Option<(f64, f64)> {
+        None
+    }
+}
+
+impl Clone for Face {
+    fn clone(&self) -> Face {
+        Face {
+            cairo: share(&self.cairo),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use piet::kurbo::{Line, Size};
+use piet::{Color, FontBuilder, RenderContext, Text, TextLayoutBuilder};
+use piet_common::Device;
+
+/// PDF output is only available with the Cairo backend.
+/// cargo run --example pdf --features cairo
+fn main() {
+    let mut device = Device::new().unwrap();
+    // A4, in points.
+    let mut pdf = device
+        .pdf_target("temp-document.pdf", Size::new(595.0, 842.0))
+        .unwrap();
+    for page in 1..=2 {
+        if page > 1 {
+            pdf.new_page().unwrap();
+        }
+        let mut rc = pdf.render_context();
+        let brush = rc.solid_brush(Color::rgb8(0x00, 0x00, 0x80));
+        rc.stroke(Line::new((72.0, 72.0), (523.0, 72.0)), &brush, 1.0);
+        let font = rc.text().new_font_by_name("Sans", 24.0).build().unwrap();
+        let text = format!("Page {}", page);
+        let layout = rc.text().new_text_layout(&font, &text).build().unwrap();
+        rc.draw_text(&layout, (72.0, 110.0), &brush);
+        rc.finish().unwrap();
+    }
+    pdf.finish().expect("error writing pdf");
+}
@@ -3,16 +3,18 @@
 
 //! Support for piet Cairo back-end.
 
-use cairo::{Context, Format, ImageSurface};
+use cairo::{Context, Format, ImageSurface, PdfSurface, Status};
 #[cfg(feature = "png")]
 use png::{ColorType, Encoder};
 #[cfg(feature = "png")]
 use std::fs::File;
 #[cfg(feature = "png")]
 use std::io::BufWriter;
+use std::io::Write;
 use std::marker::PhantomData;
 use std::path::Path;
 
+use piet::kurbo::Size;
 use piet::{ErrorKind, ImageFormat};
 #[doc(hidden)]
 pub use piet_cairo::*;
@@ -67,6 +69,17 @@ pub struct BitmapTarget<'a> {
     phantom: PhantomData<&'a ()>,
 }
 
+/// A struct that provides a `RenderContext` for the pages of a PDF document.
+///
+/// Drawing is kept as vector graphics, and text stays selectable. The units
+/// of user space are PDF points, 1/72 of an inch.
+///
+/// This is only available with the Cairo backend.
+pub struct PdfTarget {
+    surface: PdfSurface,
+    cr: Context,
+}
+
 impl Device {
     /// Create a new device.
     pub fn new() -> Result<Device, piet::Error> {
@@ -90,6 +103,35 @@ impl Device {
             phantom,
         })
     }
+
+    /// Create a new PDF target, writing to the file at `path`.
+    ///
+    /// The `page_size` is in points, and applies to every page unless it is
+    /// changed with [`PdfTarget::set_page_size`].
+    ///
+    /// [`PdfTarget::set_page_size`]: struct.PdfTarget.html#method.set_page_size
+    pub fn pdf_target<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        page_size: Size,
+    ) -> Result<PdfTarget, piet::Error> {
+        let surface =
+            PdfSurface::new(page_size.width, page_size.height, path).map_err(wrap_status)?;
+        PdfTarget::new(surface)
+    }
+
+    /// Create a new PDF target, writing to `writer`.
+    ///
+    /// See [`pdf_target`](#method.pdf_target) for details.
+    pub fn pdf_target_for_writer<W: Write + 'static>(
+        &mut self,
+        writer: W,
+        page_size: Size,
+    ) -> Result<PdfTarget, piet::Error> {
+        let surface = PdfSurface::for_stream(page_size.width, page_size.height, writer)
+            .map_err(wrap_status)?;
+        PdfTarget::new(surface)
+    }
 }
 
 impl<'a> BitmapTarget<'a> {
@@ -116,7 +158,7 @@ impl<'a> BitmapTarget<'a> {
         let buf = self
             .surface
             .get_data()
-            .map_err(Into::<Box<dyn std::error::Error>>::into)?;
+            .map_err(Into::<Box<dyn std::error::Error + Send + Sync>>::into)?;
         for y in 0..height {
             let src_off = y * stride;
             let dst_off = y * width * 4;
@@ -153,3 +195,118 @@ impl<'a> BitmapTarget<'a> {
         Err(piet::new_error(ErrorKind::MissingFeature))
     }
 }
+
+impl PdfTarget {
+    fn new(surface: PdfSurface) -> Result<PdfTarget, piet::Error> {
+        let cr = Context::new(&surface);
+        check_status(cr.status())?;
+        Ok(PdfTarget { surface, cr })
+    }
+
+    /// Get a piet `RenderContext` for the current page.
+    ///
+    /// Note: caller is responsible for calling `finish` on the render
+    /// context at the end of rendering.
+    pub fn render_context(&mut self) -> CairoRenderContext<'_> {
+        CairoRenderContext::new(&mut self.cr)
+    }
+
+    /// Finish the current page, and start a new one.
+    ///
+    /// The new page has the same size as the current one, unless it is
+    /// changed with [`set_page_size`](#method.set_page_size).
+    pub fn new_page(&mut self) -> Result<(), piet::Error> {
+        self.cr.show_page();
+        check_status(self.cr.status())
+    }
+
+    /// Change the size of the current page, in points.
+    ///
+    /// This has to be done before anything is drawn on the page.
+    pub fn set_page_size(&mut self, page_size: Size) -> Result<(), piet::Error> {
+        self.surface
+            .set_size(page_size.width, page_size.height)
+            .map_err(wrap_status)
+    }
+
+    /// Finish the document, writing out everything that is still pending.
+    pub fn finish(self) -> Result<(), piet::Error> {
+        std::mem::drop(self.cr);
+        self.surface.finish();
+        check_status(self.surface.status())
+    }
+}
+
+fn check_status(status: Status) -> Result<(), piet::Error> {
+    match status {
+        Status::Success => Ok(()),
+        status => Err(wrap_status(status)),
+    }
+}
+
+fn wrap_status(status: Status) -> piet::Error {
+    let e: Box<dyn std::error::Error + Send + Sync> = format!("Cairo error: {}", status).into();
+    e.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use piet::kurbo::Rect;
+    use piet::{Color, FontBuilder, RenderContext, Text, TextLayoutBuilder};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A writer whose output can be read after it's given away.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn draw_page(target: &mut PdfTarget, text: &str) -> Result<(), piet::Error> {
+        let mut rc = target.render_context();
+        rc.fill(
+            Rect::new(72.0, 72.0, 144.0, 144.0),
+            &Color::rgb8(0, 0, 0x80),
+        );
+        let font = rc.text().new_font_by_name("Sans", 12.0).build()?;
+        let layout = rc.text().new_text_layout(&font, text).build()?;
+        rc.draw_text(&layout, (72.0, 200.0), &Color::BLACK);
+        rc.finish()
+    }
+
+    #[test]
+    fn pdf_pages_have_selectable_text() {
+        let buffer = SharedBuffer::default();
+        let mut device = Device::new().unwrap();
+        let mut target = device
+            .pdf_target_for_writer(buffer.clone(), Size::new(595.0, 842.0))
+            .unwrap();
+        draw_page(&mut target, "First page").unwrap();
+        target.new_page().unwrap();
+        target.set_page_size(Size::new(842.0, 595.0)).unwrap();
+        draw_page(&mut target, "Second page").unwrap();
+        target.finish().unwrap();
+
+        let doc = lopdf::Document::load_mem(&buffer.0.borrow()).unwrap();
+        let pages = doc.get_pages();
+        assert_eq!(pages.len(), 2);
+        assert!(doc.extract_text(&[1]).unwrap().contains("First page"));
+        assert!(doc.extract_text(&[2]).unwrap().contains("Second page"));
+        let media_box = doc
+            .get_dictionary(pages[&2])
+            .and_then(|page| page.get(b"MediaBox"))
+            .and_then(|media_box| media_box.as_array())
+            .unwrap();
+        let width = media_box[2].as_float().unwrap();
+        assert!((width - 842.0).abs() < 1e-3);
+    }
+}
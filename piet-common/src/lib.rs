@@ -9,6 +9,20 @@
 //! supporting multiple backends simultaneously) you should use crates such as
 //! [piet][] and [piet-cairo][] directly.
 //!
+//! The `tiny-skia` feature selects a pure Rust software renderer in place of
+//! the platform's default backend. On platforms other than Windows and the
+//! web, that's cairo, which comes with the `platform-cairo` feature; it's
+//! on by default, and does nothing on Windows and the web. Turn off the
+//! default features along with selecting `tiny-skia` to build without a
+//! system cairo:
+//!
+//! ```toml
+//! piet-common = { version = "0.0.9", default-features = false, features = ["tiny-skia"] }
+//! ```
+//!
+//! The `cairo` feature asks for cairo explicitly, and is an error on Windows
+//! and the web, where piet-common doesn't support it.
+//!
 //! The associated types for brushes, text, and images are exported as type
 //! definitions (resolving to concrete types within the backend), so they can
 //! be used directly. The text-related types are prefixed with "Piet" to avoid
@@ -29,21 +43,43 @@ pub use piet::*;
 #[doc(hidden)]
 pub use piet::kurbo;
 
-#[cfg(any(
-    feature = "cairo",
-    not(any(target_arch = "wasm32", target_os = "windows", feature = "direct2d"))
+#[cfg(all(feature = "cairo", any(target_arch = "wasm32", target_os = "windows")))]
+compile_error!("piet-common's cairo backend isn't available on Windows or the web");
+
+#[cfg(all(
+    feature = "platform-cairo",
+    not(any(
+        target_arch = "wasm32",
+        target_os = "windows",
+        feature = "d2d",
+        feature = "web",
+        feature = "tiny-skia"
+    ))
 ))]
 #[path = "cairo_back.rs"]
 mod backend;
 
-#[cfg(any(feature = "d2d", all(target_os = "windows", not(feature = "cairo"))))]
+#[cfg(any(
+    feature = "d2d",
+    all(
+        target_os = "windows",
+        not(any(feature = "tiny-skia", feature = "web"))
+    )
+))]
 #[path = "direct2d_back.rs"]
 mod backend;
 
-#[cfg(any(feature = "web", target_arch = "wasm32"))]
+#[cfg(any(
+    feature = "web",
+    all(target_arch = "wasm32", not(feature = "tiny-skia"))
+))]
 #[path = "web_back.rs"]
 mod backend;
 
+#[cfg(all(feature = "tiny-skia", not(any(feature = "d2d", feature = "web"))))]
+#[path = "tiny_skia_back.rs"]
+mod backend;
+
 pub use backend::*;
 
 #[cfg(test)]
@@ -0,0 +1,136 @@
+//! Support for piet tiny-skia back-end.
+
+#[cfg(feature = "png")]
+use png::{ColorType, Encoder};
+#[cfg(feature = "png")]
+use std::fs::File;
+#[cfg(feature = "png")]
+use std::io::BufWriter;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use piet::kurbo::Affine;
+use piet::{ErrorKind, ImageFormat, RenderContext};
+#[doc(hidden)]
+pub use piet_tiny_skia::*;
+
+use piet_tiny_skia::tiny_skia::Pixmap;
+
+/// The `RenderContext` for the tiny-skia backend, which is selected.
+pub type Piet<'a> = TinySkiaRenderContext<'a>;
+
+/// The associated brush type for this backend.
+///
+/// This type matches `RenderContext::Brush`
+pub type Brush = piet_tiny_skia::Brush;
+
+/// The associated text factory for this backend.
+///
+/// This type matches `RenderContext::Text`
+pub type PietText<'a> = TinySkiaText;
+
+/// The associated font type for this backend.
+///
+/// This type matches `RenderContext::Text::Font`
+pub type PietFont = TinySkiaFont;
+
+/// The associated font builder for this backend.
+///
+/// This type matches `RenderContext::Text::FontBuilder`
+pub type PietFontBuilder<'a> = TinySkiaFontBuilder;
+
+/// The associated text layout type for this backend.
+///
+/// This type matches `RenderContext::Text::TextLayout`
+pub type PietTextLayout = TinySkiaTextLayout;
+
+/// The associated text layout builder for this backend.
+///
+/// This type matches `RenderContext::Text::TextLayoutBuilder`
+pub type PietTextLayoutBuilder<'a> = TinySkiaTextLayoutBuilder;
+
+/// The associated image type for this backend.
+///
+/// This type matches `RenderContext::Image`
+pub type Image = TinySkiaImage;
+
+/// A struct that can be used to create bitmap render contexts.
+///
+/// In the case of tiny-skia, being a software renderer, no state is needed.
+pub struct Device;
+
+/// A struct provides a `RenderContext` and then can have its bitmap extracted.
+pub struct BitmapTarget<'a> {
+    pixmap: Pixmap,
+    pix_scale: f64,
+    phantom: PhantomData<&'a ()>,
+}
+
+impl Device {
+    /// Create a new device.
+    pub fn new() -> Result<Device, piet::Error> {
+        Ok(Device)
+    }
+
+    /// Create a new bitmap target.
+    pub fn bitmap_target(
+        &mut self,
+        width: usize,
+        height: usize,
+        pix_scale: f64,
+    ) -> Result<BitmapTarget<'_>, piet::Error> {
+        let pixmap = Pixmap::new(width as u32, height as u32)
+            .ok_or_else(|| piet::new_error(ErrorKind::InvalidInput))?;
+        let phantom = Default::default();
+        Ok(BitmapTarget {
+            pixmap,
+            pix_scale,
+            phantom,
+        })
+    }
+}
+
+impl<'a> BitmapTarget<'a> {
+    /// Get a piet `RenderContext` for the bitmap.
+    ///
+    /// Note: caller is responsible for calling `finish` on the render
+    /// context at the end of rendering.
+    pub fn render_context(&mut self) -> TinySkiaRenderContext<'_> {
+        let mut rc = TinySkiaRenderContext::new(&mut self.pixmap);
+        rc.transform(Affine::scale(self.pix_scale));
+        rc
+    }
+
+    /// Get raw RGBA pixels from the bitmap.
+    pub fn into_raw_pixels(self, fmt: ImageFormat) -> Result<Vec<u8>, piet::Error> {
+        // TODO: convert other formats.
+        if fmt != ImageFormat::RgbaPremul {
+            return Err(piet::new_error(ErrorKind::NotSupported));
+        }
+        // tiny-skia already stores premultiplied RGBA, without padding.
+        Ok(self.pixmap.take())
+    }
+
+    /// Save bitmap to RGBA PNG file
+    #[cfg(feature = "png")]
+    pub fn save_to_file<P: AsRef<Path>>(self, path: P) -> Result<(), piet::Error> {
+        let height = self.pixmap.height();
+        let width = self.pixmap.width();
+        let image = self.into_raw_pixels(ImageFormat::RgbaPremul)?;
+        let file = BufWriter::new(File::create(path).map_err(|e| Into::<Box<_>>::into(e))?);
+        let mut encoder = Encoder::new(file, width, height);
+        encoder.set_color(ColorType::RGBA);
+        encoder
+            .write_header()
+            .map_err(|e| Into::<Box<_>>::into(e))?
+            .write_image_data(&image)
+            .map_err(|e| Into::<Box<_>>::into(e))?;
+        Ok(())
+    }
+
+    /// Stub for feature is missing
+    #[cfg(not(feature = "png"))]
+    pub fn save_to_file<P: AsRef<Path>>(self, _path: P) -> Result<(), piet::Error> {
+        Err(piet::new_error(ErrorKind::MissingFeature))
+    }
+}
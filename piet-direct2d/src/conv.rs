@@ -2,14 +2,17 @@
 
 use winapi::um::d2d1::{
     D2D1_CAP_STYLE, D2D1_CAP_STYLE_FLAT, D2D1_CAP_STYLE_ROUND, D2D1_CAP_STYLE_SQUARE, D2D1_COLOR_F,
-    D2D1_DASH_STYLE_CUSTOM, D2D1_DASH_STYLE_SOLID, D2D1_GRADIENT_STOP, D2D1_LINE_JOIN,
+    D2D1_DASH_STYLE_CUSTOM, D2D1_DASH_STYLE_SOLID, D2D1_EXTEND_MODE, D2D1_EXTEND_MODE_CLAMP,
+    D2D1_EXTEND_MODE_MIRROR, D2D1_EXTEND_MODE_WRAP, D2D1_GRADIENT_STOP, D2D1_LINE_JOIN,
     D2D1_LINE_JOIN_BEVEL, D2D1_LINE_JOIN_MITER, D2D1_LINE_JOIN_ROUND, D2D1_MATRIX_3X2_F,
     D2D1_POINT_2F, D2D1_RECT_F, D2D1_STROKE_STYLE_PROPERTIES,
 };
 
 use piet::kurbo::{Affine, Point, Rect, Vec2};
 
-use piet::{Color, Error, GradientStop, LineCap, LineJoin, RoundFrom, RoundInto, StrokeStyle};
+use piet::{
+    Color, Error, ExtendMode, GradientStop, LineCap, LineJoin, RoundFrom, RoundInto, StrokeStyle,
+};
 
 use crate::d2d::D2DFactory;
 
@@ -99,12 +102,12 @@ pub(crate) fn rect_to_rectf(rect: Rect) -> D2D1_RECT_F {
 }
 
 pub(crate) fn color_to_colorf(color: Color) -> D2D1_COLOR_F {
-    let rgba = color.as_rgba_u32();
+    let (r, g, b, a) = color.as_rgba();
     D2D1_COLOR_F {
-        r: (((rgba >> 24) & 255) as f32) * (1.0 / 255.0),
-        g: (((rgba >> 16) & 255) as f32) * (1.0 / 255.0),
-        b: (((rgba >> 8) & 255) as f32) * (1.0 / 255.0),
-        a: ((rgba & 255) as f32) * (1.0 / 255.0),
+        r: r as f32,
+        g: g as f32,
+        b: b as f32,
+        a: a as f32,
     }
 }
 
@@ -115,6 +118,14 @@ pub(crate) fn gradient_stop_to_d2d(stop: &GradientStop) -> D2D1_GRADIENT_STOP {
     }
 }
 
+pub(crate) fn convert_extend_mode(extend: ExtendMode) -> D2D1_EXTEND_MODE {
+    match extend {
+        ExtendMode::Pad => D2D1_EXTEND_MODE_CLAMP,
+        ExtendMode::Repeat => D2D1_EXTEND_MODE_WRAP,
+        ExtendMode::Reflect => D2D1_EXTEND_MODE_MIRROR,
+    }
+}
+
 fn convert_line_cap(line_cap: LineCap) -> D2D1_CAP_STYLE {
     match line_cap {
         LineCap::Butt => D2D1_CAP_STYLE_FLAT,
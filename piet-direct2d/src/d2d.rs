@@ -22,7 +22,7 @@ use winapi::um::d2d1::{
     ID2D1GradientStopCollection, ID2D1Image, ID2D1Layer, ID2D1PathGeometry, ID2D1SolidColorBrush,
     ID2D1StrokeStyle, D2D1_ANTIALIAS_MODE_PER_PRIMITIVE, D2D1_BEZIER_SEGMENT,
     D2D1_BITMAP_INTERPOLATION_MODE, D2D1_BRUSH_PROPERTIES, D2D1_COLOR_F, D2D1_DEBUG_LEVEL_WARNING,
-    D2D1_DRAW_TEXT_OPTIONS, D2D1_EXTEND_MODE_CLAMP, D2D1_FACTORY_OPTIONS,
+    D2D1_DRAW_TEXT_OPTIONS, D2D1_EXTEND_MODE, D2D1_FACTORY_OPTIONS,
     D2D1_FACTORY_TYPE_MULTI_THREADED, D2D1_FIGURE_BEGIN_FILLED, D2D1_FIGURE_BEGIN_HOLLOW,
     D2D1_FIGURE_END_CLOSED, D2D1_FIGURE_END_OPEN, D2D1_FILL_MODE_ALTERNATE, D2D1_FILL_MODE_WINDING,
     D2D1_GAMMA_2_2, D2D1_GRADIENT_STOP, D2D1_LAYER_OPTIONS_NONE, D2D1_LAYER_PARAMETERS,
@@ -93,6 +93,12 @@ pub struct Brush(ComPtr<ID2D1Brush>);
 
 pub struct Bitmap(ComPtr<ID2D1Bitmap1>);
 
+impl Brush {
+    pub(crate) fn as_raw(&self) -> *mut ID2D1Brush {
+        self.0.as_raw()
+    }
+}
+
 impl From<HRESULT> for Error {
     fn from(hr: HRESULT) -> Error {
         Error::WinapiError(hr)
@@ -386,6 +392,10 @@ impl DeviceContext {
 
     // Should be &mut layer?
     pub(crate) fn push_layer_mask(&mut self, mask: &PathGeometry, layer: &Layer) {
+        self.push_layer(Some(mask), 1.0, layer);
+    }
+
+    pub(crate) fn push_layer(&mut self, mask: Option<&PathGeometry>, opacity: f32, layer: &Layer) {
         unsafe {
             let params = D2D1_LAYER_PARAMETERS {
                 contentBounds: D2D1_RECT_F {
@@ -394,10 +404,12 @@ impl DeviceContext {
                     right: std::f32::INFINITY,
                     bottom: std::f32::INFINITY,
                 },
-                geometricMask: mask.0.as_raw() as *mut ID2D1Geometry,
+                geometricMask: mask
+                    .map(|m| m.0.as_raw() as *mut ID2D1Geometry)
+                    .unwrap_or(null_mut()),
                 maskAntialiasMode: D2D1_ANTIALIAS_MODE_PER_PRIMITIVE,
                 maskTransform: IDENTITY_MATRIX_3X2_F,
-                opacity: 1.0,
+                opacity,
                 opacityBrush: null_mut(),
                 layerOptions: D2D1_LAYER_OPTIONS_NONE,
             };
@@ -424,6 +436,7 @@ impl DeviceContext {
     pub(crate) fn create_gradient_stops(
         &mut self,
         stops: &[D2D1_GRADIENT_STOP],
+        extend: D2D1_EXTEND_MODE,
     ) -> Result<GradientStopCollection, Error> {
         unsafe {
             // Should this assert or should we return an overflow error? Super
@@ -436,7 +449,7 @@ impl DeviceContext {
                 stops.as_ptr(),
                 stops.len() as u32,
                 D2D1_GAMMA_2_2,
-                D2D1_EXTEND_MODE_CLAMP,
+                extend,
                 &mut ptr,
             );
             wrap(hr, ptr, GradientStopCollection)
@@ -7,12 +7,17 @@ use std::fmt::{Debug, Display, Formatter};
 use std::mem::MaybeUninit;
 use std::ptr::null_mut;
 
-use winapi::shared::winerror::{HRESULT, SUCCEEDED, S_OK};
+use winapi::shared::minwindef::FALSE;
+use winapi::shared::winerror::{DWRITE_E_NOFONT, HRESULT, SUCCEEDED, S_OK};
 use winapi::um::dwrite::{
-    DWriteCreateFactory, IDWriteFactory, IDWriteTextFormat, IDWriteTextLayout,
-    DWRITE_FACTORY_TYPE_SHARED, DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STYLE_NORMAL,
-    DWRITE_FONT_WEIGHT_NORMAL, DWRITE_HIT_TEST_METRICS, DWRITE_LINE_METRICS, DWRITE_TEXT_METRICS,
+    DWriteCreateFactory, IDWriteFactory, IDWriteFont, IDWriteFontCollection, IDWriteFontFamily,
+    IDWriteLocalizedStrings, IDWriteTextFormat, IDWriteTextLayout, DWRITE_FACTORY_TYPE_SHARED,
+    DWRITE_FONT_METRICS, DWRITE_FONT_STRETCH, DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STYLE,
+    DWRITE_FONT_STYLE_NORMAL, DWRITE_FONT_WEIGHT, DWRITE_FONT_WEIGHT_NORMAL,
+    DWRITE_HIT_TEST_METRICS, DWRITE_LINE_METRICS, DWRITE_READING_DIRECTION, DWRITE_TEXT_ALIGNMENT,
+    DWRITE_TEXT_METRICS, DWRITE_TEXT_RANGE,
 };
+use winapi::um::unknwnbase::IUnknown;
 use winapi::Interface;
 
 use wio::com::ComPtr;
@@ -41,6 +46,9 @@ pub struct TextFormatBuilder<'a> {
     factory: &'a DwriteFactory,
     size: Option<f32>,
     family: Option<&'a str>,
+    weight: DWRITE_FONT_WEIGHT,
+    style: DWRITE_FONT_STYLE,
+    stretch: DWRITE_FONT_STRETCH,
 }
 
 pub struct TextLayoutBuilder<'a> {
@@ -123,6 +131,56 @@ impl DwriteFactory {
     pub unsafe fn from_raw(raw: *mut IDWriteFactory) -> Self {
         Self(ComPtr::from_raw(raw))
     }
+
+    /// The names of the families of the installed fonts, in US English
+    /// where they have an English name.
+    pub fn system_family_names(&self) -> Result<Vec<String>, Error> {
+        unsafe {
+            let collection = self.system_font_collection()?;
+            let locale = "en-us".to_wide_null();
+            let mut names = Vec::new();
+            for i in 0..collection.GetFontFamilyCount() {
+                let mut family: *mut IDWriteFontFamily = null_mut();
+                check(collection.GetFontFamily(i, &mut family))?;
+                let family = ComPtr::from_raw(family);
+                let mut strings: *mut IDWriteLocalizedStrings = null_mut();
+                check(family.GetFamilyNames(&mut strings))?;
+                let strings = ComPtr::from_raw(strings);
+
+                let mut index = 0;
+                let mut exists = 0;
+                check(strings.FindLocaleName(locale.as_ptr(), &mut index, &mut exists))?;
+                if exists == 0 {
+                    index = 0;
+                }
+                let mut len = 0;
+                check(strings.GetStringLength(index, &mut len))?;
+                let mut name = vec![0u16; len as usize + 1];
+                check(strings.GetString(index, name.as_mut_ptr(), len + 1))?;
+                name.truncate(len as usize);
+                names.push(String::from_utf16_lossy(&name));
+            }
+            Ok(names)
+        }
+    }
+
+    /// Whether an installed font has a family name, in any language.
+    pub fn has_system_family(&self, name: &str) -> Result<bool, Error> {
+        unsafe {
+            let collection = self.system_font_collection()?;
+            let name = name.to_wide_null();
+            let mut index = 0;
+            let mut exists = 0;
+            check(collection.FindFamilyName(name.as_ptr(), &mut index, &mut exists))?;
+            Ok(exists != 0)
+        }
+    }
+
+    unsafe fn system_font_collection(&self) -> Result<ComPtr<IDWriteFontCollection>, Error> {
+        let mut collection: *mut IDWriteFontCollection = null_mut();
+        check(self.0.GetSystemFontCollection(&mut collection, FALSE))?;
+        Ok(ComPtr::from_raw(collection))
+    }
 }
 
 impl<'a> TextFormatBuilder<'a> {
@@ -131,6 +189,9 @@ impl<'a> TextFormatBuilder<'a> {
             factory,
             size: None,
             family: None,
+            weight: DWRITE_FONT_WEIGHT_NORMAL,
+            style: DWRITE_FONT_STYLE_NORMAL,
+            stretch: DWRITE_FONT_STRETCH_NORMAL,
         }
     }
 
@@ -144,6 +205,21 @@ impl<'a> TextFormatBuilder<'a> {
         self
     }
 
+    pub fn weight(mut self, weight: u16) -> TextFormatBuilder<'a> {
+        self.weight = weight.into();
+        self
+    }
+
+    pub fn style(mut self, style: DWRITE_FONT_STYLE) -> TextFormatBuilder<'a> {
+        self.style = style;
+        self
+    }
+
+    pub fn stretch(mut self, stretch: DWRITE_FONT_STRETCH) -> TextFormatBuilder<'a> {
+        self.stretch = stretch;
+        self
+    }
+
     pub fn build(self) -> Result<TextFormat, Error> {
         let family = self
             .family
@@ -156,9 +232,9 @@ impl<'a> TextFormatBuilder<'a> {
             let hr = self.factory.0.CreateTextFormat(
                 family.as_ptr(),
                 null_mut(), // collection
-                DWRITE_FONT_WEIGHT_NORMAL,
-                DWRITE_FONT_STYLE_NORMAL,
-                DWRITE_FONT_STRETCH_NORMAL,
+                self.weight,
+                self.style,
+                self.stretch,
                 size,
                 locale.as_ptr(),
                 &mut ptr,
@@ -168,6 +244,48 @@ impl<'a> TextFormatBuilder<'a> {
     }
 }
 
+impl TextFormat {
+    pub fn get_font_size(&self) -> f32 {
+        unsafe { self.0.GetFontSize() }
+    }
+
+    /// Get the metrics of the font that best matches the format, in design
+    /// units.
+    pub fn get_font_metrics(&self) -> Result<DWRITE_FONT_METRICS, Error> {
+        unsafe {
+            let mut collection: *mut IDWriteFontCollection = null_mut();
+            check(self.0.GetFontCollection(&mut collection))?;
+            let collection = ComPtr::from_raw(collection);
+
+            let len = self.0.GetFontFamilyNameLength() + 1;
+            let mut name = vec![0u16; len as usize];
+            check(self.0.GetFontFamilyName(name.as_mut_ptr(), len))?;
+            let mut index = 0;
+            let mut exists = 0;
+            check(collection.FindFamilyName(name.as_ptr(), &mut index, &mut exists))?;
+            if exists == 0 {
+                return Err(DWRITE_E_NOFONT.into());
+            }
+
+            let mut family: *mut IDWriteFontFamily = null_mut();
+            check(collection.GetFontFamily(index, &mut family))?;
+            let family = ComPtr::from_raw(family);
+            let mut font: *mut IDWriteFont = null_mut();
+            check(family.GetFirstMatchingFont(
+                self.0.GetFontWeight(),
+                self.0.GetFontStretch(),
+                self.0.GetFontStyle(),
+                &mut font,
+            ))?;
+            let font = ComPtr::from_raw(font);
+
+            let mut metrics = std::mem::zeroed();
+            font.GetMetrics(&mut metrics);
+            Ok(metrics)
+        }
+    }
+}
+
 impl<'a> TextLayoutBuilder<'a> {
     pub fn new(factory: &'a DwriteFactory) -> TextLayoutBuilder<'a> {
         TextLayoutBuilder {
@@ -257,6 +375,61 @@ impl TextLayout {
         self.0.as_raw()
     }
 
+    /// Set the font family for a range, in utf-16 code units.
+    pub fn set_font_family(&self, family: &str, range: (u32, u32)) -> Result<(), Error> {
+        let family = family.to_wide_null();
+        unsafe { check(self.0.SetFontFamilyName(family.as_ptr(), text_range(range))) }
+    }
+
+    pub fn set_font_size(&self, size: f32, range: (u32, u32)) -> Result<(), Error> {
+        unsafe { check(self.0.SetFontSize(size, text_range(range))) }
+    }
+
+    pub fn set_font_weight(&self, weight: u16, range: (u32, u32)) -> Result<(), Error> {
+        unsafe { check(self.0.SetFontWeight(weight.into(), text_range(range))) }
+    }
+
+    pub fn set_font_style(&self, style: DWRITE_FONT_STYLE, range: (u32, u32)) -> Result<(), Error> {
+        unsafe { check(self.0.SetFontStyle(style, text_range(range))) }
+    }
+
+    pub fn set_underline(&self, underline: bool, range: (u32, u32)) -> Result<(), Error> {
+        unsafe { check(self.0.SetUnderline(underline.into(), text_range(range))) }
+    }
+
+    pub fn set_strikethrough(&self, strikethrough: bool, range: (u32, u32)) -> Result<(), Error> {
+        unsafe {
+            check(
+                self.0
+                    .SetStrikethrough(strikethrough.into(), text_range(range)),
+            )
+        }
+    }
+
+    pub fn set_text_alignment(&self, alignment: DWRITE_TEXT_ALIGNMENT) -> Result<(), Error> {
+        unsafe { check(self.0.SetTextAlignment(alignment)) }
+    }
+
+    pub fn set_reading_direction(&self, direction: DWRITE_READING_DIRECTION) -> Result<(), Error> {
+        unsafe { check(self.0.SetReadingDirection(direction)) }
+    }
+
+    pub fn set_max_width(&self, width: f32) -> Result<(), Error> {
+        unsafe { check(self.0.SetMaxWidth(width)) }
+    }
+
+    /// Set the object used to draw a range, such as a Direct2D brush.
+    ///
+    /// # Safety
+    /// The effect must be a valid COM object.
+    pub unsafe fn set_drawing_effect(
+        &self,
+        effect: *mut IUnknown,
+        range: (u32, u32),
+    ) -> Result<(), Error> {
+        check(self.0.SetDrawingEffect(effect, text_range(range)))
+    }
+
     pub fn get_metrics(&self) -> DWRITE_TEXT_METRICS {
         unsafe {
             let mut result = std::mem::zeroed();
@@ -311,6 +484,21 @@ impl TextLayout {
     }
 }
 
+fn text_range((start, length): (u32, u32)) -> DWRITE_TEXT_RANGE {
+    DWRITE_TEXT_RANGE {
+        startPosition: start,
+        length,
+    }
+}
+
+fn check(hr: HRESULT) -> Result<(), Error> {
+    if SUCCEEDED(hr) {
+        Ok(())
+    } else {
+        Err(hr.into())
+    }
+}
+
 #[derive(Copy, Clone)]
 /// Results from calling `hit_test_point` on a TextLayout.
 pub struct HitTestPoint {
@@ -19,11 +19,11 @@ use winapi::um::d2d1::{
 };
 use winapi::um::dcommon::{D2D1_ALPHA_MODE_IGNORE, D2D1_ALPHA_MODE_PREMULTIPLIED};
 
-use piet::kurbo::{Affine, PathEl, Point, Rect, Shape};
+use piet::kurbo::{Affine, PathEl, Point, Rect, Shape, Size};
 
 use piet::{
-    new_error, Color, Error, ErrorKind, FixedGradient, ImageFormat, InterpolationMode, IntoBrush,
-    RenderContext, StrokeStyle,
+    new_error, BlendMode, Color, Error, ErrorKind, ExtendMode, FixedGradient, ImageFormat,
+    InterpolationMode, IntoBrush, RenderContext, StrokeStyle,
 };
 
 pub use crate::d2d::{D2DDevice, D2DFactory, DeviceContext as D2DDeviceContext};
@@ -31,8 +31,8 @@ pub use crate::dwrite::DwriteFactory;
 pub use crate::text::{D2DFont, D2DFontBuilder, D2DText, D2DTextLayout, D2DTextLayoutBuilder};
 
 use crate::conv::{
-    affine_to_matrix3x2f, color_to_colorf, convert_stroke_style, gradient_stop_to_d2d,
-    rect_to_rectf, to_point2f,
+    affine_to_matrix3x2f, color_to_colorf, convert_extend_mode, convert_stroke_style,
+    gradient_stop_to_d2d, rect_to_rectf, to_point2f,
 };
 use crate::d2d::{Bitmap, Brush, DeviceContext, FillRule, PathGeometry};
 
@@ -54,6 +54,9 @@ struct CtxState {
     // Note: when we start pushing both layers and axis aligned clips, this will
     // need to keep track of which is which. But for now, keep it simple.
     n_layers_pop: usize,
+
+    /// Whether this state was pushed by `push_layer` rather than `save`.
+    is_layer: bool,
 }
 
 impl<'b, 'a: 'b> D2DRenderContext<'a> {
@@ -144,6 +147,10 @@ impl<'a> RenderContext for D2DRenderContext<'a> {
         std::mem::replace(&mut self.err, Ok(()))
     }
 
+    fn report_error(&mut self, err: Error) {
+        self.err = Err(err);
+    }
+
     fn clear(&mut self, color: Color) {
         self.rt.clear(color_to_colorf(color));
     }
@@ -162,7 +169,8 @@ impl<'a> RenderContext for D2DRenderContext<'a> {
                     endPoint: to_point2f(linear.end),
                 };
                 let stops: Vec<_> = linear.stops.iter().map(gradient_stop_to_d2d).collect();
-                let stops = self.rt.create_gradient_stops(&stops)?;
+                let extend = convert_extend_mode(linear.extend);
+                let stops = self.rt.create_gradient_stops(&stops, extend)?;
                 let result = self.rt.create_linear_gradient(&props, &stops)?;
                 Ok(result)
             }
@@ -174,13 +182,25 @@ impl<'a> RenderContext for D2DRenderContext<'a> {
                     radiusY: radial.radius as f32,
                 };
                 let stops: Vec<_> = radial.stops.iter().map(gradient_stop_to_d2d).collect();
-                let stops = self.rt.create_gradient_stops(&stops)?;
+                let extend = convert_extend_mode(radial.extend);
+                let stops = self.rt.create_gradient_stops(&stops, extend)?;
                 let result = self.rt.create_radial_gradient(&props, &stops)?;
                 Ok(result)
             }
+            FixedGradient::Sweep(_) => Err(new_error(ErrorKind::NotSupported)),
         }
     }
 
+    fn image_pattern(
+        &mut self,
+        _image: &Self::Image,
+        _transform: Affine,
+        _extend: ExtendMode,
+    ) -> Result<Brush, Error> {
+        // TODO: create a bitmap brush, with the transform and extend modes.
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
     fn fill(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
         // TODO: various special-case shapes, for efficiency
         let brush = brush.make_brush(self, || shape.bounding_box());
@@ -199,6 +219,11 @@ impl<'a> RenderContext for D2DRenderContext<'a> {
         }
     }
 
+    fn blurred_rect(&mut self, _rect: Rect, _blur_radius: f64, _brush: &impl IntoBrush<Self>) {
+        // TODO: fill through an opacity mask made with `piet::compute_blurred_rect`.
+        self.err = Err(new_error(ErrorKind::NotSupported));
+    }
+
     fn stroke(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>, width: f64) {
         let brush = brush.make_brush(self, || shape.bounding_box());
         // TODO: various special-case shapes, for efficiency
@@ -277,6 +302,18 @@ impl<'a> RenderContext for D2DRenderContext<'a> {
         let mut pos = to_point2f(pos.into());
         pos.y -= line_metrics[0].baseline;
         let text_options = D2D1_DRAW_TEXT_OPTIONS_NONE;
+        // Colored ranges are drawn with brushes set as drawing effects.
+        for (range, color) in &layout.colors {
+            let brush = self.solid_brush(color.clone());
+            let result = unsafe {
+                layout
+                    .layout
+                    .set_drawing_effect(brush.as_raw() as *mut _, *range)
+            };
+            if let Err(e) = result {
+                self.err = Err(e.into());
+            }
+        }
 
         self.rt
             .draw_text_layout(pos, &layout.layout, &*brush, text_options);
@@ -286,13 +323,14 @@ impl<'a> RenderContext for D2DRenderContext<'a> {
         let new_state = CtxState {
             transform: self.current_transform(),
             n_layers_pop: 0,
+            is_layer: false,
         };
         self.ctx_stack.push(new_state);
         Ok(())
     }
 
     fn restore(&mut self) -> Result<(), Error> {
-        if self.ctx_stack.len() <= 1 {
+        if self.ctx_stack.len() <= 1 || self.ctx_stack.last().unwrap().is_layer {
             return Err(new_error(ErrorKind::StackUnbalance));
         }
         self.pop_state();
@@ -302,6 +340,48 @@ impl<'a> RenderContext for D2DRenderContext<'a> {
         Ok(())
     }
 
+    fn push_layer(&mut self, opacity: f64, clip: Option<Rect>) -> Result<(), Error> {
+        let layer = self.rt.create_layer(None)?;
+        let mask = match clip {
+            Some(rect) => Some(path_from_shape(
+                self.factory,
+                true,
+                rect,
+                FillRule::NonZero,
+            )?),
+            None => None,
+        };
+        self.save()?;
+        self.rt.push_layer(mask.as_ref(), opacity as f32, &layer);
+        let state = self.ctx_stack.last_mut().unwrap();
+        state.n_layers_pop += 1;
+        state.is_layer = true;
+        Ok(())
+    }
+
+    fn pop_layer(&mut self) -> Result<(), Error> {
+        if self.ctx_stack.len() <= 1 || !self.ctx_stack.last().unwrap().is_layer {
+            return Err(new_error(ErrorKind::StackUnbalance));
+        }
+        self.pop_state();
+        self.rt
+            .set_transform(&affine_to_matrix3x2f(self.current_transform()));
+        Ok(())
+    }
+
+    fn push_image_mask(&mut self, _image: &Self::Image, _dst_rect: Rect) -> Result<(), Error> {
+        // TODO: push a layer with a bitmap opacity brush.
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn push_mask(&mut self) -> Result<(), Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn pop_mask(&mut self) -> Result<(), Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
     // Discussion question: should this subsume EndDraw, with BeginDraw on
     // D2DRenderContext creation? I'm thinking not, as the shell might want
     // to do other stuff, possibly related to incremental paint.
@@ -319,6 +399,13 @@ impl<'a> RenderContext for D2DRenderContext<'a> {
             .set_transform(&affine_to_matrix3x2f(self.current_transform()));
     }
 
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        // TODO: modes other than source-over need Direct2D effects.
+        if mode != BlendMode::SourceOver {
+            self.err = Err(new_error(ErrorKind::NotSupported));
+        }
+    }
+
     fn current_transform(&self) -> Affine {
         // This is an unwrap because we protect the invariant.
         self.ctx_stack.last().unwrap().transform
@@ -371,6 +458,27 @@ impl<'a> RenderContext for D2DRenderContext<'a> {
         Ok(bitmap)
     }
 
+    fn create_offscreen(
+        &mut self,
+        _size: Size,
+        _f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<Self::Image, Error> {
+        // TODO: draw into a bitmap set as the device context's target.
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn capture_image_area(&mut self, _src_rect: impl Into<Rect>) -> Result<Self::Image, Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn read_image(
+        &mut self,
+        _image: &Self::Image,
+        _format: ImageFormat,
+    ) -> Result<(usize, usize, Vec<u8>), Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
     #[inline]
     fn draw_image(
         &mut self,
@@ -4,12 +4,27 @@ pub use d2d::{D2DDevice, D2DFactory, DeviceContext as D2DDeviceContext};
 pub use dwrite::DwriteFactory;
 
 use std::convert::TryInto;
+use std::ops::{Range, RangeBounds};
 
 use piet::kurbo::Point;
 
 use piet::{
-    Error, Font, FontBuilder, HitTestMetrics, HitTestPoint, HitTestTextPosition, Text, TextLayout,
-    TextLayoutBuilder,
+    new_error, AttributeSpans, Color, Error, ErrorKind, Font, FontBuilder, FontMetrics,
+    FontStretch, FontStyle, FontWeight, GenericFamily, HitTestMetrics, HitTestPoint,
+    HitTestTextPosition, LineMetric, Text, TextAlignment, TextAttribute, TextLayout,
+    TextLayoutBuilder, TextLayoutMetrics,
+};
+
+use unicode_bidi::{get_base_direction, Direction};
+use winapi::um::dwrite::{
+    DWRITE_FONT_STRETCH, DWRITE_FONT_STRETCH_CONDENSED, DWRITE_FONT_STRETCH_EXPANDED,
+    DWRITE_FONT_STRETCH_EXTRA_CONDENSED, DWRITE_FONT_STRETCH_EXTRA_EXPANDED,
+    DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STRETCH_SEMI_CONDENSED,
+    DWRITE_FONT_STRETCH_SEMI_EXPANDED, DWRITE_FONT_STRETCH_ULTRA_CONDENSED,
+    DWRITE_FONT_STRETCH_ULTRA_EXPANDED, DWRITE_FONT_STYLE, DWRITE_FONT_STYLE_ITALIC,
+    DWRITE_FONT_STYLE_NORMAL, DWRITE_FONT_STYLE_OBLIQUE, DWRITE_READING_DIRECTION_RIGHT_TO_LEFT,
+    DWRITE_TEXT_ALIGNMENT_CENTER, DWRITE_TEXT_ALIGNMENT_JUSTIFIED, DWRITE_TEXT_ALIGNMENT_LEADING,
+    DWRITE_TEXT_ALIGNMENT_TRAILING,
 };
 
 use crate::d2d;
@@ -19,7 +34,11 @@ pub struct D2DText<'a> {
     dwrite: &'a DwriteFactory,
 }
 
-pub struct D2DFont(TextFormat);
+pub struct D2DFont {
+    format: TextFormat,
+    /// The installed family the font resolved to.
+    family: String,
+}
 
 pub struct D2DFontBuilder<'a> {
     builder: TextFormatBuilder<'a>,
@@ -29,11 +48,35 @@ pub struct D2DFontBuilder<'a> {
 pub struct D2DTextLayout {
     pub text: String,
     pub layout: dwrite::TextLayout,
+    /// Ranges in utf-16 code units with their own colors, which are set as
+    /// drawing effects when the layout is drawn.
+    pub(crate) colors: Vec<((u32, u32), Color)>,
+    lines: Vec<D2DLine>,
+    /// The offset of the first baseline from the top of the layout.
+    first_baseline: f64,
+    /// The metrics of the layout's font, for what DirectWrite's line
+    /// metrics don't include.
+    font_metrics: FontMetrics,
+}
+
+/// A line of a layout, from DirectWrite's line metrics.
+struct D2DLine {
+    /// The text of the line, including trailing whitespace and newline.
+    range: Range<usize>,
+    /// The offset of the baseline from the baseline of the first line.
+    baseline: f64,
+    ascent: f64,
+    height: f64,
 }
 
 pub struct D2DTextLayoutBuilder<'a> {
+    dwrite: &'a DwriteFactory,
     text: String,
     builder: dwrite::TextLayoutBuilder<'a>,
+    attributes: AttributeSpans,
+    max_width: Option<f64>,
+    alignment: TextAlignment,
+    font_metrics: FontMetrics,
 }
 
 impl<'a> D2DText<'a> {
@@ -56,16 +99,39 @@ impl<'a> Text for D2DText<'a> {
         let builder = TextFormatBuilder::new(self.dwrite).size(size as f32);
         D2DFontBuilder {
             builder,
-            name: name.to_owned(),
+            name: resolve_family(self.dwrite, name),
         }
     }
 
+    /// Not supported yet: DirectWrite can only use fonts from memory through
+    /// a custom font collection loader.
+    fn load_font(&mut self, _data: &[u8]) -> Result<String, Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn font_families(&mut self) -> Vec<String> {
+        let mut families = self.dwrite.system_family_names().unwrap_or_default();
+        families.sort();
+        families.dedup();
+        families
+    }
+
+    fn has_font_family(&mut self, name: &str) -> bool {
+        GenericFamily::from_name(name).is_some()
+            || self.dwrite.has_system_family(name).unwrap_or(false)
+    }
+
     fn new_text_layout(&mut self, font: &Self::Font, text: &str) -> Self::TextLayoutBuilder {
         D2DTextLayoutBuilder {
+            dwrite: self.dwrite,
             text: text.to_owned(),
             builder: dwrite::TextLayoutBuilder::new(self.dwrite)
-                .format(&font.0)
+                .format(&font.format)
                 .text(text),
+            attributes: AttributeSpans::new(),
+            max_width: None,
+            alignment: TextAlignment::default(),
+            font_metrics: font.metrics(),
         }
     }
 }
@@ -73,36 +139,213 @@ impl<'a> Text for D2DText<'a> {
 impl<'a> FontBuilder for D2DFontBuilder<'a> {
     type Out = D2DFont;
 
+    fn weight(mut self, weight: FontWeight) -> Self {
+        self.builder = self.builder.weight(weight.to_raw());
+        self
+    }
+
+    fn style(mut self, style: FontStyle) -> Self {
+        self.builder = self.builder.style(dwrite_style(style));
+        self
+    }
+
+    fn stretch(mut self, stretch: FontStretch) -> Self {
+        self.builder = self.builder.stretch(dwrite_stretch(stretch));
+        self
+    }
+
     fn build(self) -> Result<Self::Out, Error> {
-        Ok(D2DFont(self.builder.family(&self.name).build()?))
+        let format = self.builder.family(&self.name).build()?;
+        Ok(D2DFont {
+            format,
+            family: self.name,
+        })
     }
 }
 
-impl Font for D2DFont {}
+impl Font for D2DFont {
+    fn metrics(&self) -> FontMetrics {
+        let size = self.format.get_font_size() as f64;
+        let metrics = match self.format.get_font_metrics() {
+            Ok(metrics) => metrics,
+            // No font matched the family, and DirectWrite uses a fallback.
+            Err(_) => return FontMetrics::approximate(size),
+        };
+        let scale = size / metrics.designUnitsPerEm as f64;
+        FontMetrics {
+            ascent: metrics.ascent as f64 * scale,
+            descent: metrics.descent as f64 * scale,
+            line_gap: metrics.lineGap as f64 * scale,
+            x_height: metrics.xHeight as f64 * scale,
+            cap_height: metrics.capHeight as f64 * scale,
+            // DirectWrite measures upwards from the baseline.
+            underline_position: -metrics.underlinePosition as f64 * scale,
+            underline_thickness: metrics.underlineThickness as f64 * scale,
+        }
+    }
+
+    fn family_name(&self) -> &str {
+        &self.family
+    }
+}
 
 impl<'a> TextLayoutBuilder for D2DTextLayoutBuilder<'a> {
     type Out = D2DTextLayout;
 
+    fn range_attribute(
+        mut self,
+        range: impl RangeBounds<usize>,
+        attribute: impl Into<TextAttribute>,
+    ) -> Self {
+        self.attributes.add(&self.text, range, attribute.into());
+        self
+    }
+
+    fn max_width(mut self, width: f64) -> Self {
+        self.max_width = Some(width);
+        self
+    }
+
+    fn alignment(mut self, alignment: TextAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
     fn build(self) -> Result<Self::Out, Error> {
+        let layout = self
+            .builder
+            .width(self.max_width.unwrap_or(1e6) as f32)
+            .height(1e6)
+            .build()?;
+        let mut colors = Vec::new();
+        // DirectWrite applies later settings over earlier ones, as piet does.
+        for (range, attribute) in self.attributes.iter() {
+            let range = utf16_range(&self.text, range);
+            match attribute {
+                TextAttribute::FontFamily(family) => {
+                    layout.set_font_family(&resolve_family(self.dwrite, family), range)?
+                }
+                TextAttribute::FontSize(size) => layout.set_font_size(*size as f32, range)?,
+                TextAttribute::Weight(weight) => layout.set_font_weight(weight.to_raw(), range)?,
+                TextAttribute::Style(style) => {
+                    layout.set_font_style(dwrite_style(*style), range)?
+                }
+                TextAttribute::ForegroundColor(color) => colors.push((range, color.clone())),
+                TextAttribute::Underline(underline) => layout.set_underline(*underline, range)?,
+                TextAttribute::Strikethrough(strikethrough) => {
+                    layout.set_strikethrough(*strikethrough, range)?
+                }
+            }
+        }
+        // DirectWrite has one reading direction for the layout, so it's
+        // that of the first paragraph.
+        let rtl = get_base_direction(self.text.as_str()) == Direction::Rtl;
+        if rtl {
+            layout.set_reading_direction(DWRITE_READING_DIRECTION_RIGHT_TO_LEFT)?;
+        }
+        layout.set_text_alignment(match self.alignment {
+            TextAlignment::Start => DWRITE_TEXT_ALIGNMENT_LEADING,
+            TextAlignment::End => DWRITE_TEXT_ALIGNMENT_TRAILING,
+            TextAlignment::Center => DWRITE_TEXT_ALIGNMENT_CENTER,
+            TextAlignment::Justified => DWRITE_TEXT_ALIGNMENT_JUSTIFIED,
+        })?;
+        // Without a width, lines are aligned within the widest one.
+        if self.max_width.is_none() && (rtl || self.alignment != TextAlignment::Start) {
+            let width = layout.get_metrics().widthIncludingTrailingWhitespace;
+            layout.set_max_width(width)?;
+        }
+
+        // The lines depend on the attributes, so are found after setting them.
+        let mut line_metrics = Vec::new();
+        layout.get_line_metrics(&mut line_metrics);
+        let first_baseline = line_metrics
+            .first()
+            .map(|metrics| metrics.baseline as f64)
+            .unwrap_or(0.0);
+        let mut start = 0;
+        let mut start_16 = 0;
+        let mut top = 0.0;
+        let lines = line_metrics
+            .iter()
+            .map(|metrics| {
+                let end_16 = start_16 + metrics.length as usize;
+                let end = count_until_utf16(&self.text, end_16).unwrap_or_else(|| self.text.len());
+                let line = D2DLine {
+                    range: start..end,
+                    baseline: top + metrics.baseline as f64 - first_baseline,
+                    ascent: metrics.baseline as f64,
+                    height: metrics.height as f64,
+                };
+                top += metrics.height as f64;
+                start = end;
+                start_16 = end_16;
+                line
+            })
+            .collect();
         Ok(D2DTextLayout {
             text: self.text,
-            layout: self
-                .builder
-                .width(1e6) // TODO: probably want to support wrapping
-                .height(1e6)
-                .build()?,
+            layout,
+            colors,
+            lines,
+            first_baseline,
+            font_metrics: self.font_metrics,
         })
     }
 }
 
+/// The start and length of a range of `text` in utf-16 code units.
+fn utf16_range(text: &str, range: Range<usize>) -> (u32, u32) {
+    let start = count_utf16(&text[..range.start]);
+    let len = count_utf16(&text[range]);
+    (start as u32, len as u32)
+}
+
 impl TextLayout for D2DTextLayout {
     fn width(&self) -> f64 {
         self.layout.get_metrics().width as f64
     }
 
+    fn metrics(&self) -> TextLayoutMetrics {
+        let line_gap = self.font_metrics.line_gap;
+        let descent = match self.lines.last() {
+            Some(last) => last.height - last.ascent - line_gap,
+            None => 0.0,
+        };
+        TextLayoutMetrics {
+            height: self.lines.iter().map(|line| line.height).sum(),
+            ascent: self.first_baseline,
+            descent,
+            line_gap,
+            line_count: self.lines.len(),
+        }
+    }
+
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn line_metric(&self, line_number: usize) -> Option<LineMetric> {
+        let line = self.lines.get(line_number)?;
+        Some(LineMetric {
+            start_offset: line.range.start,
+            end_offset: line.range.end,
+            baseline: line.baseline,
+            ascent: line.ascent,
+            height: line.height,
+        })
+    }
+
+    fn line_text(&self, line_number: usize) -> Option<&str> {
+        let line = self.lines.get(line_number)?;
+        Some(&self.text[line.range.clone()])
+    }
+
     fn hit_test_point(&self, point: Point) -> HitTestPoint {
+        // DirectWrite measures from the top of the layout, piet from the
+        // first baseline.
+        let y = point.y + self.first_baseline;
         // lossy from f64 to f32, but shouldn't have too much impact
-        let htp = self.layout.hit_test_point(point.x as f32, point.y as f32);
+        let htp = self.layout.hit_test_point(point.x as f32, y as f32);
 
         // Round up to next grapheme cluster boundary if directwrite
         // reports a trailing hit.
@@ -147,13 +390,22 @@ impl TextLayout for D2DTextLayout {
         // TODO quick fix until directwrite fixes bool bug
         let trailing = true;
 
+        // A position at a line break is on the line after it.
+        let baseline = self
+            .lines
+            .iter()
+            .rev()
+            .find(|line| line.range.start <= text_position)
+            .map(|line| line.baseline)
+            .unwrap_or(0.0);
+
         self.layout
             .hit_test_text_position(idx_16, trailing)
             .map(|http| {
                 HitTestTextPosition {
                     point: Point {
                         x: http.point_x as f64,
-                        y: http.point_y as f64,
+                        y: baseline,
                     },
                     metrics: HitTestMetrics {
                         text_position, // no need to use directwrite return value
@@ -202,6 +454,45 @@ pub(crate) fn count_until_utf16(s: &str, utf16_text_position: usize) -> Option<u
     None
 }
 
+fn dwrite_style(style: FontStyle) -> DWRITE_FONT_STYLE {
+    match style {
+        FontStyle::Regular => DWRITE_FONT_STYLE_NORMAL,
+        FontStyle::Italic => DWRITE_FONT_STYLE_ITALIC,
+        FontStyle::Oblique => DWRITE_FONT_STYLE_OBLIQUE,
+    }
+}
+
+fn dwrite_stretch(stretch: FontStretch) -> DWRITE_FONT_STRETCH {
+    match stretch {
+        FontStretch::UltraCondensed => DWRITE_FONT_STRETCH_ULTRA_CONDENSED,
+        FontStretch::ExtraCondensed => DWRITE_FONT_STRETCH_EXTRA_CONDENSED,
+        FontStretch::Condensed => DWRITE_FONT_STRETCH_CONDENSED,
+        FontStretch::SemiCondensed => DWRITE_FONT_STRETCH_SEMI_CONDENSED,
+        FontStretch::Normal => DWRITE_FONT_STRETCH_NORMAL,
+        FontStretch::SemiExpanded => DWRITE_FONT_STRETCH_SEMI_EXPANDED,
+        FontStretch::Expanded => DWRITE_FONT_STRETCH_EXPANDED,
+        FontStretch::ExtraExpanded => DWRITE_FONT_STRETCH_EXTRA_EXPANDED,
+        FontStretch::UltraExpanded => DWRITE_FONT_STRETCH_ULTRA_EXPANDED,
+    }
+}
+
+/// The installed family DirectWrite draws a family with. Generic families
+/// are standard Windows fonts, and missing families fall back to the user
+/// interface font.
+fn resolve_family(dwrite: &DwriteFactory, name: &str) -> String {
+    let name = match GenericFamily::from_name(name) {
+        Some(GenericFamily::Serif) => "Times New Roman",
+        Some(GenericFamily::SansSerif) => "Segoe UI",
+        Some(GenericFamily::Monospace) => "Consolas",
+        Some(GenericFamily::SystemUi) => "Segoe UI",
+        None => name,
+    };
+    match dwrite.has_system_family(name) {
+        Ok(false) => "Segoe UI".to_owned(),
+        _ => name.to_owned(),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -523,6 +814,26 @@ mod test {
         assert_eq!(pt.metrics.text_position, 14);
     }
 
+    #[test]
+    fn test_font_family() {
+        let dwrite = DwriteFactory::new().unwrap();
+        let mut text = D2DText::new(&dwrite);
+        assert!(text.has_font_family("segoe ui"));
+        assert!(text.has_font_family("system-ui"));
+        assert!(text
+            .font_families()
+            .iter()
+            .any(|family| family == "Segoe UI"));
+
+        let font = text.new_font_by_name("serif", 12.0).build().unwrap();
+        assert_eq!(font.family_name(), "Times New Roman");
+        let font = text
+            .new_font_by_name("No Such Family", 12.0)
+            .build()
+            .unwrap();
+        assert_eq!(font.family_name(), "Segoe UI");
+    }
+
     #[test]
     fn test_count_until_utf16() {
         // Notes on this input:
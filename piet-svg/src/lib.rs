@@ -1,16 +1,20 @@
 //! SVG output support for piet
 //!
-//! Text and images are unimplemented and will always return errors.
+//! Text is written with font names, to be laid out by the program showing
+//! the SVG, so layouts can't be measured. Loaded fonts are embedded in the
+//! document. Pixel images are unimplemented and
+//! will always return errors. Images can be made with `create_offscreen`,
+//! which keeps the drawing as vector content.
 
 mod text;
 
 use std::borrow::Cow;
 use std::{io, mem};
 
-use piet::kurbo::{Affine, Point, Rect, Shape};
+use piet::kurbo::{Affine, Point, Rect, Shape, Size};
 use piet::{
-    new_error, Color, Error, ErrorKind, FixedGradient, ImageFormat, InterpolationMode, IntoBrush,
-    LineCap, LineJoin, StrokeStyle,
+    new_error, BlendMode, Color, Error, ErrorKind, ExtendMode, FixedGradient, FixedSweepGradient,
+    ImageFormat, InterpolationMode, IntoBrush, LineCap, LineJoin, StrokeStyle,
 };
 use svg::node::Node;
 
@@ -22,9 +26,11 @@ type Result<T> = std::result::Result<T, Error>;
 pub struct RenderContext {
     stack: Vec<State>,
     state: State,
+    layers: Vec<Layer>,
     doc: svg::Document,
     next_id: u64,
     text: Text,
+    err: Result<()>,
 }
 
 impl RenderContext {
@@ -34,9 +40,11 @@ impl RenderContext {
         Self {
             stack: Vec::new(),
             state: State::default(),
+            layers: Vec::new(),
             doc: svg::Document::new(),
             next_id: 0,
             text: Text::new(),
+            err: Ok(()),
         }
     }
 
@@ -44,7 +52,10 @@ impl RenderContext {
     ///
     /// Additional rendering can be done afterwards.
     pub fn write(&self, writer: impl io::Write) -> io::Result<()> {
-        svg::write(writer, &self.doc)
+        match self.text.font_faces() {
+            Some(style) => svg::write(writer, &self.doc.clone().add(style)),
+            None => svg::write(writer, &self.doc),
+        }
     }
 
     fn new_id(&mut self) -> Id {
@@ -52,6 +63,22 @@ impl RenderContext {
         self.next_id += 1;
         x
     }
+
+    /// Append a drawn node to the innermost layer, or the document if there is none.
+    fn append(&mut self, node: impl Node) {
+        match self.layers.last_mut() {
+            Some(layer) => layer.group.append(node),
+            None => self.doc.append(node),
+        }
+    }
+
+    /// Add a drawn shape to the innermost layer, or the document if there is none.
+    fn add_shape(&mut self, shape: impl Shape, attrs: &Attrs) {
+        match self.layers.last_mut() {
+            Some(layer) => add_shape(&mut layer.group, shape, attrs),
+            None => add_shape(&mut self.doc, shape, attrs),
+        }
+    }
 }
 
 impl piet::RenderContext for RenderContext {
@@ -63,7 +90,11 @@ impl piet::RenderContext for RenderContext {
     type Image = Image;
 
     fn status(&mut self) -> Result<()> {
-        Ok(())
+        mem::replace(&mut self.err, Ok(()))
+    }
+
+    fn report_error(&mut self, err: Error) {
+        self.err = Err(err);
     }
 
     fn clear(&mut self, color: Color) {
@@ -75,7 +106,7 @@ impl piet::RenderContext for RenderContext {
         if let Some(id) = self.state.clip {
             rect.assign("clip-path", format!("url(#{})", id.to_string()));
         }
-        self.doc.append(rect);
+        self.append(rect);
     }
 
     fn solid_brush(&mut self, color: Color) -> Brush {
@@ -85,8 +116,12 @@ impl piet::RenderContext for RenderContext {
     }
 
     fn gradient(&mut self, gradient: impl Into<FixedGradient>) -> Result<Brush> {
+        let gradient = gradient.into();
+        gradient
+            .validate()
+            .map_err(|e| e.with_context("gradient"))?;
         let id = self.new_id();
-        match gradient.into() {
+        match gradient {
             FixedGradient::Linear(x) => {
                 let mut gradient = svg::node::element::LinearGradient::new()
                     .set("gradientUnits", "userSpaceOnUse")
@@ -95,6 +130,9 @@ impl piet::RenderContext for RenderContext {
                     .set("y1", x.start.y)
                     .set("x2", x.end.x)
                     .set("y2", x.end.y);
+                if let Some(spread) = spread_method(x.extend) {
+                    gradient.assign("spreadMethod", spread);
+                }
                 for stop in x.stops {
                     gradient.append(
                         svg::node::element::Stop::new()
@@ -113,6 +151,9 @@ impl piet::RenderContext for RenderContext {
                     .set("fx", x.center.x + x.origin_offset.x)
                     .set("fy", x.center.y + x.origin_offset.y)
                     .set("r", x.radius);
+                if let Some(spread) = spread_method(x.extend) {
+                    gradient.assign("spreadMethod", spread);
+                }
                 for stop in x.stops {
                     gradient.append(
                         svg::node::element::Stop::new()
@@ -122,20 +163,33 @@ impl piet::RenderContext for RenderContext {
                 }
                 self.doc.append(gradient);
             }
+            FixedGradient::Sweep(x) => {
+                self.doc.append(sweep_pattern(id, &x));
+            }
         }
         Ok(Brush {
             kind: BrushKind::Ref(id),
         })
     }
 
+    fn image_pattern(
+        &mut self,
+        _image: &Self::Image,
+        _transform: Affine,
+        _extend: ExtendMode,
+    ) -> Result<Brush> {
+        // Images are unimplemented; once they are, this can be a `<pattern>`.
+        Err(new_error(ErrorKind::NotSupported).with_context("image_pattern"))
+    }
+
     fn fill(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
         let brush = brush.make_brush(self, || shape.bounding_box());
-        add_shape(
-            &mut self.doc,
+        self.add_shape(
             shape,
             &Attrs {
                 xf: self.state.xf,
                 clip: self.state.clip,
+                blend_mode: self.state.blend_mode,
                 fill: Some((brush.into_owned(), None)),
                 ..Attrs::default()
             },
@@ -144,18 +198,54 @@ impl piet::RenderContext for RenderContext {
 
     fn fill_even_odd(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
         let brush = brush.make_brush(self, || shape.bounding_box());
-        add_shape(
-            &mut self.doc,
+        self.add_shape(
             shape,
             &Attrs {
                 xf: self.state.xf,
                 clip: self.state.clip,
+                blend_mode: self.state.blend_mode,
                 fill: Some((brush.into_owned(), Some("evenodd"))),
                 ..Attrs::default()
             },
         );
     }
 
+    fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &impl IntoBrush<Self>) {
+        let brush = brush.make_brush(self, || rect);
+        let rect = rect.abs();
+        let id = self.new_id();
+        let extent = 2.5 * blur_radius;
+        let region = rect.inflate(extent, extent);
+        // The svg crate has no type for filter primitives.
+        let mut blur = svg::node::element::Element::new("feGaussianBlur");
+        blur.assign("stdDeviation", blur_radius);
+        let filter = svg::node::element::Filter::new()
+            .set("id", id)
+            .set("filterUnits", "userSpaceOnUse")
+            .set("x", region.x0)
+            .set("y", region.y0)
+            .set("width", region.width())
+            .set("height", region.height())
+            .add(blur);
+        self.doc.append(filter);
+
+        let mut node = svg::node::element::Rectangle::new()
+            .set("x", rect.x0)
+            .set("y", rect.y0)
+            .set("width", rect.width())
+            .set("height", rect.height())
+            .set("filter", format!("url(#{})", id.to_string()));
+        Attrs {
+            xf: self.state.xf,
+            clip: self.state.clip,
+            blend_mode: self.state.blend_mode,
+            fill: Some((brush.into_owned(), None)),
+            ..Attrs::default()
+        }
+        .apply_to(&mut node);
+        self.append(node);
+    }
+
     fn clip(&mut self, shape: impl Shape) {
         let id = self.new_id();
         let mut clip = svg::node::element::ClipPath::new().set("id", id);
@@ -174,12 +264,12 @@ impl piet::RenderContext for RenderContext {
 
     fn stroke(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>, width: f64) {
         let brush = brush.make_brush(self, || shape.bounding_box());
-        add_shape(
-            &mut self.doc,
+        self.add_shape(
             shape,
             &Attrs {
                 xf: self.state.xf,
                 clip: self.state.clip,
+                blend_mode: self.state.blend_mode,
                 stroke: Some((brush.into_owned(), width, &StrokeStyle::new())),
                 ..Attrs::default()
             },
@@ -194,12 +284,12 @@ impl piet::RenderContext for RenderContext {
         style: &StrokeStyle,
     ) {
         let brush = brush.make_brush(self, || shape.bounding_box());
-        add_shape(
-            &mut self.doc,
+        self.add_shape(
             shape,
             &Attrs {
                 xf: self.state.xf,
                 clip: self.state.clip,
+                blend_mode: self.state.blend_mode,
                 stroke: Some((brush.into_owned(), width, style)),
                 ..Attrs::default()
             },
@@ -212,11 +302,22 @@ impl piet::RenderContext for RenderContext {
 
     fn draw_text(
         &mut self,
-        _layout: &Self::TextLayout,
-        _pos: impl Into<Point>,
-        _brush: &impl IntoBrush<Self>,
+        layout: &Self::TextLayout,
+        pos: impl Into<Point>,
+        brush: &impl IntoBrush<Self>,
     ) {
-        unimplemented!()
+        // TODO: bounding box for text
+        let brush = brush.make_brush(self, || Rect::ZERO);
+        let mut text = layout.to_element(pos.into());
+        Attrs {
+            xf: self.state.xf,
+            clip: self.state.clip,
+            blend_mode: self.state.blend_mode,
+            fill: Some((brush.into_owned(), None)),
+            ..Attrs::default()
+        }
+        .apply_to(&mut text);
+        self.append(text);
     }
 
     fn save(&mut self) -> Result<()> {
@@ -226,10 +327,80 @@ impl piet::RenderContext for RenderContext {
     }
 
     fn restore(&mut self) -> Result<()> {
+        if self.layers.last().map(|layer| layer.depth) == Some(self.stack.len()) {
+            // The state on top of the stack belongs to a layer.
+            return Err(new_error(ErrorKind::StackUnbalance).with_context("restore"));
+        }
         self.state = self
             .stack
             .pop()
-            .ok_or_else(|| new_error(ErrorKind::StackUnbalance))?;
+            .ok_or_else(|| new_error(ErrorKind::StackUnbalance).with_context("restore"))?;
+        Ok(())
+    }
+
+    fn push_layer(&mut self, opacity: f64, clip: Option<Rect>) -> Result<()> {
+        self.save()?;
+        if let Some(rect) = clip {
+            self.clip(rect);
+        }
+        let mut group = svg::node::element::Group::new().set("opacity", opacity);
+        if self.state.blend_mode != BlendMode::SourceOver {
+            group.assign("style", blend_mode_style(self.state.blend_mode));
+        }
+        self.layers.push(Layer {
+            group,
+            depth: self.stack.len(),
+            kind: LayerKind::Group,
+        });
+        Ok(())
+    }
+
+    fn pop_layer(&mut self) -> Result<()> {
+        match self.layers.last() {
+            Some(layer) if layer.depth == self.stack.len() && layer.kind == LayerKind::Group => {}
+            _ => return Err(new_error(ErrorKind::StackUnbalance).with_context("pop_layer")),
+        }
+        let layer = self.layers.pop().unwrap();
+        self.append(layer.group);
+        self.state = self.stack.pop().unwrap();
+        Ok(())
+    }
+
+    fn push_image_mask(&mut self, _image: &Self::Image, _dst_rect: Rect) -> Result<()> {
+        Err(new_error(ErrorKind::NotSupported).with_context("push_image_mask"))
+    }
+
+    fn push_mask(&mut self) -> Result<()> {
+        self.save()?;
+        self.layers.push(Layer {
+            group: svg::node::element::Group::new(),
+            depth: self.stack.len(),
+            kind: LayerKind::Mask,
+        });
+        Ok(())
+    }
+
+    fn pop_mask(&mut self) -> Result<()> {
+        match self.layers.last() {
+            Some(layer) if layer.depth == self.stack.len() && layer.kind == LayerKind::Mask => {}
+            _ => return Err(new_error(ErrorKind::StackUnbalance).with_context("pop_mask")),
+        }
+        let layer = self.layers.pop().unwrap();
+        self.state = self.stack.pop().unwrap();
+        let id = self.new_id();
+        let mask = svg::node::element::Mask::new()
+            .set("id", id)
+            .set("style", "mask-type:alpha")
+            .add(layer.group);
+        self.doc.append(mask);
+
+        self.save()?;
+        self.layers.push(Layer {
+            group: svg::node::element::Group::new()
+                .set("mask", format!("url(#{})", id.to_string())),
+            depth: self.stack.len(),
+            kind: LayerKind::Group,
+        });
         Ok(())
     }
 
@@ -241,6 +412,15 @@ impl piet::RenderContext for RenderContext {
         self.state.xf *= transform;
     }
 
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        if mix_blend_mode(mode).is_some() {
+            self.state.blend_mode = mode;
+        } else {
+            let err = new_error(ErrorKind::NotSupported);
+            self.err = Err(err.with_context(format!("blend mode {:?}", mode)));
+        }
+    }
+
     fn current_transform(&self) -> Affine {
         self.state.xf
     }
@@ -252,7 +432,52 @@ impl piet::RenderContext for RenderContext {
         _buf: &[u8],
         _format: ImageFormat,
     ) -> Result<Self::Image> {
-        Err(new_error(ErrorKind::NotSupported))
+        Err(new_error(ErrorKind::NotSupported).with_context("make_image"))
+    }
+
+    fn create_offscreen(
+        &mut self,
+        size: Size,
+        f: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<Self::Image> {
+        let stack = mem::take(&mut self.stack);
+        let state = mem::take(&mut self.state);
+        let offscreen = Layer {
+            group: svg::node::element::Group::new(),
+            depth: 0,
+            kind: LayerKind::Offscreen,
+        };
+        let layers = mem::replace(&mut self.layers, vec![offscreen]);
+        let result = f(self);
+        let balanced = self.stack.is_empty() && self.layers.len() == 1;
+        let content = mem::replace(&mut self.layers, layers).swap_remove(0);
+        self.stack = stack;
+        self.state = state;
+        result?;
+        if !balanced {
+            return Err(new_error(ErrorKind::StackUnbalance).with_context("create_offscreen"));
+        }
+        // The drawing is only shown where the image is drawn, with `<use>`.
+        let id = self.new_id();
+        let defs = svg::node::element::Definitions::new().add(content.group.set("id", id));
+        self.doc.append(defs);
+        Ok(Image {
+            id,
+            width: size.width.ceil(),
+            height: size.height.ceil(),
+        })
+    }
+
+    fn capture_image_area(&mut self, _src_rect: impl Into<Rect>) -> Result<Self::Image> {
+        Err(new_error(ErrorKind::NotSupported).with_context("capture_image_area"))
+    }
+
+    fn read_image(
+        &mut self,
+        _image: &Self::Image,
+        _format: ImageFormat,
+    ) -> Result<(usize, usize, Vec<u8>)> {
+        Err(new_error(ErrorKind::NotSupported).with_context("read_image"))
     }
 
     #[inline]
@@ -278,19 +503,47 @@ impl piet::RenderContext for RenderContext {
 }
 
 fn draw_image(
-    _ctx: &mut RenderContext,
-    _image: &<RenderContext as piet::RenderContext>::Image,
-    _src_rect: Option<Rect>,
-    _dst_rect: Rect,
+    ctx: &mut RenderContext,
+    image: &<RenderContext as piet::RenderContext>::Image,
+    src_rect: Option<Rect>,
+    dst_rect: Rect,
     _interp: InterpolationMode,
 ) {
-    unimplemented!()
+    let src_rect = src_rect.unwrap_or_else(|| Rect::new(0.0, 0.0, image.width, image.height));
+    // A nested viewport maps the source area onto the destination, and
+    // clips the drawing to it.
+    let viewport = svg::node::element::SVG::new()
+        .set("x", dst_rect.x0)
+        .set("y", dst_rect.y0)
+        .set("width", dst_rect.width())
+        .set("height", dst_rect.height())
+        .set(
+            "viewBox",
+            format!(
+                "{} {} {} {}",
+                src_rect.x0,
+                src_rect.y0,
+                src_rect.width(),
+                src_rect.height()
+            ),
+        )
+        .set("preserveAspectRatio", "none")
+        .add(svg::node::element::Use::new().set("href", format!("#{}", image.id.to_string())));
+    let mut group = svg::node::element::Group::new().set("transform", xf_val(&ctx.state.xf));
+    if let Some(id) = ctx.state.clip {
+        group.assign("clip-path", format!("url(#{})", id.to_string()));
+    }
+    if ctx.state.blend_mode != BlendMode::SourceOver {
+        group.assign("style", blend_mode_style(ctx.state.blend_mode));
+    }
+    ctx.append(group.add(viewport));
 }
 
 #[derive(Default)]
 struct Attrs<'a> {
     xf: Affine,
     clip: Option<Id>,
+    blend_mode: BlendMode,
     fill: Option<(Brush, Option<&'a str>)>,
     stroke: Option<(Brush, f64, &'a StrokeStyle)>,
 }
@@ -303,6 +556,9 @@ impl Attrs<'_> {
         if let Some(id) = self.clip {
             node.assign("clip-path", format!("url(#{})", id.to_string()));
         }
+        if self.blend_mode != BlendMode::SourceOver {
+            node.assign("style", blend_mode_style(self.blend_mode));
+        }
         if let Some((ref brush, rule)) = self.fill {
             node.assign("fill", brush.val());
             if let Some(rule) = rule {
@@ -347,6 +603,40 @@ impl Attrs<'_> {
     }
 }
 
+/// The CSS `mix-blend-mode` value for a blend mode, if there is one.
+///
+/// SVG only composites with source-over, so the Porter-Duff operators
+/// can't be expressed.
+fn mix_blend_mode(mode: BlendMode) -> Option<&'static str> {
+    match mode {
+        BlendMode::SourceOver => Some("normal"),
+        BlendMode::Multiply => Some("multiply"),
+        BlendMode::Screen => Some("screen"),
+        BlendMode::Overlay => Some("overlay"),
+        BlendMode::Darken => Some("darken"),
+        BlendMode::Lighten => Some("lighten"),
+        BlendMode::ColorDodge => Some("color-dodge"),
+        BlendMode::ColorBurn => Some("color-burn"),
+        BlendMode::HardLight => Some("hard-light"),
+        BlendMode::SoftLight => Some("soft-light"),
+        BlendMode::Difference => Some("difference"),
+        BlendMode::Exclusion => Some("exclusion"),
+        BlendMode::Hue => Some("hue"),
+        BlendMode::Saturation => Some("saturation"),
+        BlendMode::Color => Some("color"),
+        BlendMode::Luminosity => Some("luminosity"),
+        _ => None,
+    }
+}
+
+fn blend_mode_style(mode: BlendMode) -> svg::node::Value {
+    format!(
+        "mix-blend-mode:{}",
+        mix_blend_mode(mode).unwrap_or("normal")
+    )
+    .into()
+}
+
 fn xf_val(xf: &Affine) -> svg::node::Value {
     let xf = xf.as_coeffs();
     format!(
@@ -393,6 +683,26 @@ fn add_shape(node: &mut impl Node, shape: impl Shape, attrs: &Attrs) {
 struct State {
     xf: Affine,
     clip: Option<Id>,
+    blend_mode: BlendMode,
+}
+
+/// A group opened by `push_layer`, `push_mask` or `create_offscreen`.
+struct Layer {
+    group: svg::node::element::Group,
+    /// The depth of the state stack just after the layer was pushed.
+    depth: usize,
+    kind: LayerKind,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LayerKind {
+    /// Drawing, closed by `pop_layer`.
+    Group,
+    /// The content of a mask, closed by `pop_mask`.
+    Mask,
+    /// The content of an offscreen image, which is closed when its
+    /// drawing is done.
+    Offscreen,
 }
 
 /// An SVG brush
@@ -426,14 +736,76 @@ impl IntoBrush<RenderContext> for Brush {
     }
 }
 
-fn fmt_color(color: &Color) -> String {
-    match color {
-        Color::Rgba32(x) => format!("#{:08x}", x),
+/// The `spreadMethod` for an extend mode, if it isn't the default.
+fn spread_method(extend: ExtendMode) -> Option<&'static str> {
+    match extend {
+        ExtendMode::Pad => None,
+        ExtendMode::Repeat => Some("repeat"),
+        ExtendMode::Reflect => Some("reflect"),
+    }
+}
+
+/// Approximate a sweep gradient, which SVG lacks, with a pattern of thin wedges.
+fn sweep_pattern(id: Id, sweep: &FixedSweepGradient) -> svg::node::element::Pattern {
+    use std::f64::consts::PI;
+    // How far the pattern reaches. We don't know how big the drawing is, but
+    // renderers may rasterize the whole tile, so it can't be huge either.
+    const RADIUS: f64 = 1e4;
+    // The angle covered by each wedge.
+    const STEP: f64 = PI / 180.0;
+
+    let center = sweep.center;
+    let mut pattern = svg::node::element::Pattern::new()
+        .set("id", id)
+        .set("patternUnits", "userSpaceOnUse")
+        .set("x", center.x - RADIUS)
+        .set("y", center.y - RADIUS)
+        .set("width", 2.0 * RADIUS)
+        .set("height", 2.0 * RADIUS);
+    let span = sweep.end_angle - sweep.start_angle;
+    let n = (2.0 * PI / STEP).round() as usize;
+    let step = 2.0 * PI / n as f64 * span.signum();
+    for i in 0..n {
+        let a0 = sweep.start_angle + i as f64 * step;
+        // Overlap with the next wedge, so antialiasing doesn't leave seams.
+        let a1 = a0 + 1.5 * step;
+        let mid = (i as f64 + 0.5) * step;
+        // Past the end, pad with the end color up to halfway round, then the start color.
+        let pos = if mid.abs() <= span.abs() {
+            mid / span
+        } else if mid.abs() - span.abs() < PI - span.abs() / 2.0 {
+            1.0
+        } else {
+            0.0
+        };
+        let points = format!(
+            "{},{} {},{} {},{}",
+            center.x,
+            center.y,
+            center.x + RADIUS * a0.cos(),
+            center.y + RADIUS * a0.sin(),
+            center.x + RADIUS * a1.cos(),
+            center.y + RADIUS * a1.sin(),
+        );
+        pattern.append(
+            svg::node::element::Polygon::new()
+                .set("points", points)
+                .set("fill", fmt_color(&sweep.color_at(pos))),
+        );
     }
+    pattern
+}
+
+fn fmt_color(color: &Color) -> String {
+    color.to_string()
 }
 
-/// SVG image (unimplemented)
-pub struct Image(());
+/// SVG image, drawn by `create_offscreen`
+pub struct Image {
+    id: Id,
+    width: f64,
+    height: f64,
+}
 
 #[derive(Debug, Copy, Clone)]
 struct Id(u64);
@@ -462,3 +834,58 @@ impl From<Id> for svg::node::Value {
         x.to_string().into()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use piet::kurbo::Rect;
+    use piet::{ErrorKind, ImageFormat, ImagePattern, RenderContext as _};
+
+    use super::*;
+
+    #[test]
+    fn image_pattern_is_reported() {
+        let mut rc = RenderContext::new();
+        let pattern = ImagePattern::new(1, 1, &[0, 0, 0, 255][..], ImageFormat::RgbaSeparate);
+        rc.fill(Rect::new(0.0, 0.0, 10.0, 10.0), &pattern);
+        let err = rc.status().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotSupported));
+        assert!(rc.status().is_ok());
+    }
+
+    #[test]
+    fn layers_are_nested_groups() {
+        let mut rc = RenderContext::new();
+        rc.push_layer(0.5, None).unwrap();
+        rc.fill(Rect::new(0.0, 0.0, 10.0, 10.0), &Color::BLACK);
+        rc.push_layer(0.25, None).unwrap();
+        rc.fill(Rect::new(5.0, 5.0, 15.0, 15.0), &Color::BLACK);
+        rc.pop_layer().unwrap();
+        rc.pop_layer().unwrap();
+        rc.finish().unwrap();
+        let mut buf = Vec::new();
+        rc.write(&mut buf).unwrap();
+        let expected = r##"<svg xmlns="http://www.w3.org/2000/svg">
+<g opacity="0.5">
+<rect fill="#000000" height="10" transform="matrix(1 0 0 1 0 0)" width="10" x="0" y="0"/>
+<g opacity="0.25">
+<rect fill="#000000" height="10" transform="matrix(1 0 0 1 0 0)" width="10" x="5" y="5"/>
+</g>
+</g>
+</svg>"##;
+        assert_eq!(String::from_utf8(buf).unwrap(), expected);
+    }
+
+    #[test]
+    fn restore_stops_at_layers() {
+        let mut rc = RenderContext::new();
+        rc.save().unwrap();
+        rc.push_layer(0.5, None).unwrap();
+        let err = rc.restore().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::StackUnbalance));
+        // the layer and the state saved before it are still there
+        rc.pop_layer().unwrap();
+        rc.restore().unwrap();
+        let err = rc.restore().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::StackUnbalance));
+    }
+}
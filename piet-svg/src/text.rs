@@ -1,17 +1,66 @@
 //! Text functionality for Piet svg backend
 
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::{Range, RangeBounds};
+
 use piet::kurbo::Point;
-use piet::{new_error, Error, ErrorKind, HitTestPoint, HitTestTextPosition};
+use piet::{
+    font_family_name, AttributeSpans, Error, FontMetrics, FontStretch, FontStyle, FontWeight,
+    HitTestPoint, HitTestTextPosition, LineMetric, TextAlignment, TextAttribute, TextLayoutMetrics,
+    TextStyle,
+};
+use svg::node::Node;
+use unicode_bidi::{get_base_direction, Direction};
 
 type Result<T> = std::result::Result<T, Error>;
 
-/// SVG text (unimplemented)
-pub struct Text(());
+/// SVG text
+///
+/// Text is laid out by the program showing the SVG, so fonts are only
+/// referred to by name, and layouts can't be measured. For the same reason
+/// text doesn't wrap; only explicit newlines start new lines.
+///
+/// Fonts loaded with `load_font` are embedded in the document as
+/// `@font-face` rules.
+pub struct Text {
+    /// The loaded fonts, with their family names.
+    fonts: Vec<(String, Vec<u8>)>,
+    /// The indices of the loaded fonts, by the hash and length of their
+    /// data.
+    loaded: HashMap<(u64, usize), Vec<usize>>,
+}
 
 impl Text {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        Text(())
+        Text {
+            fonts: Vec::new(),
+            loaded: HashMap::new(),
+        }
+    }
+
+    /// A `<style>` element with the loaded fonts, if there are any.
+    pub(crate) fn font_faces(&self) -> Option<svg::node::element::Style> {
+        if self.fonts.is_empty() {
+            return None;
+        }
+        let mut css = String::new();
+        for (family, data) in &self.fonts {
+            let mime = match data.get(..4) {
+                Some(b"OTTO") => "font/otf",
+                Some(b"ttcf") => "font/collection",
+                _ => "font/ttf",
+            };
+            css.push_str(&format!(
+                "@font-face {{ font-family: \"{}\"; src: url(data:{};base64,{}); }}\n",
+                family.replace('"', "\\\""),
+                mime,
+                base64(data)
+            ));
+        }
+        Some(svg::node::element::Style::new(css))
     }
 }
 
@@ -21,54 +70,513 @@ impl piet::Text for Text {
     type TextLayout = TextLayout;
     type TextLayoutBuilder = TextLayoutBuilder;
 
-    fn new_font_by_name(&mut self, _name: &str, _size: f64) -> FontBuilder {
-        FontBuilder(())
+    fn new_font_by_name(&mut self, name: &str, size: f64) -> FontBuilder {
+        FontBuilder(Font(TextStyle::new(name, size)))
+    }
+
+    /// Loading the same data again doesn't embed it twice.
+    fn load_font(&mut self, data: &[u8]) -> Result<String> {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        let key = (hasher.finish(), data.len());
+        let fonts = &mut self.fonts;
+        let indices = self.loaded.entry(key).or_default();
+        if let Some(&i) = indices.iter().find(|&&i| fonts[i].1 == data) {
+            return Ok(fonts[i].0.clone());
+        }
+        let family = font_family_name(data, 0)?;
+        indices.push(fonts.len());
+        fonts.push((family.clone(), data.to_vec()));
+        Ok(family)
+    }
+
+    /// The families of the loaded fonts. The installed fonts of the program
+    /// showing the SVG aren't known.
+    fn font_families(&mut self) -> Vec<String> {
+        let mut families: Vec<_> = self
+            .fonts
+            .iter()
+            .map(|(family, _)| family.clone())
+            .collect();
+        families.sort();
+        families.dedup();
+        families
     }
 
-    fn new_text_layout(&mut self, _font: &Self::Font, _text: &str) -> TextLayoutBuilder {
-        TextLayoutBuilder(())
+    fn new_text_layout(&mut self, font: &Self::Font, text: &str) -> TextLayoutBuilder {
+        TextLayoutBuilder {
+            text: text.to_owned(),
+            style: font.0.clone(),
+            attributes: AttributeSpans::new(),
+            max_width: None,
+            alignment: TextAlignment::default(),
+        }
     }
 }
 
-/// SVG font builder (unimplemented)
-pub struct FontBuilder(());
+/// SVG font builder
+pub struct FontBuilder(Font);
 
 impl piet::FontBuilder for FontBuilder {
     type Out = Font;
 
+    fn weight(mut self, weight: FontWeight) -> Self {
+        (self.0).0.weight = weight;
+        self
+    }
+
+    fn style(mut self, style: FontStyle) -> Self {
+        (self.0).0.style = style;
+        self
+    }
+
+    fn stretch(mut self, stretch: FontStretch) -> Self {
+        (self.0).0.stretch = stretch;
+        self
+    }
+
     fn build(self) -> Result<Font> {
-        Err(new_error(ErrorKind::NotSupported))
+        Ok(self.0)
     }
 }
 
-/// SVG font (unimplemented)
-pub struct Font(());
+/// SVG font
+pub struct Font(TextStyle);
+
+/// Fonts can't be measured, so these are typical proportions of the font
+/// size.
+impl piet::Font for Font {
+    fn metrics(&self) -> FontMetrics {
+        FontMetrics::approximate(self.0.size)
+    }
 
-impl piet::Font for Font {}
+    /// Fonts are resolved by the program showing the SVG, so this is the
+    /// family asked for.
+    fn family_name(&self) -> &str {
+        &self.0.family
+    }
+}
 
-pub struct TextLayoutBuilder(());
+pub struct TextLayoutBuilder {
+    text: String,
+    style: TextStyle,
+    attributes: AttributeSpans,
+    max_width: Option<f64>,
+    alignment: TextAlignment,
+}
 
 impl piet::TextLayoutBuilder for TextLayoutBuilder {
     type Out = TextLayout;
 
+    fn range_attribute(
+        mut self,
+        range: impl RangeBounds<usize>,
+        attribute: impl Into<TextAttribute>,
+    ) -> Self {
+        self.attributes.add(&self.text, range, attribute.into());
+        self
+    }
+
+    /// Text can't be measured, so it isn't wrapped, but lines are aligned
+    /// within the width.
+    fn max_width(mut self, width: f64) -> Self {
+        self.max_width = Some(width);
+        self
+    }
+
+    /// Lines are aligned by the program showing the SVG, within the width
+    /// set with `max_width`, or else around the point the text is drawn at.
+    /// Justified text is aligned to the start. The direction of the text is
+    /// that of its first paragraph.
+    fn alignment(mut self, alignment: TextAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
     fn build(self) -> Result<TextLayout> {
-        Err(new_error(ErrorKind::NotSupported))
+        let runs = self.attributes.runs(&self.text, &self.style);
+        let lines = hard_lines(&self.text);
+        let rtl = get_base_direction(self.text.as_str()) == Direction::Rtl;
+        Ok(TextLayout {
+            text: self.text,
+            style: self.style,
+            runs,
+            lines,
+            max_width: self.max_width.unwrap_or(0.0),
+            alignment: self.alignment,
+            rtl,
+        })
     }
 }
 
-/// SVG text layout (unimplemented)
-pub struct TextLayout(());
+/// SVG text layout
+///
+/// The layout isn't measured, so its width is zero, and hit testing finds
+/// nothing. Line metrics are typical proportions of the font size.
+pub struct TextLayout {
+    text: String,
+    style: TextStyle,
+    runs: Vec<(Range<usize>, TextStyle)>,
+    lines: Vec<Range<usize>>,
+    max_width: f64,
+    alignment: TextAlignment,
+    rtl: bool,
+}
+
+impl TextLayout {
+    /// A `<text>` element for the layout, with a `<tspan>` for each run
+    /// styled differently from the font, and for each line if there is
+    /// more than one.
+    pub(crate) fn to_element(&self, pos: Point) -> svg::node::element::Text {
+        // The anchor is the start of the text, or its end or middle, on the
+        // left or right of the width.
+        let (anchor, offset) = match (self.alignment, self.rtl) {
+            (TextAlignment::Center, _) => ("middle", 0.5),
+            (TextAlignment::End, false) => ("end", 1.0),
+            (TextAlignment::End, true) => ("end", 0.0),
+            (_, false) => ("start", 0.0),
+            (_, true) => ("start", 1.0),
+        };
+        let x = pos.x + offset * self.max_width;
+        let mut text = svg::node::element::Text::new()
+            .set("x", x)
+            .set("y", pos.y)
+            .set("font-family", self.style.family.as_str())
+            .set("font-size", self.style.size);
+        if self.style.weight != FontWeight::REGULAR {
+            text.assign("font-weight", self.style.weight.to_raw());
+        }
+        if self.style.style != FontStyle::Regular {
+            text.assign("font-style", css_style(self.style.style));
+        }
+        if self.style.stretch != FontStretch::Normal {
+            text.assign("font-stretch", self.style.stretch.to_css());
+        }
+        if self.rtl {
+            text.assign("direction", "rtl");
+        }
+        if anchor != "start" {
+            text.assign("text-anchor", anchor);
+        }
+        // The runs are written as one piece of markup, as whitespace between
+        // child nodes would show up as spaces in the text.
+        let mut markup = String::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            if self.lines.len() > 1 {
+                markup.push_str("<tspan");
+                push_attr(&mut markup, "x", &x.to_string());
+                if i > 0 {
+                    push_attr(&mut markup, "dy", &self.line_height().to_string());
+                }
+                markup.push('>');
+            }
+            let line_end = line.start + self.text[line.clone()].trim_end_matches(is_newline).len();
+            for (range, style) in &self.runs {
+                let start = range.start.max(line.start);
+                let end = range.end.min(line_end);
+                if start >= end {
+                    continue;
+                }
+                self.push_run(&mut markup, &self.text[start..end], style);
+            }
+            if self.lines.len() > 1 {
+                markup.push_str("</tspan>");
+            }
+        }
+        text.append(svg::node::Text::new(markup));
+        text
+    }
+
+    fn font_metrics(&self) -> FontMetrics {
+        FontMetrics::approximate(self.style.size)
+    }
+
+    fn line_height(&self) -> f64 {
+        self.font_metrics().line_height()
+    }
+
+    // allow clippy warning for comparing font sizes
+    #[allow(clippy::float_cmp)]
+    fn push_run(&self, markup: &mut String, text: &str, style: &TextStyle) {
+        let content = escape(text);
+        if *style == self.style {
+            markup.push_str(&content);
+            return;
+        }
+        markup.push_str("<tspan");
+        if style.family != self.style.family {
+            push_attr(markup, "font-family", &style.family);
+        }
+        if style.size != self.style.size {
+            push_attr(markup, "font-size", &style.size.to_string());
+        }
+        if style.weight != self.style.weight {
+            push_attr(markup, "font-weight", &style.weight.to_raw().to_string());
+        }
+        if style.style != self.style.style {
+            push_attr(markup, "font-style", css_style(style.style));
+        }
+        if style.stretch != self.style.stretch {
+            push_attr(markup, "font-stretch", style.stretch.to_css());
+        }
+        if let Some(color) = &style.color {
+            push_attr(markup, "fill", &crate::fmt_color(color));
+        }
+        let decoration = match (style.underline, style.strikethrough) {
+            (false, false) => None,
+            (true, false) => Some("underline"),
+            (false, true) => Some("line-through"),
+            (true, true) => Some("underline line-through"),
+        };
+        if let Some(decoration) = decoration {
+            push_attr(markup, "text-decoration", decoration);
+        }
+        markup.push('>');
+        markup.push_str(&content);
+        markup.push_str("</tspan>");
+    }
+}
 
 impl piet::TextLayout for TextLayout {
     fn width(&self) -> f64 {
-        unimplemented!()
+        0.0
+    }
+
+    fn metrics(&self) -> TextLayoutMetrics {
+        let font = self.font_metrics();
+        TextLayoutMetrics {
+            height: self.line_height() * self.lines.len() as f64,
+            ascent: font.ascent,
+            descent: font.descent,
+            line_gap: font.line_gap,
+            line_count: self.lines.len(),
+        }
+    }
+
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn line_metric(&self, line_number: usize) -> Option<LineMetric> {
+        let line = self.lines.get(line_number)?;
+        Some(LineMetric {
+            start_offset: line.start,
+            end_offset: line.end,
+            baseline: self.line_height() * line_number as f64,
+            ascent: self.font_metrics().ascent,
+            height: self.line_height(),
+        })
+    }
+
+    fn line_text(&self, line_number: usize) -> Option<&str> {
+        let line = self.lines.get(line_number)?;
+        Some(&self.text[line.clone()])
     }
 
     fn hit_test_point(&self, _point: Point) -> HitTestPoint {
-        unimplemented!()
+        HitTestPoint::default()
     }
 
     fn hit_test_text_position(&self, _text_position: usize) -> Option<HitTestTextPosition> {
-        unimplemented!()
+        None
+    }
+}
+
+/// Split text into lines at newlines, keeping the newlines.
+fn hard_lines(text: &str) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        // A `\r\n` pair breaks after the `\n`.
+        if is_newline(c) && !(c == '\r' && text[i + 1..].starts_with('\n')) {
+            let end = i + c.len_utf8();
+            lines.push(start..end);
+            start = end;
+        }
+    }
+    lines.push(start..text.len());
+    lines
+}
+
+/// The CSS `font-style` of a style.
+fn css_style(style: FontStyle) -> &'static str {
+    match style {
+        FontStyle::Regular => "normal",
+        FontStyle::Italic => "italic",
+        FontStyle::Oblique => "oblique",
+    }
+}
+
+fn is_newline(c: char) -> bool {
+    matches!(
+        c,
+        '\n' | '\r' | '\u{0b}' | '\u{0c}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+    )
+}
+
+/// Encode data as base64, for a data URL.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Escape text for XML character data or attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn push_attr(markup: &mut String, name: &str, value: &str) {
+    markup.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+}
+
+#[cfg(test)]
+mod test {
+    use piet::{Color, FontBuilder as _, Text as _, TextLayoutBuilder as _};
+
+    use super::*;
+
+    fn build(
+        text: &str,
+        font: impl FnOnce(FontBuilder) -> FontBuilder,
+        attributes: impl FnOnce(TextLayoutBuilder) -> TextLayoutBuilder,
+    ) -> TextLayout {
+        let mut factory = Text::new();
+        let font = font(factory.new_font_by_name("Sans", 12.0))
+            .build()
+            .unwrap();
+        attributes(factory.new_text_layout(&font, text))
+            .build()
+            .unwrap()
+    }
+
+    /// The markup of the runs of a layout, inside its `<text>` element.
+    fn runs(layout: &TextLayout) -> String {
+        let element = layout.to_element(Point::new(1.0, 2.0)).to_string();
+        let start = element.find('>').unwrap() + 1;
+        let end = element.rfind("</text>").unwrap();
+        element[start..end].trim().to_owned()
+    }
+
+    #[test]
+    fn overlapping_runs() {
+        let layout = build(
+            "abcdefghijkl",
+            |font| font,
+            |builder| {
+                builder
+                    .range_attribute(0..7, FontWeight::BOLD)
+                    .range_attribute(4..10, Color::rgb8(0xff, 0, 0))
+            },
+        );
+        assert_eq!(
+            runs(&layout),
+            "<tspan font-weight=\"700\">abcd</tspan>\
+             <tspan font-weight=\"700\" fill=\"#ff0000\">efg</tspan>\
+             <tspan fill=\"#ff0000\">hij</tspan>kl"
+        );
+    }
+
+    #[test]
+    fn lines() {
+        let layout = build(
+            "one\ntwo\r\nthree",
+            |font| font,
+            |builder| builder.range_attribute(2..5, TextAttribute::Underline(true)),
+        );
+        let dy = layout.line_height();
+        assert_eq!(
+            runs(&layout),
+            format!(
+                "<tspan x=\"1\">on<tspan text-decoration=\"underline\">e</tspan></tspan>\
+                 <tspan x=\"1\" dy=\"{0}\"><tspan text-decoration=\"underline\">t</tspan>wo</tspan>\
+                 <tspan x=\"1\" dy=\"{0}\">three</tspan>",
+                dy
+            )
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        let layout = build(
+            "<a & \"b\">",
+            |font| font,
+            |builder| builder.range_attribute(1.., TextAttribute::FontFamily("A\"<&>".into())),
+        );
+        assert_eq!(
+            runs(&layout),
+            "&lt;<tspan font-family=\"A&quot;&lt;&amp;&gt;\">a &amp; &quot;b&quot;&gt;</tspan>"
+        );
+    }
+
+    #[test]
+    fn font_properties() {
+        let layout = build(
+            "wide",
+            |font| {
+                font.weight(FontWeight::BOLD)
+                    .style(FontStyle::Italic)
+                    .stretch(FontStretch::Expanded)
+            },
+            |builder| {
+                builder
+                    .range_attribute(1..2, FontWeight::LIGHT)
+                    .range_attribute(2..3, FontStyle::Oblique)
+            },
+        );
+        let element = layout.to_element(Point::ZERO).to_string();
+        assert!(element.contains("font-weight=\"700\""));
+        assert!(element.contains("font-style=\"italic\""));
+        assert!(element.contains("font-stretch=\"expanded\""));
+        assert_eq!(
+            runs(&layout),
+            "w<tspan font-weight=\"300\">i</tspan><tspan font-style=\"oblique\">d</tspan>e"
+        );
+
+        let regular = build("plain", |font| font, |builder| builder);
+        let element = regular.to_element(Point::ZERO).to_string();
+        assert!(!element.contains("font-weight"));
+        assert!(!element.contains("font-style"));
+        assert!(!element.contains("font-stretch"));
+    }
+
+    #[test]
+    fn fonts_are_embedded_once() {
+        let mut text = Text::new();
+        assert!(text.font_faces().is_none());
+        let family = text.load_font(piet_test::SANS_SERIF_FONT).unwrap();
+        assert_eq!(family, "DejaVu Sans");
+        assert_eq!(text.load_font(piet_test::SANS_SERIF_FONT).unwrap(), family);
+        text.load_font(piet_test::SERIF_FONT).unwrap();
+        let css = text.font_faces().unwrap().to_string();
+        assert_eq!(css.matches("@font-face").count(), 2);
+    }
+
+    #[test]
+    fn base64_tails() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"M"), "TQ==");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Many"), "TWFueQ==");
+        assert_eq!(base64(&[0xff, 0xfe, 0xfd, 0xfc, 0xfb]), "//79/Ps=");
     }
 }
@@ -2,20 +2,65 @@
 
 // Right now, this is just code to generate sample images.
 
-use piet::{Error, RenderContext};
+use piet::{Error, ErrorKind, GenericFamily, RenderContext, Text};
 mod picture_0;
 mod picture_1;
+mod picture_10;
+mod picture_11;
+mod picture_12;
+mod picture_13;
+mod picture_14;
+mod picture_15;
+mod picture_16;
+mod picture_17;
 mod picture_2;
 mod picture_3;
 mod picture_4;
 mod picture_5;
+mod picture_6;
+mod picture_7;
+mod picture_8;
+mod picture_9;
 
 use crate::picture_0::draw as draw_picture_0;
 use crate::picture_1::draw as draw_picture_1;
+use crate::picture_10::draw as draw_picture_10;
+use crate::picture_11::draw as draw_picture_11;
+use crate::picture_12::draw as draw_picture_12;
+use crate::picture_13::draw as draw_picture_13;
+use crate::picture_14::draw as draw_picture_14;
+use crate::picture_15::draw as draw_picture_15;
+use crate::picture_16::draw as draw_picture_16;
+use crate::picture_17::draw as draw_picture_17;
 use crate::picture_2::draw as draw_picture_2;
 use crate::picture_3::draw as draw_picture_3;
 use crate::picture_4::draw as draw_picture_4;
 use crate::picture_5::draw as draw_picture_5;
+use crate::picture_6::draw as draw_picture_6;
+use crate::picture_7::draw as draw_picture_7;
+use crate::picture_8::draw as draw_picture_8;
+use crate::picture_9::draw as draw_picture_9;
+
+/// DejaVu Sans, the sans-serif font of the test pictures, which is bundled
+/// so that they look the same on every machine.
+pub const SANS_SERIF_FONT: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+/// DejaVu Serif, the serif font of the test pictures.
+pub const SERIF_FONT: &[u8] = include_bytes!("../fonts/DejaVuSerif.ttf");
+
+/// Load a bundled font, returning its family name.
+///
+/// Back-ends that can't load fonts use a generic family instead.
+fn load_font(
+    rc: &mut impl RenderContext,
+    data: &[u8],
+    fallback: GenericFamily,
+) -> Result<String, Error> {
+    match rc.text().load_font(data) {
+        Err(e) if matches!(e.kind(), ErrorKind::NotSupported) => Ok(fallback.name().to_owned()),
+        result => result,
+    }
+}
 
 /// Draw a test picture, by number.
 ///
@@ -29,6 +74,18 @@ pub fn draw_test_picture(rc: &mut impl RenderContext, number: usize) -> Result<(
         3 => draw_picture_3(rc),
         4 => draw_picture_4(rc),
         5 => draw_picture_5(rc),
+        6 => draw_picture_6(rc),
+        7 => draw_picture_7(rc),
+        8 => draw_picture_8(rc),
+        9 => draw_picture_9(rc),
+        10 => draw_picture_10(rc),
+        11 => draw_picture_11(rc),
+        12 => draw_picture_12(rc),
+        13 => draw_picture_13(rc),
+        14 => draw_picture_14(rc),
+        15 => draw_picture_15(rc),
+        16 => draw_picture_16(rc),
+        17 => draw_picture_17(rc),
         _ => {
             eprintln!(
                 "Don't have test picture {} yet. Why don't you make it?",
@@ -3,10 +3,12 @@
 use piet::kurbo::{Affine, BezPath, Line, Point, Rect, RoundedRect, Vec2};
 
 use piet::{
-    Color, Error, FontBuilder, ImageFormat, InterpolationMode, RenderContext, Text, TextLayout,
-    TextLayoutBuilder,
+    Color, Error, FontBuilder, GenericFamily, ImageFormat, InterpolationMode, RenderContext, Text,
+    TextLayout, TextLayoutBuilder,
 };
 
+use crate::{load_font, SANS_SERIF_FONT};
+
 pub fn draw(rc: &mut impl RenderContext) -> Result<(), Error> {
     rc.clear(Color::WHITE);
     let brush = rc.solid_brush(Color::rgb8(0x00, 0x00, 0x80));
@@ -26,7 +28,8 @@ pub fn draw(rc: &mut impl RenderContext) -> Result<(), Error> {
 
     rc.stroke(RoundedRect::new(145.0, 45.0, 185.0, 85.0, 5.0), &brush, 1.0);
 
-    let font = rc.text().new_font_by_name("Segoe UI", 12.0).build()?;
+    let family = load_font(rc, SANS_SERIF_FONT, GenericFamily::SystemUi)?;
+    let font = rc.text().new_font_by_name(&family, 12.0).build()?;
     let layout = rc.text().new_text_layout(&font, "Hello piet!").build()?;
     let w: f64 = layout.width();
     let brush = rc.solid_brush(Color::rgba8(0x80, 0x00, 0x00, 0xC0));
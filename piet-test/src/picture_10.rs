@@ -0,0 +1,41 @@
+//! Sweep gradients.
+
+use std::f64::consts::PI;
+
+use piet::kurbo::{Circle, Point, Rect};
+
+use piet::{
+    Color, Error, FixedGradient, FixedSweepGradient, GradientStops, RenderContext, SweepGradient,
+    UnitPoint,
+};
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    // A color wheel.
+    let hues: Vec<Color> = (0..=6)
+        .map(|i| Color::hlc(i as f64 * 60.0, 60.0, 80.0))
+        .collect();
+    let wheel = rc.gradient(FixedGradient::Sweep(FixedSweepGradient {
+        center: Point::new(40.0, 50.0),
+        start_angle: 0.0,
+        end_angle: 2.0 * PI,
+        stops: GradientStops::to_vec(hues.as_slice()),
+    }))?;
+    rc.fill(Circle::new((40.0, 50.0), 35.0), &wheel);
+
+    // A partial sweep, padded with the end colors.
+    let partial = rc.gradient(FixedGradient::Sweep(FixedSweepGradient {
+        center: Point::new(110.0, 50.0),
+        start_angle: -0.5 * PI,
+        end_angle: 0.5 * PI,
+        stops: (Color::rgb8(0xc0, 0x00, 0x00), Color::rgb8(0x00, 0x00, 0xc0)).to_vec(),
+    }))?;
+    rc.fill(Circle::new((110.0, 50.0), 25.0), &partial);
+
+    // A unit-space sweep, running counterclockwise around an off-center point.
+    let spinner = SweepGradient::new(0.0, -1.5 * PI, (Color::BLACK, Color::BLACK.with_alpha(0.0)))
+        .with_center(UnitPoint::new(0.5, 0.25));
+    rc.fill(Rect::new(150.0, 10.0, 190.0, 90.0), &spinner);
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Gradient extend modes.
+
+use piet::kurbo::{Circle, Point, Rect, RoundedRect, Vec2};
+
+use piet::{
+    Color, Error, ExtendMode, FixedGradient, FixedLinearGradient, FixedRadialGradient,
+    GradientStops, LinearGradient, RenderContext, UnitPoint,
+};
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    let stops = (Color::rgb8(0x40, 0x80, 0xe0), Color::rgb8(0xc0, 0xe0, 0xff)).to_vec();
+    let modes = [ExtendMode::Pad, ExtendMode::Repeat, ExtendMode::Reflect];
+    for (i, &extend) in modes.iter().enumerate() {
+        // A short gradient in the middle of each bar.
+        let y = 10.0 + i as f64 * 15.0;
+        let bar = rc.gradient(FixedGradient::Linear(FixedLinearGradient {
+            start: Point::new(60.0, y),
+            end: Point::new(70.0, y + 5.0),
+            stops: stops.clone(),
+            extend,
+        }))?;
+        rc.fill(RoundedRect::new(10.0, y, 120.0, y + 10.0, 5.0), &bar);
+
+        // Rings around a point, with the gradient a fraction of the radius.
+        let center = Point::new(160.0, 18.0 + i as f64 * 32.0);
+        let rings = rc.gradient(FixedGradient::Radial(FixedRadialGradient {
+            center,
+            origin_offset: Vec2::ZERO,
+            radius: 5.0,
+            stops: stops.clone(),
+            extend,
+        }))?;
+        rc.fill(Circle::new(center, 14.0), &rings);
+    }
+
+    // A unit-space gradient, repeating across a progress bar.
+    let stripes = LinearGradient::new(UnitPoint::new(0.0, 0.0), UnitPoint::new(0.05, 0.5), stops)
+        .with_extend(ExtendMode::Repeat);
+    rc.fill(Rect::new(10.0, 60.0, 120.0, 80.0), &stripes);
+    Ok(())
+}
@@ -0,0 +1,50 @@
+//! Image pattern brushes.
+
+use piet::kurbo::{Affine, Circle, Rect, RoundedRect};
+
+use piet::{
+    Color, Error, ExtendMode, FontBuilder, GenericFamily, ImageFormat, ImagePattern, RenderContext,
+    Text, TextLayoutBuilder,
+};
+
+use crate::{load_font, SANS_SERIF_FONT};
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    // A brush made once from a back-end image, and drawn with twice.
+    let (width, height) = (8, 8);
+    let data = make_checkers(width, height);
+    let image = rc.make_image(width, height, &data, ImageFormat::RgbaSeparate)?;
+    let transform = Affine::translate((10.0, 10.0)) * Affine::rotate(0.3) * Affine::scale(1.5);
+    let brush = rc.image_pattern(&image, transform, ExtendMode::Repeat)?;
+    rc.fill(RoundedRect::new(10.0, 10.0, 90.0, 50.0, 8.0), &brush);
+    rc.stroke(Circle::new((140.0, 30.0), 18.0), &brush, 6.0);
+
+    // A stored pattern, reflected, filling text.
+    let pattern = ImagePattern::new(width, height, data, ImageFormat::RgbaSeparate)
+        .with_transform(Affine::scale(0.75))
+        .with_extend(ExtendMode::Reflect);
+    let family = load_font(rc, SANS_SERIF_FONT, GenericFamily::SystemUi)?;
+    let font = rc.text().new_font_by_name(&family, 28.0).build()?;
+    let layout = rc.text().new_text_layout(&font, "Pattern").build()?;
+    rc.draw_text(&layout, (10.0, 90.0), &pattern);
+    rc.fill(Rect::new(130.0, 60.0, 190.0, 95.0), &pattern);
+    Ok(())
+}
+
+/// A checkerboard with a gradient, so that the orientation is visible.
+fn make_checkers(width: usize, height: usize) -> Vec<u8> {
+    let mut result = vec![0; width * height * 4];
+    for y in 0..height {
+        for x in 0..width {
+            let ix = (y * width + x) * 4;
+            let dark = (x / 2 + y / 2) % 2 == 0;
+            result[ix] = (x * 255 / width) as u8;
+            result[ix + 1] = if dark { 0x40 } else { 0xc0 };
+            result[ix + 2] = (y * 255 / height) as u8;
+            result[ix + 3] = 0xff;
+        }
+    }
+    result
+}
@@ -0,0 +1,64 @@
+//! Offscreen images.
+
+use piet::kurbo::{Affine, Circle, Rect, RoundedRect, Size};
+
+use piet::{Color, Error, InterpolationMode, LinearGradient, RenderContext, UnitPoint};
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    // The transform in effect here doesn't apply inside the offscreen image.
+    rc.transform(Affine::translate((5.0, 5.0)));
+    let badge = rc.create_offscreen(Size::new(40.0, 40.0), |rc| {
+        let fill = LinearGradient::new(
+            UnitPoint::TOP,
+            UnitPoint::BOTTOM,
+            (Color::rgb8(0xff, 0xc0, 0x40), Color::rgb8(0xe0, 0x60, 0x20)),
+        );
+        rc.fill(Circle::new((20.0, 20.0), 18.0), &fill);
+        rc.with_save(|rc| {
+            rc.clip(Circle::new((20.0, 20.0), 18.0));
+            rc.fill(
+                Rect::new(0.0, 16.0, 40.0, 24.0),
+                &Color::rgba8(0xff, 0xff, 0xff, 0x80),
+            );
+            Ok(())
+        })?;
+        rc.stroke(
+            Circle::new((20.0, 20.0), 18.0),
+            &Color::rgb8(0x80, 0x30, 0x10),
+            2.0,
+        );
+        Ok(())
+    })?;
+
+    // The same image, drawn several times.
+    for i in 0..4 {
+        let x = 10.0 + i as f64 * 30.0;
+        let size = 10.0 + i as f64 * 8.0;
+        rc.draw_image(
+            &badge,
+            Rect::new(x, 10.0, x + size, 10.0 + size),
+            InterpolationMode::Bilinear,
+        );
+    }
+    rc.with_save(|rc| {
+        rc.transform(Affine::translate((160.0, 40.0)) * Affine::rotate(0.5));
+        rc.draw_image(
+            &badge,
+            Rect::new(-20.0, -15.0, 20.0, 15.0),
+            InterpolationMode::Bilinear,
+        );
+        Ok(())
+    })?;
+
+    // Part of the image, enlarged, through a rounded clip.
+    rc.clip(RoundedRect::new(10.0, 55.0, 110.0, 95.0, 10.0));
+    rc.draw_image_area(
+        &badge,
+        Rect::new(10.0, 10.0, 30.0, 30.0),
+        Rect::new(10.0, 55.0, 110.0, 95.0),
+        InterpolationMode::Bilinear,
+    );
+    Ok(())
+}
@@ -0,0 +1,30 @@
+//! Gradients interpolated in different color spaces.
+
+use piet::kurbo::Rect;
+
+use piet::{Color, Error, InterpolationSpace, LinearGradient, RenderContext, UnitPoint};
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    let spaces = [
+        InterpolationSpace::Srgb,
+        InterpolationSpace::LinearSrgb,
+        InterpolationSpace::Hsl,
+        InterpolationSpace::OkLab,
+        InterpolationSpace::OkLch,
+    ];
+    for (i, &space) in spaces.iter().enumerate() {
+        let y = 5.0 + i as f64 * 18.0;
+        let rect = Rect::new(5.0, y, 195.0, y + 14.0);
+        let gradient = LinearGradient::new(
+            UnitPoint::LEFT,
+            UnitPoint::RIGHT,
+            (Color::rgb8(0x00, 0x40, 0xff), Color::rgb8(0xff, 0xe0, 0x00)),
+        )
+        .with_interpolation(space);
+        rc.fill(rect, &gradient);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! Text with attributes on ranges.
+
+use piet::{
+    Color, Error, FontBuilder, FontStyle, FontWeight, GenericFamily, RenderContext, Text,
+    TextAttribute, TextLayoutBuilder,
+};
+
+use crate::{load_font, SANS_SERIF_FONT, SERIF_FONT};
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    let family = load_font(rc, SANS_SERIF_FONT, GenericFamily::SansSerif)?;
+    let font = rc.text().new_font_by_name(&family, 12.0).build()?;
+    let text = "Some bold, italic, red";
+    let layout = rc
+        .text()
+        .new_text_layout(&font, text)
+        .range_attribute(5..9, FontWeight::BOLD)
+        .range_attribute(11..17, FontStyle::Italic)
+        .range_attribute(19.., Color::rgb8(0xc0, 0x20, 0x20))
+        .build()?;
+    rc.draw_text(&layout, (10.0, 25.0), &Color::BLACK);
+
+    let serif = load_font(rc, SERIF_FONT, GenericFamily::Serif)?;
+    let text = "Big, serif, under, struck";
+    let layout = rc
+        .text()
+        .new_text_layout(&font, text)
+        .range_attribute(..3, TextAttribute::FontSize(20.0))
+        .range_attribute(5..10, TextAttribute::FontFamily(serif))
+        .range_attribute(12..17, TextAttribute::Underline(true))
+        .range_attribute(19.., TextAttribute::Strikethrough(true))
+        .build()?;
+    let brush = rc.solid_brush(Color::rgb8(0x20, 0x40, 0x80));
+    rc.draw_text(&layout, (10.0, 60.0), &brush);
+
+    Ok(())
+}
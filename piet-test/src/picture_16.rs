@@ -0,0 +1,42 @@
+//! Text wrapped to a width, and explicit newlines.
+
+use piet::kurbo::Line;
+use piet::{
+    Color, Error, FontBuilder, GenericFamily, RenderContext, Text, TextLayout, TextLayoutBuilder,
+};
+
+use crate::{load_font, SANS_SERIF_FONT};
+
+const WIDTH: f64 = 120.0;
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    let family = load_font(rc, SANS_SERIF_FONT, GenericFamily::SansSerif)?;
+    let font = rc.text().new_font_by_name(&family, 12.0).build()?;
+    let text = "Text that is too long for one line wraps between words.\nNewlines start new lines.";
+    let layout = rc
+        .text()
+        .new_text_layout(&font, text)
+        .max_width(WIDTH)
+        .build()?;
+    rc.stroke(
+        Line::new((10.0 + WIDTH, 5.0), (10.0 + WIDTH, 140.0)),
+        &Color::rgb8(0xc0, 0x80, 0x80),
+        1.0,
+    );
+    rc.draw_text(&layout, (10.0, 20.0), &Color::BLACK);
+
+    // Mark the start of each line, found by hit testing.
+    let brush = rc.solid_brush(Color::rgb8(0x20, 0x40, 0x80));
+    let mut start = 0;
+    for line in 0..layout.line_count() {
+        if let Some(hit) = layout.hit_test_text_position(start) {
+            let y = 20.0 + hit.point.y;
+            rc.stroke(Line::new((5.0, y), (8.0, y)), &brush, 1.0);
+        }
+        start += layout.line_text(line).map(str::len).unwrap_or(0);
+    }
+
+    Ok(())
+}
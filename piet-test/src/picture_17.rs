@@ -0,0 +1,39 @@
+//! Text aligned within a width: start, end, center and justified.
+
+use piet::kurbo::Rect;
+use piet::{
+    Color, Error, FontBuilder, GenericFamily, RenderContext, Text, TextAlignment, TextLayoutBuilder,
+};
+
+use crate::{load_font, SANS_SERIF_FONT};
+
+const WIDTH: f64 = 85.0;
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    let family = load_font(rc, SANS_SERIF_FONT, GenericFamily::SansSerif)?;
+    let font = rc.text().new_font_by_name(&family, 8.0).build()?;
+    let text = "Lines of text are placed within the width of the layout.";
+    let alignments = [
+        TextAlignment::Start,
+        TextAlignment::End,
+        TextAlignment::Center,
+        TextAlignment::Justified,
+    ];
+    let outline = Color::rgb8(0xc0, 0x80, 0x80);
+    for (i, &alignment) in alignments.iter().enumerate() {
+        let x = 10.0 + (i % 2) as f64 * (WIDTH + 10.0);
+        let y = 5.0 + (i / 2) as f64 * 47.0;
+        rc.stroke(Rect::new(x, y, x + WIDTH, y + 42.0), &outline, 1.0);
+        let layout = rc
+            .text()
+            .new_text_layout(&font, text)
+            .max_width(WIDTH)
+            .alignment(alignment)
+            .build()?;
+        rc.draw_text(&layout, (x, y + 8.0), &Color::BLACK);
+    }
+
+    Ok(())
+}
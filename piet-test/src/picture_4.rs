@@ -3,8 +3,8 @@
 use piet::kurbo::{Point, Rect, Vec2};
 
 use piet::{
-    Color, Error, FixedGradient, FixedLinearGradient, FixedRadialGradient, GradientStop,
-    RenderContext,
+    Color, Error, ExtendMode, FixedGradient, FixedLinearGradient, FixedRadialGradient,
+    GradientStop, RenderContext,
 };
 
 pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
@@ -24,6 +24,7 @@ pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
         origin_offset: Vec2::new(10.0, 10.0),
         radius: 30.0,
         stops,
+        extend: ExtendMode::Pad,
     }))?;
     rc.fill(Rect::new(0.0, 0.0, 60.0, 60.0), &gradient);
     let stops2 = vec![
@@ -40,6 +41,7 @@ pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
         start: Point::new(0.0, 0.0),
         end: Point::new(60.0, 0.0),
         stops: stops2,
+        extend: ExtendMode::Pad,
     }))?;
     rc.fill(Rect::new(0.0, 80.0, 60.0, 100.0), &gradient2);
     Ok(())
@@ -1,14 +1,19 @@
 //! Basic example of just text
 
 use piet::kurbo::Line;
-use piet::{Color, Error, FontBuilder, RenderContext, Text, TextLayout, TextLayoutBuilder};
+use piet::{
+    Color, Error, FontBuilder, GenericFamily, RenderContext, Text, TextLayout, TextLayoutBuilder,
+};
+
+use crate::{load_font, SANS_SERIF_FONT};
 
 pub fn draw(rc: &mut impl RenderContext) -> Result<(), Error> {
     // Black background
     rc.clear(Color::BLACK);
 
     // do something texty
-    let font = rc.text().new_font_by_name("Segoe UI", 12.0).build()?;
+    let family = load_font(rc, SANS_SERIF_FONT, GenericFamily::SystemUi)?;
+    let font = rc.text().new_font_by_name(&family, 12.0).build()?;
 
     let layout = rc.text().new_text_layout(&font, "piet text!").build()?;
 
@@ -0,0 +1,35 @@
+//! Layers with group opacity.
+
+use piet::kurbo::{Circle, Rect};
+
+use piet::{Color, Error, RenderContext};
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    // Overlapping shapes drawn directly show their overlap.
+    let red = Color::rgba8(0xc0, 0x00, 0x00, 0x80);
+    let blue = Color::rgba8(0x00, 0x00, 0xc0, 0x80);
+    rc.fill(Circle::new((40.0, 40.0), 25.0), &red);
+    rc.fill(Circle::new((70.0, 40.0), 25.0), &blue);
+
+    // Inside a layer, the shapes are composited as a single unit.
+    let red = Color::rgb8(0xc0, 0x00, 0x00);
+    let blue = Color::rgb8(0x00, 0x00, 0xc0);
+    rc.with_layer(0.5, None, |rc| {
+        rc.fill(Circle::new((140.0, 40.0), 25.0), &red);
+        rc.fill(Circle::new((170.0, 40.0), 25.0), &blue);
+        Ok(())
+    })?;
+
+    // Layers nest, and can be clipped.
+    let clip = Rect::new(15.0, 90.0, 180.0, 120.0);
+    rc.with_layer(0.8, Some(clip), |rc| {
+        rc.fill(Rect::new(10.0, 80.0, 100.0, 130.0), &red);
+        rc.with_layer(0.5, None, |rc| {
+            rc.fill(Rect::new(60.0, 80.0, 190.0, 130.0), &blue);
+            Ok(())
+        })
+    })?;
+    Ok(())
+}
@@ -0,0 +1,40 @@
+//! Blend modes and compositing operators.
+
+use piet::kurbo::{Circle, Rect};
+
+use piet::{BlendMode, Color, Error, RenderContext};
+
+const MODES: &[BlendMode] = &[
+    BlendMode::SourceOver,
+    BlendMode::Multiply,
+    BlendMode::Screen,
+    BlendMode::Overlay,
+    BlendMode::Darken,
+    BlendMode::Lighten,
+    BlendMode::Difference,
+    BlendMode::Exclusion,
+    BlendMode::SourceIn,
+    BlendMode::SourceOut,
+    BlendMode::Xor,
+    BlendMode::Clear,
+];
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    let backdrop = Color::rgb8(0x20, 0x80, 0xc0);
+    let source = Color::rgba8(0xe0, 0x60, 0x20, 0xc0);
+    for (i, mode) in MODES.iter().enumerate() {
+        let x = 5.0 + (i % 6) as f64 * 32.0;
+        let y = 5.0 + (i / 6) as f64 * 40.0;
+        // Each cell gets its own layer, so the Porter-Duff operators only
+        // interact with the backdrop in that cell.
+        rc.with_layer(1.0, None, |rc| {
+            rc.fill(Rect::new(x, y, x + 18.0, y + 18.0), &backdrop);
+            rc.set_blend_mode(*mode);
+            rc.fill(Circle::new((x + 18.0, y + 18.0), 10.0), &source);
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,51 @@
+//! Alpha masks, from drawn content and from an image.
+
+use piet::kurbo::{Circle, Rect};
+
+use piet::{Color, Error, ImageFormat, RenderContext};
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    let stripes = |rc: &mut R, x0: f64| {
+        let red = Color::rgb8(0xc0, 0x00, 0x00);
+        let blue = Color::rgb8(0x00, 0x00, 0xc0);
+        for i in 0..8 {
+            let x = x0 + i as f64 * 10.0;
+            let color = if i % 2 == 0 { &red } else { &blue };
+            rc.fill(Rect::new(x, 10.0, x + 10.0, 90.0), color);
+        }
+    };
+
+    // A mask drawn with a solid and a translucent shape.
+    rc.with_mask(
+        |rc| {
+            rc.fill(Circle::new((30.0, 50.0), 25.0), &Color::BLACK);
+            rc.fill(
+                Circle::new((65.0, 50.0), 25.0),
+                &Color::rgba8(0, 0, 0, 0x60),
+            );
+            Ok(())
+        },
+        |rc| {
+            stripes(rc, 10.0);
+            Ok(())
+        },
+    )?;
+
+    // A mask taken from the alpha channel of an image.
+    let (width, height) = (16, 16);
+    let mut mask_data = vec![0; width * height * 4];
+    for y in 0..height {
+        for x in 0..width {
+            let ix = (y * width + x) * 4;
+            mask_data[ix + 3] = ((x + y) * 255 / (width + height - 2)) as u8;
+        }
+    }
+    let mask = rc.make_image(width, height, &mask_data, ImageFormat::RgbaSeparate)?;
+    rc.with_image_mask(&mask, Rect::new(110.0, 10.0, 190.0, 90.0), |rc| {
+        stripes(rc, 110.0);
+        Ok(())
+    })?;
+    Ok(())
+}
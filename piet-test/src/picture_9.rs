@@ -0,0 +1,42 @@
+//! Blurred rectangles, as used for drop shadows.
+
+use piet::kurbo::{Affine, Rect, RoundedRect, Vec2};
+
+use piet::{
+    Color, Error, ExtendMode, FixedGradient, FixedLinearGradient, GradientStop, RenderContext,
+};
+
+pub fn draw<R: RenderContext>(rc: &mut R) -> Result<(), Error> {
+    rc.clear(Color::WHITE);
+
+    let shadow = Color::rgba8(0, 0, 0, 0x80);
+    let card = Color::rgb8(0xe0, 0xe8, 0xf0);
+    for (i, &radius) in [0.0, 1.0, 3.0, 6.0].iter().enumerate() {
+        let x = 15.0 + i as f64 * 45.0;
+        let rect = Rect::new(x, 10.0, x + 30.0, 50.0);
+        rc.blurred_rect(rect + Vec2::new(2.0, 3.0), radius, &shadow);
+        rc.fill(RoundedRect::from_rect(rect, 2.0), &card);
+    }
+
+    // A blurred rect with a gradient brush, under a transform.
+    let brush = rc.gradient(FixedGradient::Linear(FixedLinearGradient {
+        start: (20.0, 0.0).into(),
+        end: (180.0, 0.0).into(),
+        stops: vec![
+            GradientStop {
+                pos: 0.0,
+                color: Color::rgb8(0xc0, 0x00, 0x00),
+            },
+            GradientStop {
+                pos: 1.0,
+                color: Color::rgb8(0x00, 0x00, 0xc0),
+            },
+        ],
+        extend: ExtendMode::Pad,
+    }))?;
+    rc.save()?;
+    rc.transform(Affine::translate((0.0, 65.0)));
+    rc.blurred_rect(Rect::new(20.0, 5.0, 180.0, 20.0), 4.0, &brush);
+    rc.restore()?;
+    Ok(())
+}
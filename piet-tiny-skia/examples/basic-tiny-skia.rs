@@ -0,0 +1,36 @@
+//! Basic example of rendering with tiny-skia.
+//!
+//! The backend has no text yet, so pictures with text fail to draw. The
+//! default picture has none.
+
+use piet::RenderContext;
+use piet_tiny_skia::tiny_skia::Pixmap;
+use piet_tiny_skia::TinySkiaRenderContext;
+
+use piet::kurbo::Affine;
+use piet_test::draw_test_picture;
+
+const TEXTURE_WIDTH: u32 = 400;
+const TEXTURE_HEIGHT: u32 = 200;
+
+const HIDPI: f64 = 2.0;
+
+fn main() {
+    let test_picture_number = std::env::args()
+        .nth(1)
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(1);
+
+    let mut pixmap = Pixmap::new(TEXTURE_WIDTH, TEXTURE_HEIGHT).expect("Can't create pixmap");
+    let mut piet_context = TinySkiaRenderContext::new(&mut pixmap);
+    piet_context.transform(Affine::scale(HIDPI));
+    let result = draw_test_picture(&mut piet_context, test_picture_number)
+        .and_then(|()| piet_context.finish());
+    if let Err(e) = result {
+        eprintln!("Can't draw picture {}: {}", test_picture_number, e);
+        std::process::exit(1);
+    }
+    pixmap
+        .save_png("temp-tiny-skia.png")
+        .expect("Error writing image file");
+}
@@ -0,0 +1,998 @@
+//! A pure Rust software backend for the Piet 2D graphics abstraction.
+//!
+//! Rendering is done on the CPU by [tiny-skia], into a `Pixmap`, which holds
+//! RGBA pixels with premultiplied alpha.
+//!
+//! Text isn't supported yet: building a font or a text layout returns
+//! [`ErrorKind::NotSupported`], and so does `finish` after `draw_text`.
+//!
+//! [`ErrorKind::NotSupported`]: piet::ErrorKind::NotSupported
+//! [tiny-skia]: https://crates.io/crates/tiny-skia
+
+mod text;
+
+use std::borrow::Cow;
+use std::rc::Rc;
+
+use tiny_skia::{
+    FillRule, FilterQuality, GradientStop, IntSize, Mask, MaskType, Paint, Path, PathBuilder,
+    Pattern, Pixmap, PixmapPaint, Shader, SpreadMode, Stroke, StrokeDash, Transform,
+};
+
+use piet::kurbo::{Affine, PathEl, Point, Rect, Shape, Size};
+
+use piet::{
+    compute_blurred_rect, new_error, size_for_blurred_rect, BlendMode, Color, Error, ErrorKind,
+    ExtendMode, FixedGradient, FixedSweepGradient, ImageFormat, InterpolationMode, IntoBrush,
+    LineCap, LineJoin, RenderContext, StrokeStyle,
+};
+
+#[doc(hidden)]
+pub use tiny_skia;
+
+pub use crate::text::{
+    TinySkiaFont, TinySkiaFontBuilder, TinySkiaText, TinySkiaTextLayout, TinySkiaTextLayoutBuilder,
+};
+
+/// A render context drawing into a tiny-skia `Pixmap`.
+pub struct TinySkiaRenderContext<'a> {
+    target: &'a mut Pixmap,
+    text: TinySkiaText,
+    /// The current context state.
+    state: State,
+    /// The states saved by `save`, innermost last.
+    stack: Vec<State>,
+    /// The layers and masks currently being drawn, innermost last.
+    layers: Vec<Layer>,
+    err: Result<(), Error>,
+}
+
+/// The context state, as saved and restored by `save` and `restore`.
+#[derive(Clone, Default)]
+struct State {
+    transform: Affine,
+    /// The clip, in device space. It's shared between saved states, as it
+    /// changes much less often than it is saved.
+    clip: Option<Rc<Mask>>,
+    blend_mode: BlendMode,
+}
+
+/// A pixmap being drawn into, instead of the target.
+struct Layer {
+    pixmap: Pixmap,
+    kind: LayerKind,
+    /// The depth of the state stack when the layer was pushed. States
+    /// saved outside the layer can't be restored inside it.
+    depth: usize,
+}
+
+enum LayerKind {
+    /// A layer composited with a uniform opacity.
+    Opacity(f32),
+    /// A layer composited through a mask.
+    Mask(Mask),
+    /// The content of a mask, which becomes a `Mask` layer when popped.
+    MaskContent,
+}
+
+/// A brush for the tiny-skia backend.
+#[derive(Clone)]
+pub enum Brush {
+    /// A solid color or a gradient that tiny-skia draws natively.
+    Shader(Shader<'static>),
+    /// A sweep gradient, which is evaluated per pixel when drawn.
+    Sweep(FixedSweepGradient),
+    /// An image pattern.
+    Image {
+        image: TinySkiaImage,
+        transform: Transform,
+        spread_mode: SpreadMode,
+        quality: FilterQuality,
+    },
+}
+
+/// An image, stored with premultiplied alpha.
+///
+/// Images are reference counted, so brushes can refer to them cheaply.
+#[derive(Clone)]
+pub struct TinySkiaImage(Rc<Pixmap>);
+
+/// How a path is painted.
+enum Draw<'a> {
+    Fill(FillRule),
+    Stroke(&'a Stroke),
+}
+
+impl<'a> TinySkiaRenderContext<'a> {
+    /// Create a new tiny-skia back-end, drawing into `pixmap`.
+    pub fn new(pixmap: &'a mut Pixmap) -> TinySkiaRenderContext<'a> {
+        TinySkiaRenderContext {
+            target: pixmap,
+            text: TinySkiaText::new(),
+            state: State::default(),
+            stack: Vec::new(),
+            layers: Vec::new(),
+            err: Ok(()),
+        }
+    }
+}
+
+impl TinySkiaImage {
+    /// The pixels of the image.
+    pub fn pixmap(&self) -> &Pixmap {
+        &self.0
+    }
+}
+
+impl<'a> RenderContext for TinySkiaRenderContext<'a> {
+    type Brush = Brush;
+
+    type Text = TinySkiaText;
+    type TextLayout = TinySkiaTextLayout;
+
+    type Image = TinySkiaImage;
+
+    fn status(&mut self) -> Result<(), Error> {
+        std::mem::replace(&mut self.err, Ok(()))
+    }
+
+    fn report_error(&mut self, err: Error) {
+        self.err = Err(err);
+    }
+
+    fn clear(&mut self, color: Color) {
+        let mut paint = Paint::default();
+        paint.set_color(convert_color(&color.with_alpha(1.0)));
+        let clip = self.state.clip.clone();
+        let surface = self.surface();
+        let rect = surface_rect(surface);
+        surface.fill_rect(rect, &paint, Transform::identity(), clip.as_deref());
+    }
+
+    fn solid_brush(&mut self, color: Color) -> Brush {
+        Brush::Shader(Shader::SolidColor(convert_color(&color)))
+    }
+
+    fn gradient(&mut self, gradient: impl Into<FixedGradient>) -> Result<Brush, Error> {
+        let (shader, stops) = match gradient.into() {
+            FixedGradient::Linear(linear) => (
+                tiny_skia::LinearGradient::new(
+                    convert_point(linear.start),
+                    convert_point(linear.end),
+                    convert_stops(&linear.stops),
+                    convert_extend_mode(linear.extend),
+                    Transform::identity(),
+                ),
+                linear.stops,
+            ),
+            FixedGradient::Radial(radial) => (
+                // tiny-skia's radial gradient starts from a focal point.
+                tiny_skia::RadialGradient::new(
+                    convert_point(radial.center + radial.origin_offset),
+                    convert_point(radial.center),
+                    radial.radius as f32,
+                    convert_stops(&radial.stops),
+                    convert_extend_mode(radial.extend),
+                    Transform::identity(),
+                ),
+                radial.stops,
+            ),
+            FixedGradient::Sweep(sweep) => return Ok(Brush::Sweep(sweep)),
+        };
+        // tiny-skia refuses degenerate gradients, such as ones of zero length,
+        // which are drawn with the last stop instead.
+        let shader = shader.unwrap_or_else(|| match stops.last() {
+            Some(stop) => Shader::SolidColor(convert_color(&stop.color)),
+            None => Shader::SolidColor(tiny_skia::Color::TRANSPARENT),
+        });
+        Ok(Brush::Shader(shader))
+    }
+
+    fn image_pattern(
+        &mut self,
+        image: &Self::Image,
+        transform: Affine,
+        extend: ExtendMode,
+    ) -> Result<Brush, Error> {
+        Ok(Brush::Image {
+            image: image.clone(),
+            transform: convert_affine(transform),
+            spread_mode: convert_extend_mode(extend),
+            quality: FilterQuality::Bilinear,
+        })
+    }
+
+    fn fill(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
+        let brush = brush.make_brush(self, || shape.bounding_box());
+        if let Some(path) = convert_path(shape) {
+            self.paint(&path, &brush, Draw::Fill(FillRule::Winding), None);
+        }
+    }
+
+    fn fill_even_odd(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
+        let brush = brush.make_brush(self, || shape.bounding_box());
+        if let Some(path) = convert_path(shape) {
+            self.paint(&path, &brush, Draw::Fill(FillRule::EvenOdd), None);
+        }
+    }
+
+    fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &impl IntoBrush<Self>) {
+        let brush = brush.make_brush(self, || rect);
+        let size = size_for_blurred_rect(rect, blur_radius);
+        let (width, height) = (size.width as usize, size.height as usize);
+        let mut buf = vec![0; width * height];
+        let bounds = compute_blurred_rect(rect, blur_radius, width, &mut buf);
+        // Put the blur in the alpha channel of an image, so that it can be
+        // transformed like one.
+        let data = buf.iter().flat_map(|&a| [0, 0, 0, a]).collect();
+        let image = IntSize::from_wh(width as u32, height as u32)
+            .and_then(|size| Pixmap::from_vec(data, size));
+        // The only failure here is an empty mask, which leaves nothing to draw.
+        if let (Some(image), Some(path)) = (image, convert_path(bounds)) {
+            let image_xf = Affine::translate(bounds.origin().to_vec2());
+            let mask = self.image_mask(&image, image_xf, bounds);
+            self.paint(&path, &brush, Draw::Fill(FillRule::Winding), Some(mask));
+        }
+    }
+
+    fn clip(&mut self, shape: impl Shape) {
+        let transform = convert_affine(self.state.transform);
+        let mut mask = match &self.state.clip {
+            Some(clip) => (**clip).clone(),
+            None => {
+                let mut mask = self.new_mask();
+                mask.invert();
+                mask
+            }
+        };
+        match convert_path(shape) {
+            Some(path) => mask.intersect_path(&path, FillRule::Winding, true, transform),
+            // Clipping to an empty shape clips out everything.
+            None => mask.clear(),
+        }
+        self.state.clip = Some(Rc::new(mask));
+    }
+
+    fn stroke(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>, width: f64) {
+        let brush = brush.make_brush(self, || shape.bounding_box());
+        if let Some(path) = convert_path(shape) {
+            let stroke = convert_stroke(width, None);
+            self.paint(&path, &brush, Draw::Stroke(&stroke), None);
+        }
+    }
+
+    fn stroke_styled(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        width: f64,
+        style: &StrokeStyle,
+    ) {
+        let brush = brush.make_brush(self, || shape.bounding_box());
+        if let Some(path) = convert_path(shape) {
+            let stroke = convert_stroke(width, Some(style));
+            self.paint(&path, &brush, Draw::Stroke(&stroke), None);
+        }
+    }
+
+    fn text(&mut self) -> &mut Self::Text {
+        &mut self.text
+    }
+
+    fn draw_text(
+        &mut self,
+        _layout: &Self::TextLayout,
+        _pos: impl Into<Point>,
+        _brush: &impl IntoBrush<Self>,
+    ) {
+        self.err = Err(new_error(ErrorKind::NotSupported));
+    }
+
+    fn save(&mut self) -> Result<(), Error> {
+        self.stack.push(self.state.clone());
+        Ok(())
+    }
+
+    fn restore(&mut self) -> Result<(), Error> {
+        let floor = self.layers.last().map(|layer| layer.depth).unwrap_or(0);
+        if self.stack.len() <= floor {
+            return Err(new_error(ErrorKind::StackUnbalance));
+        }
+        self.state = self.stack.pop().unwrap();
+        Ok(())
+    }
+
+    fn push_layer(&mut self, opacity: f64, clip: Option<Rect>) -> Result<(), Error> {
+        self.save()?;
+        if let Some(rect) = clip {
+            self.clip(rect);
+        }
+        self.push_pixmap(LayerKind::Opacity(opacity as f32))
+    }
+
+    fn pop_layer(&mut self) -> Result<(), Error> {
+        // A mask has to be finished with `pop_mask` first.
+        if let Some(LayerKind::MaskContent) = self.layers.last().map(|layer| &layer.kind) {
+            return Err(new_error(ErrorKind::StackUnbalance));
+        }
+        let layer = self.pop_pixmap()?;
+        let (opacity, mask) = match layer.kind {
+            LayerKind::Opacity(opacity) => (opacity, None),
+            LayerKind::Mask(mask) => (1.0, Some(mask)),
+            LayerKind::MaskContent => unreachable!(),
+        };
+        let paint = PixmapPaint {
+            opacity,
+            blend_mode: convert_blend_mode(self.state.blend_mode),
+            quality: FilterQuality::Nearest,
+        };
+        let clip = self.state.clip.clone();
+        let mask = combine_masks(clip.as_deref(), mask);
+        self.surface().draw_pixmap(
+            0,
+            0,
+            layer.pixmap.as_ref(),
+            &paint,
+            Transform::identity(),
+            mask.as_deref(),
+        );
+        self.restore()
+    }
+
+    fn push_image_mask(&mut self, image: &Self::Image, dst_rect: Rect) -> Result<(), Error> {
+        let pixmap = image.pixmap();
+        let scale_x = dst_rect.width() / pixmap.width() as f64;
+        let scale_y = dst_rect.height() / pixmap.height() as f64;
+        let image_xf = Affine::translate(dst_rect.origin().to_vec2())
+            * Affine::scale_non_uniform(scale_x, scale_y);
+        let mask = self.image_mask(pixmap, image_xf, dst_rect);
+        self.save()?;
+        self.push_pixmap(LayerKind::Mask(mask))
+    }
+
+    fn push_mask(&mut self) -> Result<(), Error> {
+        self.push_pixmap(LayerKind::MaskContent)
+    }
+
+    fn pop_mask(&mut self) -> Result<(), Error> {
+        match self.layers.last().map(|layer| &layer.kind) {
+            Some(LayerKind::MaskContent) => (),
+            _ => return Err(new_error(ErrorKind::StackUnbalance)),
+        }
+        let layer = self.pop_pixmap()?;
+        let mask = Mask::from_pixmap(layer.pixmap.as_ref(), MaskType::Alpha);
+        self.save()?;
+        self.push_pixmap(LayerKind::Mask(mask))
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.status()
+    }
+
+    fn transform(&mut self, transform: Affine) {
+        self.state.transform *= transform;
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.state.blend_mode = mode;
+    }
+
+    fn current_transform(&self) -> Affine {
+        self.state.transform
+    }
+
+    fn make_image(
+        &mut self,
+        width: usize,
+        height: usize,
+        buf: &[u8],
+        format: ImageFormat,
+    ) -> Result<Self::Image, Error> {
+        let pixels = width * height;
+        let data: Vec<u8> = match format {
+            ImageFormat::Rgb => buf
+                .chunks_exact(3)
+                .take(pixels)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            ImageFormat::RgbaPremul => buf.iter().take(pixels * 4).copied().collect(),
+            ImageFormat::RgbaSeparate => buf
+                .chunks_exact(4)
+                .take(pixels)
+                .flat_map(|p| {
+                    [
+                        mul_u8(p[0], p[3]),
+                        mul_u8(p[1], p[3]),
+                        mul_u8(p[2], p[3]),
+                        p[3],
+                    ]
+                })
+                .collect(),
+            _ => return Err(new_error(ErrorKind::NotSupported)),
+        };
+        // A buffer that is too short leaves the data the wrong size, which
+        // is rejected here.
+        let pixmap = IntSize::from_wh(width as u32, height as u32)
+            .and_then(|size| Pixmap::from_vec(data, size))
+            .ok_or_else(|| new_error(ErrorKind::InvalidInput))?;
+        Ok(TinySkiaImage(Rc::new(pixmap)))
+    }
+
+    fn create_offscreen(
+        &mut self,
+        size: Size,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<Self::Image, Error> {
+        let pixmap = Pixmap::new(size.width.ceil() as u32, size.height.ceil() as u32)
+            .ok_or_else(|| new_error(ErrorKind::InvalidInput))?;
+        let target = std::mem::replace(self.target, pixmap);
+        let state = std::mem::take(&mut self.state);
+        let stack = std::mem::take(&mut self.stack);
+        let layers = std::mem::take(&mut self.layers);
+        let err = std::mem::replace(&mut self.err, Ok(()));
+        let result = f(self).and_then(|()| self.status());
+        let balanced = self.stack.is_empty() && self.layers.is_empty();
+        let pixmap = std::mem::replace(self.target, target);
+        self.state = state;
+        self.stack = stack;
+        self.layers = layers;
+        self.err = err;
+        result?;
+        if !balanced {
+            return Err(new_error(ErrorKind::StackUnbalance));
+        }
+        Ok(TinySkiaImage(Rc::new(pixmap)))
+    }
+
+    fn capture_image_area(&mut self, src_rect: impl Into<Rect>) -> Result<Self::Image, Error> {
+        let rect = self
+            .state
+            .transform
+            .transform_rect_bbox(src_rect.into())
+            .expand();
+        let mut pixmap = Pixmap::new(rect.width() as u32, rect.height() as u32)
+            .ok_or_else(|| new_error(ErrorKind::InvalidInput))?;
+        let paint = PixmapPaint {
+            blend_mode: tiny_skia::BlendMode::Source,
+            ..PixmapPaint::default()
+        };
+        pixmap.draw_pixmap(
+            -rect.x0 as i32,
+            -rect.y0 as i32,
+            self.target.as_ref(),
+            &paint,
+            Transform::identity(),
+            None,
+        );
+        Ok(TinySkiaImage(Rc::new(pixmap)))
+    }
+
+    fn read_image(
+        &mut self,
+        image: &Self::Image,
+        format: ImageFormat,
+    ) -> Result<(usize, usize, Vec<u8>), Error> {
+        let pixmap = image.pixmap();
+        let buf = match format {
+            ImageFormat::RgbaPremul => pixmap.data().to_vec(),
+            ImageFormat::RgbaSeparate => pixmap
+                .pixels()
+                .iter()
+                .map(|p| p.demultiply())
+                .flat_map(|c| [c.red(), c.green(), c.blue(), c.alpha()])
+                .collect(),
+            ImageFormat::Rgb => pixmap
+                .pixels()
+                .iter()
+                .map(|p| p.demultiply())
+                .flat_map(|c| [c.red(), c.green(), c.blue()])
+                .collect(),
+            _ => return Err(new_error(ErrorKind::NotSupported)),
+        };
+        Ok((pixmap.width() as usize, pixmap.height() as usize, buf))
+    }
+
+    #[inline]
+    fn draw_image(
+        &mut self,
+        image: &Self::Image,
+        dst_rect: impl Into<Rect>,
+        interp: InterpolationMode,
+    ) {
+        draw_image(self, image, None, dst_rect.into(), interp);
+    }
+
+    #[inline]
+    fn draw_image_area(
+        &mut self,
+        image: &Self::Image,
+        src_rect: impl Into<Rect>,
+        dst_rect: impl Into<Rect>,
+        interp: InterpolationMode,
+    ) {
+        draw_image(self, image, Some(src_rect.into()), dst_rect.into(), interp);
+    }
+}
+
+fn draw_image(
+    ctx: &mut TinySkiaRenderContext,
+    image: &TinySkiaImage,
+    src_rect: Option<Rect>,
+    dst_rect: Rect,
+    interp: InterpolationMode,
+) {
+    let pixmap = image.pixmap();
+    let src_rect = match src_rect {
+        Some(src_rect) => src_rect,
+        None => Rect::new(0.0, 0.0, pixmap.width() as f64, pixmap.height() as f64),
+    };
+    let scale_x = dst_rect.width() / src_rect.width();
+    let scale_y = dst_rect.height() / src_rect.height();
+    let transform = Affine::translate((
+        dst_rect.x0 - scale_x * src_rect.x0,
+        dst_rect.y0 - scale_y * src_rect.y0,
+    )) * Affine::scale_non_uniform(scale_x, scale_y);
+    let quality = match interp {
+        InterpolationMode::NearestNeighbor => FilterQuality::Nearest,
+        InterpolationMode::Bilinear => FilterQuality::Bilinear,
+    };
+    let brush = Brush::Image {
+        image: image.clone(),
+        transform: convert_affine(transform),
+        spread_mode: SpreadMode::Pad,
+        quality,
+    };
+    if let Some(path) = convert_path(dst_rect) {
+        ctx.paint(&path, &brush, Draw::Fill(FillRule::Winding), None);
+    }
+}
+
+impl<'a> IntoBrush<TinySkiaRenderContext<'a>> for Brush {
+    fn make_brush<'b>(
+        &'b self,
+        _piet: &mut TinySkiaRenderContext,
+        _bbox: impl FnOnce() -> Rect,
+    ) -> std::borrow::Cow<'b, Brush> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<'a> TinySkiaRenderContext<'a> {
+    /// The pixmap currently being drawn into.
+    fn surface(&mut self) -> &mut Pixmap {
+        match self.layers.last_mut() {
+            Some(layer) => &mut layer.pixmap,
+            None => self.target,
+        }
+    }
+
+    /// A transparent pixmap the size of the target.
+    fn new_pixmap(&self) -> Pixmap {
+        // The target exists, so its size is valid.
+        Pixmap::new(self.target.width(), self.target.height()).unwrap()
+    }
+
+    /// An empty mask the size of the target.
+    fn new_mask(&self) -> Mask {
+        Mask::new(self.target.width(), self.target.height()).unwrap()
+    }
+
+    /// Paint a path with a brush, through the clip and an optional mask.
+    fn paint(&mut self, path: &Path, brush: &Brush, draw: Draw, mask: Option<Mask>) {
+        let transform = convert_affine(self.state.transform);
+        let sweep;
+        let shader = match brush {
+            Brush::Shader(shader) => shader.clone(),
+            Brush::Sweep(gradient) => {
+                // The gradient is rendered in device space, so the pattern
+                // has to undo the transform of the path.
+                sweep = sweep_pixmap(gradient, self.state.transform, self.new_pixmap());
+                Pattern::new(
+                    sweep.as_ref(),
+                    SpreadMode::Pad,
+                    FilterQuality::Nearest,
+                    1.0,
+                    convert_affine(self.state.transform.inverse()),
+                )
+            }
+            Brush::Image {
+                image,
+                transform,
+                spread_mode,
+                quality,
+            } => Pattern::new(
+                image.pixmap().as_ref(),
+                *spread_mode,
+                *quality,
+                1.0,
+                *transform,
+            ),
+        };
+        let blend_mode = self.state.blend_mode;
+        let mut paint = Paint {
+            shader,
+            blend_mode: convert_blend_mode(blend_mode),
+            ..Paint::default()
+        };
+        let clip = self.state.clip.clone();
+        let mask = combine_masks(clip.as_deref(), mask);
+        // Some modes also change the destination outside of the shape, which
+        // tiny-skia doesn't do, so the shape is drawn into a layer first and
+        // then composited over the whole surface.
+        let mut layer = match blend_mode {
+            BlendMode::SourceIn
+            | BlendMode::SourceOut
+            | BlendMode::DestIn
+            | BlendMode::DestAtop => {
+                paint.blend_mode = tiny_skia::BlendMode::SourceOver;
+                Some(self.new_pixmap())
+            }
+            _ => None,
+        };
+        let surface = match layer.as_mut() {
+            Some(layer) => layer,
+            None => self.surface(),
+        };
+        match draw {
+            Draw::Fill(fill_rule) => {
+                surface.fill_path(path, &paint, fill_rule, transform, mask.as_deref())
+            }
+            Draw::Stroke(stroke) => {
+                surface.stroke_path(path, &paint, stroke, transform, mask.as_deref())
+            }
+        }
+        if let Some(layer) = layer {
+            let paint = PixmapPaint {
+                blend_mode: convert_blend_mode(blend_mode),
+                ..PixmapPaint::default()
+            };
+            self.surface().draw_pixmap(
+                0,
+                0,
+                layer.as_ref(),
+                &paint,
+                Transform::identity(),
+                clip.as_deref(),
+            );
+        }
+    }
+
+    /// Render the alpha channel of an image into a mask.
+    ///
+    /// The image is placed by `image_xf`, and only drawn within `rect`, both
+    /// in user space.
+    fn image_mask(&self, image: &Pixmap, image_xf: Affine, rect: Rect) -> Mask {
+        let mut pixmap = self.new_pixmap();
+        let paint = Paint {
+            shader: Pattern::new(
+                image.as_ref(),
+                SpreadMode::Pad,
+                FilterQuality::Bilinear,
+                1.0,
+                convert_affine(image_xf),
+            ),
+            ..Paint::default()
+        };
+        if let Some(path) = convert_path(rect) {
+            let transform = convert_affine(self.state.transform);
+            pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
+        }
+        Mask::from_pixmap(pixmap.as_ref(), MaskType::Alpha)
+    }
+
+    /// Start drawing into a new layer.
+    ///
+    /// This saves the state, which can't be restored until the layer is popped.
+    fn push_pixmap(&mut self, kind: LayerKind) -> Result<(), Error> {
+        let pixmap = self.new_pixmap();
+        self.save()?;
+        self.layers.push(Layer {
+            pixmap,
+            kind,
+            depth: self.stack.len(),
+        });
+        Ok(())
+    }
+
+    /// Stop drawing into the innermost layer, and restore the state from
+    /// when it was pushed.
+    fn pop_pixmap(&mut self) -> Result<Layer, Error> {
+        match self.layers.last() {
+            Some(layer) if layer.depth == self.stack.len() => (),
+            _ => return Err(new_error(ErrorKind::StackUnbalance)),
+        }
+        self.state = self.stack.pop().unwrap();
+        Ok(self.layers.pop().unwrap())
+    }
+}
+
+/// Combine the clip with another mask.
+fn combine_masks(clip: Option<&Mask>, mask: Option<Mask>) -> Option<Cow<'_, Mask>> {
+    match (clip, mask) {
+        (Some(clip), Some(mut mask)) => {
+            for (a, &b) in mask.data_mut().iter_mut().zip(clip.data()) {
+                *a = mul_u8(*a, b);
+            }
+            Some(Cow::Owned(mask))
+        }
+        (Some(clip), None) => Some(Cow::Borrowed(clip)),
+        (None, mask) => mask.map(Cow::Owned),
+    }
+}
+
+/// Render a sweep gradient into a device space pixmap.
+fn sweep_pixmap(sweep: &FixedSweepGradient, transform: Affine, mut pixmap: Pixmap) -> Pixmap {
+    use std::f64::consts::PI;
+    let inverse = transform.inverse();
+    let span = (sweep.end_angle - sweep.start_angle).clamp(-2.0 * PI, 2.0 * PI);
+    let width = pixmap.width() as usize;
+    for (i, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+        let (x, y) = ((i % width) as f64 + 0.5, (i / width) as f64 + 0.5);
+        let angle = (inverse * Point::new(x, y) - sweep.center).atan2();
+        // How far past the start the angle is, in the direction of the sweep.
+        let delta = ((angle - sweep.start_angle) * span.signum()).rem_euclid(2.0 * PI);
+        let pos = if delta < span.abs() {
+            delta / span.abs()
+        } else if delta - span.abs() < PI - span.abs() / 2.0 {
+            // The rest of the circle is split between the end colors.
+            1.0
+        } else {
+            0.0
+        };
+        *pixel = convert_color(&sweep.color_at(pos))
+            .premultiply()
+            .to_color_u8();
+    }
+    pixmap
+}
+
+/// The whole area of a pixmap.
+fn surface_rect(pixmap: &Pixmap) -> tiny_skia::Rect {
+    // Pixmaps are never empty.
+    tiny_skia::Rect::from_xywh(0.0, 0.0, pixmap.width() as f32, pixmap.height() as f32).unwrap()
+}
+
+/// Multiply two fractions in the range 0..=255.
+fn mul_u8(x: u8, a: u8) -> u8 {
+    let y = (x as u16) * (a as u16);
+    ((y + (y >> 8) + 0x80) >> 8) as u8
+}
+
+fn convert_color(color: &Color) -> tiny_skia::Color {
+    let (r, g, b, a) = color.as_rgba();
+    tiny_skia::Color::from_rgba(r as f32, g as f32, b as f32, a as f32)
+        .unwrap_or(tiny_skia::Color::TRANSPARENT)
+}
+
+fn convert_stops(stops: &[piet::GradientStop]) -> Vec<GradientStop> {
+    stops
+        .iter()
+        .map(|stop| GradientStop::new(stop.pos, convert_color(&stop.color)))
+        .collect()
+}
+
+fn convert_point(point: Point) -> tiny_skia::Point {
+    tiny_skia::Point::from_xy(point.x as f32, point.y as f32)
+}
+
+fn convert_affine(affine: Affine) -> Transform {
+    let [a, b, c, d, e, f] = affine.as_coeffs();
+    Transform::from_row(a as f32, b as f32, c as f32, d as f32, e as f32, f as f32)
+}
+
+/// Convert a shape to a path, if it isn't empty.
+fn convert_path(shape: impl Shape) -> Option<Path> {
+    let mut builder = PathBuilder::new();
+    for el in shape.to_bez_path(1e-3) {
+        match el {
+            PathEl::MoveTo(p) => builder.move_to(p.x as f32, p.y as f32),
+            PathEl::LineTo(p) => builder.line_to(p.x as f32, p.y as f32),
+            PathEl::QuadTo(p1, p2) => {
+                builder.quad_to(p1.x as f32, p1.y as f32, p2.x as f32, p2.y as f32)
+            }
+            PathEl::CurveTo(p1, p2, p3) => builder.cubic_to(
+                p1.x as f32,
+                p1.y as f32,
+                p2.x as f32,
+                p2.y as f32,
+                p3.x as f32,
+                p3.y as f32,
+            ),
+            PathEl::ClosePath => builder.close(),
+        }
+    }
+    builder.finish()
+}
+
+fn convert_stroke(width: f64, style: Option<&StrokeStyle>) -> Stroke {
+    let line_join = style
+        .and_then(|style| style.line_join)
+        .unwrap_or(LineJoin::Miter);
+    let line_cap = style
+        .and_then(|style| style.line_cap)
+        .unwrap_or(LineCap::Butt);
+    let miter_limit = style.and_then(|style| style.miter_limit).unwrap_or(10.0);
+    let dash = style
+        .and_then(|style| style.dash.as_ref())
+        .and_then(|(dashes, offset)| {
+            let mut dashes: Vec<f32> = dashes.iter().map(|&dash| dash as f32).collect();
+            // tiny-skia needs an even number of lengths.
+            if dashes.len() % 2 == 1 {
+                dashes = dashes.repeat(2);
+            }
+            StrokeDash::new(dashes, *offset as f32)
+        });
+    Stroke {
+        width: width as f32,
+        miter_limit: miter_limit as f32,
+        line_cap: convert_line_cap(line_cap),
+        line_join: convert_line_join(line_join),
+        dash,
+    }
+}
+
+fn convert_line_cap(line_cap: LineCap) -> tiny_skia::LineCap {
+    match line_cap {
+        LineCap::Butt => tiny_skia::LineCap::Butt,
+        LineCap::Round => tiny_skia::LineCap::Round,
+        LineCap::Square => tiny_skia::LineCap::Square,
+    }
+}
+
+fn convert_line_join(line_join: LineJoin) -> tiny_skia::LineJoin {
+    match line_join {
+        LineJoin::Miter => tiny_skia::LineJoin::Miter,
+        LineJoin::Round => tiny_skia::LineJoin::Round,
+        LineJoin::Bevel => tiny_skia::LineJoin::Bevel,
+    }
+}
+
+fn convert_extend_mode(extend: ExtendMode) -> SpreadMode {
+    match extend {
+        ExtendMode::Pad => SpreadMode::Pad,
+        ExtendMode::Repeat => SpreadMode::Repeat,
+        ExtendMode::Reflect => SpreadMode::Reflect,
+    }
+}
+
+fn convert_blend_mode(mode: BlendMode) -> tiny_skia::BlendMode {
+    match mode {
+        BlendMode::Clear => tiny_skia::BlendMode::Clear,
+        BlendMode::Copy => tiny_skia::BlendMode::Source,
+        BlendMode::SourceOver => tiny_skia::BlendMode::SourceOver,
+        BlendMode::SourceIn => tiny_skia::BlendMode::SourceIn,
+        BlendMode::SourceOut => tiny_skia::BlendMode::SourceOut,
+        BlendMode::SourceAtop => tiny_skia::BlendMode::SourceAtop,
+        BlendMode::DestOver => tiny_skia::BlendMode::DestinationOver,
+        BlendMode::DestIn => tiny_skia::BlendMode::DestinationIn,
+        BlendMode::DestOut => tiny_skia::BlendMode::DestinationOut,
+        BlendMode::DestAtop => tiny_skia::BlendMode::DestinationAtop,
+        BlendMode::Xor => tiny_skia::BlendMode::Xor,
+        BlendMode::Add => tiny_skia::BlendMode::Plus,
+        BlendMode::Multiply => tiny_skia::BlendMode::Multiply,
+        BlendMode::Screen => tiny_skia::BlendMode::Screen,
+        BlendMode::Overlay => tiny_skia::BlendMode::Overlay,
+        BlendMode::Darken => tiny_skia::BlendMode::Darken,
+        BlendMode::Lighten => tiny_skia::BlendMode::Lighten,
+        BlendMode::ColorDodge => tiny_skia::BlendMode::ColorDodge,
+        BlendMode::ColorBurn => tiny_skia::BlendMode::ColorBurn,
+        BlendMode::HardLight => tiny_skia::BlendMode::HardLight,
+        BlendMode::SoftLight => tiny_skia::BlendMode::SoftLight,
+        BlendMode::Difference => tiny_skia::BlendMode::Difference,
+        BlendMode::Exclusion => tiny_skia::BlendMode::Exclusion,
+        BlendMode::Hue => tiny_skia::BlendMode::Hue,
+        BlendMode::Saturation => tiny_skia::BlendMode::Saturation,
+        BlendMode::Color => tiny_skia::BlendMode::Color,
+        BlendMode::Luminosity => tiny_skia::BlendMode::Luminosity,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use piet::Text;
+    use piet_test::draw_test_picture;
+
+    fn render(draw: impl FnOnce(&mut TinySkiaRenderContext) -> Result<(), Error>) -> Pixmap {
+        let mut pixmap = Pixmap::new(200, 100).unwrap();
+        {
+            let mut piet = TinySkiaRenderContext::new(&mut pixmap);
+            draw(&mut piet).unwrap();
+            piet.finish().unwrap();
+        }
+        pixmap
+    }
+
+    fn alpha_at(pixmap: &Pixmap, x: u32, y: u32) -> u8 {
+        pixmap.pixel(x, y).unwrap().alpha()
+    }
+
+    #[test]
+    fn captured_area_skips_open_layers() {
+        let mut pixels = None;
+        render(|rc| {
+            rc.fill(Rect::new(0.0, 0.0, 10.0, 10.0), &Color::rgb8(0xff, 0, 0));
+            rc.push_layer(0.5, None)?;
+            rc.fill(Rect::new(10.0, 0.0, 20.0, 10.0), &Color::rgb8(0, 0, 0xff));
+            // Drawing in a layer isn't on the target until it's popped.
+            let image = rc.capture_image_area(Rect::new(0.0, 0.0, 20.0, 10.0))?;
+            pixels = Some(rc.read_image(&image, ImageFormat::RgbaSeparate)?);
+            rc.pop_layer()
+        });
+        let (width, height, buf) = pixels.unwrap();
+        assert_eq!((width, height), (20, 10));
+        let beneath = (5 * width + 5) * 4;
+        assert_eq!(&buf[beneath..beneath + 4], &[0xff, 0, 0, 0xff]);
+        let in_layer = (5 * width + 15) * 4;
+        assert_eq!(&buf[in_layer..in_layer + 4], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn fill_rules() {
+        // Two nested squares, wound in the same direction.
+        let mut path = piet::kurbo::BezPath::new();
+        for &half in &[20.0, 10.0] {
+            let rect = Rect::new(50.0 - half, 50.0 - half, 50.0 + half, 50.0 + half);
+            for el in rect.to_bez_path(0.0) {
+                path.push(el);
+            }
+        }
+        let brush = Color::BLACK;
+        let non_zero = render(|rc| {
+            rc.fill(&path, &brush);
+            Ok(())
+        });
+        let even_odd = render(|rc| {
+            rc.fill_even_odd(&path, &brush);
+            Ok(())
+        });
+        assert_eq!(alpha_at(&non_zero, 50, 50), 255);
+        assert_eq!(alpha_at(&even_odd, 50, 50), 0);
+        assert_eq!(alpha_at(&even_odd, 35, 50), 255);
+    }
+
+    #[test]
+    fn clip_follows_transform() {
+        let pixmap = render(|rc| {
+            rc.transform(Affine::translate((100.0, 0.0)));
+            rc.clip(Rect::new(0.0, 0.0, 50.0, 50.0));
+            rc.fill(Rect::new(-100.0, 0.0, 100.0, 100.0), &Color::BLACK);
+            Ok(())
+        });
+        assert_eq!(alpha_at(&pixmap, 125, 25), 255);
+        assert_eq!(alpha_at(&pixmap, 25, 25), 0);
+        assert_eq!(alpha_at(&pixmap, 125, 75), 0);
+    }
+
+    #[test]
+    fn restore_across_layer_is_unbalanced() {
+        let mut pixmap = Pixmap::new(10, 10).unwrap();
+        let mut rc = TinySkiaRenderContext::new(&mut pixmap);
+        rc.save().unwrap();
+        rc.push_layer(0.5, None).unwrap();
+        assert!(rc.restore().is_err());
+        rc.pop_layer().unwrap();
+        rc.restore().unwrap();
+    }
+
+    #[test]
+    fn plays_recording_with_fonts() {
+        let mut recording = piet::RecordingRenderContext::new();
+        recording
+            .text()
+            .load_font(piet_test::SANS_SERIF_FONT)
+            .unwrap();
+        recording.fill(Rect::new(10.0, 10.0, 20.0, 20.0), &Color::BLACK);
+        let list = recording.into_display_list();
+        let pixmap = render(|rc| list.play(rc));
+        assert_eq!(alpha_at(&pixmap, 15, 15), 255);
+    }
+
+    #[test]
+    fn draws_test_pictures() {
+        // Pictures 0, 5 and 12 have text, which isn't supported yet.
+        for number in (1..=14).filter(|&n| n != 5 && n != 12) {
+            render(|rc| draw_test_picture(rc, number));
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Text functionality for the tiny-skia backend.
+//!
+//! There is no font rasterization yet, so fonts can't be built, and layouts
+//! only exist to satisfy the type requirements of `RenderContext`.
+
+use std::ops::RangeBounds;
+
+use piet::kurbo::Point;
+
+use piet::{
+    new_error, Error, ErrorKind, Font, FontBuilder, FontMetrics, FontStretch, FontStyle,
+    FontWeight, HitTestPoint, HitTestTextPosition, LineMetric, Text, TextAlignment, TextAttribute,
+    TextLayout, TextLayoutBuilder, TextLayoutMetrics,
+};
+
+/// The text factory for the tiny-skia backend.
+pub struct TinySkiaText;
+
+/// A font, which can't be built yet.
+pub struct TinySkiaFont;
+
+/// A font builder, which always fails with `NotSupported`.
+pub struct TinySkiaFontBuilder;
+
+/// A text layout, which can't be built yet.
+pub struct TinySkiaTextLayout;
+
+/// A text layout builder, which always fails with `NotSupported`.
+pub struct TinySkiaTextLayoutBuilder;
+
+impl TinySkiaText {
+    /// Create a new factory that satisfies the piet `Text` trait.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> TinySkiaText {
+        TinySkiaText
+    }
+}
+
+impl Text for TinySkiaText {
+    type Font = TinySkiaFont;
+    type FontBuilder = TinySkiaFontBuilder;
+    type TextLayout = TinySkiaTextLayout;
+    type TextLayoutBuilder = TinySkiaTextLayoutBuilder;
+
+    fn new_font_by_name(&mut self, _name: &str, _size: f64) -> Self::FontBuilder {
+        TinySkiaFontBuilder
+    }
+
+    fn load_font(&mut self, _data: &[u8]) -> Result<String, Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn font_families(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// No family is available, as fonts can't be built.
+    fn has_font_family(&mut self, _name: &str) -> bool {
+        false
+    }
+
+    fn new_text_layout(&mut self, _font: &Self::Font, _text: &str) -> Self::TextLayoutBuilder {
+        TinySkiaTextLayoutBuilder
+    }
+}
+
+impl Font for TinySkiaFont {
+    fn metrics(&self) -> FontMetrics {
+        FontMetrics::default()
+    }
+
+    fn family_name(&self) -> &str {
+        ""
+    }
+}
+
+impl FontBuilder for TinySkiaFontBuilder {
+    type Out = TinySkiaFont;
+
+    fn weight(self, _weight: FontWeight) -> Self {
+        self
+    }
+
+    fn style(self, _style: FontStyle) -> Self {
+        self
+    }
+
+    fn stretch(self, _stretch: FontStretch) -> Self {
+        self
+    }
+
+    fn build(self) -> Result<Self::Out, Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+}
+
+impl TextLayoutBuilder for TinySkiaTextLayoutBuilder {
+    type Out = TinySkiaTextLayout;
+
+    fn range_attribute(
+        self,
+        _range: impl RangeBounds<usize>,
+        _attribute: impl Into<TextAttribute>,
+    ) -> Self {
+        self
+    }
+
+    fn max_width(self, _width: f64) -> Self {
+        self
+    }
+
+    fn alignment(self, _alignment: TextAlignment) -> Self {
+        self
+    }
+
+    fn build(self) -> Result<Self::Out, Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+}
+
+impl TextLayout for TinySkiaTextLayout {
+    fn width(&self) -> f64 {
+        0.0
+    }
+
+    fn metrics(&self) -> TextLayoutMetrics {
+        TextLayoutMetrics::default()
+    }
+
+    fn line_count(&self) -> usize {
+        0
+    }
+
+    fn line_metric(&self, _line_number: usize) -> Option<LineMetric> {
+        None
+    }
+
+    fn line_text(&self, _line_number: usize) -> Option<&str> {
+        None
+    }
+
+    fn hit_test_point(&self, _point: Point) -> HitTestPoint {
+        HitTestPoint::default()
+    }
+
+    fn hit_test_text_position(&self, _text_position: usize) -> Option<HitTestTextPosition> {
+        None
+    }
+}
@@ -16,15 +16,18 @@ use web_sys::{
     Window,
 };
 
-use piet::kurbo::{Affine, PathEl, Point, Rect, Shape};
+use piet::kurbo::{Affine, PathEl, Point, Rect, Shape, Size};
 
 use piet::{
-    Color, Error, FixedGradient, GradientStop, ImageFormat, InterpolationMode, IntoBrush, LineCap,
+    new_error, BlendMode, Color, Error, ErrorKind, ExtendMode, FixedGradient, FixedLinearGradient,
+    FixedRadialGradient, GradientStop, ImageFormat, InterpolationMode, IntoBrush, LineCap,
     LineJoin, RenderContext, StrokeStyle,
 };
 
 pub use text::WebTextLayout;
 
+use text::lines::trim_newline;
+
 pub struct WebRenderContext<'a> {
     ctx: &'a mut CanvasRenderingContext2d,
     /// Used for creating image bitmaps and possibly other resources.
@@ -57,7 +60,8 @@ pub struct WebImage {
 }
 
 #[derive(Debug)]
-struct WrappedJs(JsValue);
+// A `JsValue` can't be sent between threads, so only its description is kept.
+struct WrappedJs(String);
 
 trait WrapError<T> {
     fn wrap(self) -> Result<T, Error>;
@@ -67,7 +71,7 @@ impl std::error::Error for WrappedJs {}
 
 impl fmt::Display for WrappedJs {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Canvas error: {:?}", self.0)
+        write!(f, "Canvas error: {}", self.0)
     }
 }
 
@@ -76,7 +80,8 @@ impl fmt::Display for WrappedJs {
 impl<T> WrapError<T> for Result<T, JsValue> {
     fn wrap(self) -> Result<T, Error> {
         self.map_err(|e| {
-            let e: Box<dyn std::error::Error> = Box::new(WrappedJs(e));
+            let e: Box<dyn std::error::Error + Send + Sync> =
+                Box::new(WrappedJs(format!("{:?}", e)));
             e.into()
         })
     }
@@ -111,6 +116,10 @@ impl<'a> RenderContext for WebRenderContext<'a> {
         std::mem::replace(&mut self.err, Ok(()))
     }
 
+    fn report_error(&mut self, err: Error) {
+        self.err = Err(err);
+    }
+
     fn clear(&mut self, color: Color) {
         let (width, height) = match self.ctx.canvas() {
             Some(canvas) => (canvas.width(), canvas.height()),
@@ -120,7 +129,10 @@ impl<'a> RenderContext for WebRenderContext<'a> {
         };
         let shape = Rect::new(0.0, 0.0, width as f64, height as f64);
         let brush = self.solid_brush(color);
+        self.ctx.save();
+        let _ = self.ctx.set_global_composite_operation("source-over");
         self.fill(shape, &brush);
+        self.ctx.restore();
     }
 
     fn solid_brush(&mut self, color: Color) -> Brush {
@@ -129,6 +141,13 @@ impl<'a> RenderContext for WebRenderContext<'a> {
 
     fn gradient(&mut self, gradient: impl Into<FixedGradient>) -> Result<Brush, Error> {
         match gradient.into() {
+            // TODO: canvas gradients always pad; other modes need a pattern.
+            FixedGradient::Linear(FixedLinearGradient { extend, .. })
+            | FixedGradient::Radial(FixedRadialGradient { extend, .. })
+                if extend != ExtendMode::Pad =>
+            {
+                Err(new_error(ErrorKind::NotSupported))
+            }
             FixedGradient::Linear(linear) => {
                 let (x0, y0) = (linear.start.x, linear.start.y);
                 let (x1, y1) = (linear.end.x, linear.end.y);
@@ -147,9 +166,22 @@ impl<'a> RenderContext for WebRenderContext<'a> {
                 set_gradient_stops(&mut rg, &radial.stops);
                 Ok(Brush::Gradient(rg))
             }
+            // TODO: use createConicGradient once web-sys exposes it.
+            FixedGradient::Sweep(_) => Err(new_error(ErrorKind::NotSupported)),
         }
     }
 
+    fn image_pattern(
+        &mut self,
+        _image: &Self::Image,
+        _transform: Affine,
+        _extend: ExtendMode,
+    ) -> Result<Brush, Error> {
+        // TODO: canvas patterns can only repeat, and web-sys only takes an
+        // `SvgMatrix` for their transform.
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
     fn fill(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
         let brush = brush.make_brush(self, || shape.bounding_box());
         self.set_path(shape);
@@ -166,6 +198,19 @@ impl<'a> RenderContext for WebRenderContext<'a> {
             .fill_with_canvas_winding_rule(CanvasWindingRule::Evenodd);
     }
 
+    fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &impl IntoBrush<Self>) {
+        let brush = brush.make_brush(self, || rect);
+        self.ctx.save();
+        // TODO: the filter radius is in canvas pixels, so it isn't affected by the
+        // transform, unlike the rect itself.
+        self.ctx.set_filter(&format!("blur({}px)", blur_radius));
+        self.set_path(rect);
+        self.set_brush(&*brush, true);
+        self.ctx
+            .fill_with_canvas_winding_rule(CanvasWindingRule::Nonzero);
+        self.ctx.restore();
+    }
+
     fn clip(&mut self, shape: impl Shape) {
         self.set_path(shape);
         self.ctx
@@ -206,11 +251,52 @@ impl<'a> RenderContext for WebRenderContext<'a> {
     ) {
         // TODO: bounding box for text
         let brush = brush.make_brush(self, || Rect::ZERO);
-        self.ctx.set_font(&layout.font.get_font_string());
-        self.set_brush(&*brush, true);
         let pos = pos.into();
-        if let Err(e) = self.ctx.fill_text(&layout.text, pos.x, pos.y).wrap() {
-            self.err = Err(e);
+        for line in &layout.lines {
+            let line_end = trim_newline(&layout.text, line.range.clone());
+            let y = pos.y + line.baseline;
+            for run in &layout.runs {
+                let start = run.range.start.max(line.range.start);
+                let end = run.range.end.min(line_end);
+                if start >= end {
+                    continue;
+                }
+                match &run.color {
+                    Some(color) => {
+                        let color = self.solid_brush(color.clone());
+                        self.set_brush(&color, true);
+                    }
+                    None => self.set_brush(&brush, true),
+                }
+                let x = pos.x + layout.line_x(line, start);
+                let width = pos.x + layout.line_x(line, end) - x;
+                self.ctx.set_font(&run.font.get_font_string());
+                let text = &layout.text[start..end];
+                if line.space_width > 0.0 {
+                    // Each word of a justified line is placed on its own.
+                    let mut word_start = start;
+                    for word in text.split_inclusive(' ') {
+                        let x = pos.x + layout.line_x(line, word_start);
+                        if let Err(e) = self.ctx.fill_text(word, x, y).wrap() {
+                            self.err = Err(e);
+                        }
+                        word_start += word.len();
+                    }
+                } else if let Err(e) = self.ctx.fill_text(text, x, y).wrap() {
+                    self.err = Err(e);
+                }
+                // Canvas has no text decorations, so these are typical proportions
+                // of the font size.
+                let thickness = (run.font.size / 16.0).max(1.0);
+                if run.underline {
+                    let y = y + run.font.size * 0.1;
+                    self.ctx.fill_rect(x, y, width, thickness);
+                }
+                if run.strikethrough {
+                    let y = y - run.font.size * 0.3;
+                    self.ctx.fill_rect(x, y, width, thickness);
+                }
+            }
         }
     }
 
@@ -224,6 +310,27 @@ impl<'a> RenderContext for WebRenderContext<'a> {
         Ok(())
     }
 
+    fn push_layer(&mut self, _opacity: f64, _clip: Option<Rect>) -> Result<(), Error> {
+        // TODO: render layers into an offscreen canvas and composite with globalAlpha.
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn pop_layer(&mut self) -> Result<(), Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn push_image_mask(&mut self, _image: &Self::Image, _dst_rect: Rect) -> Result<(), Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn push_mask(&mut self) -> Result<(), Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn pop_mask(&mut self) -> Result<(), Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
     fn finish(&mut self) -> Result<(), Error> {
         self.status()
     }
@@ -233,6 +340,44 @@ impl<'a> RenderContext for WebRenderContext<'a> {
         let _ = self.ctx.transform(a[0], a[1], a[2], a[3], a[4], a[5]);
     }
 
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        let op = match mode {
+            BlendMode::Copy => "copy",
+            BlendMode::SourceOver => "source-over",
+            BlendMode::SourceIn => "source-in",
+            BlendMode::SourceOut => "source-out",
+            BlendMode::SourceAtop => "source-atop",
+            BlendMode::DestOver => "destination-over",
+            BlendMode::DestIn => "destination-in",
+            BlendMode::DestOut => "destination-out",
+            BlendMode::DestAtop => "destination-atop",
+            BlendMode::Xor => "xor",
+            BlendMode::Add => "lighter",
+            BlendMode::Multiply => "multiply",
+            BlendMode::Screen => "screen",
+            BlendMode::Overlay => "overlay",
+            BlendMode::Darken => "darken",
+            BlendMode::Lighten => "lighten",
+            BlendMode::ColorDodge => "color-dodge",
+            BlendMode::ColorBurn => "color-burn",
+            BlendMode::HardLight => "hard-light",
+            BlendMode::SoftLight => "soft-light",
+            BlendMode::Difference => "difference",
+            BlendMode::Exclusion => "exclusion",
+            BlendMode::Hue => "hue",
+            BlendMode::Saturation => "saturation",
+            BlendMode::Color => "color",
+            BlendMode::Luminosity => "luminosity",
+            BlendMode::Clear => {
+                self.err = Err(new_error(ErrorKind::NotSupported));
+                return;
+            }
+        };
+        if let Err(e) = self.ctx.set_global_composite_operation(op).wrap() {
+            self.err = Err(e);
+        }
+    }
+
     fn current_transform(&self) -> Affine {
         // todo
         // current_transform() and get_transform() currently not implemented:
@@ -304,6 +449,90 @@ impl<'a> RenderContext for WebRenderContext<'a> {
         })
     }
 
+    fn create_offscreen(
+        &mut self,
+        size: Size,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<Self::Image, Error> {
+        let width = size.width.ceil() as u32;
+        let height = size.height.ceil() as u32;
+        let document = self.window.document().unwrap();
+        let element = document.create_element("canvas").unwrap();
+        let canvas = element.dyn_into::<HtmlCanvasElement>().unwrap();
+        canvas.set_width(width);
+        canvas.set_height(height);
+        let context = canvas
+            .get_context("2d")
+            .unwrap()
+            .unwrap()
+            .dyn_into::<web_sys::CanvasRenderingContext2d>()
+            .unwrap();
+        // The canvas state stack isn't visible, so unbalanced saves go
+        // unnoticed; they are dropped with the offscreen context.
+        let ctx = std::mem::replace(self.ctx, context);
+        let err = std::mem::replace(&mut self.err, Ok(()));
+        let result = f(self).and_then(|()| self.status());
+        *self.ctx = ctx;
+        self.err = err;
+        result?;
+        Ok(WebImage {
+            inner: canvas,
+            width,
+            height,
+        })
+    }
+
+    fn capture_image_area(&mut self, _src_rect: impl Into<Rect>) -> Result<Self::Image, Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn read_image(
+        &mut self,
+        image: &Self::Image,
+        format: ImageFormat,
+    ) -> Result<(usize, usize, Vec<u8>), Error> {
+        let context = image
+            .inner
+            .get_context("2d")
+            .unwrap()
+            .unwrap()
+            .dyn_into::<web_sys::CanvasRenderingContext2d>()
+            .unwrap();
+        let (width, height) = (image.width as usize, image.height as usize);
+        // Image data has separate alpha.
+        let data = context
+            .get_image_data(0.0, 0.0, width as f64, height as f64)
+            .wrap()?
+            .data();
+        let buf = match format {
+            ImageFormat::RgbaSeparate => data.0,
+            ImageFormat::RgbaPremul => {
+                fn premul(x: u8, a: u8) -> u8 {
+                    let y = (x as u16) * (a as u16);
+                    ((y + (y >> 8) + 0x80) >> 8) as u8
+                }
+                data.0
+                    .chunks_exact(4)
+                    .flat_map(|p| {
+                        vec![
+                            premul(p[0], p[3]),
+                            premul(p[1], p[3]),
+                            premul(p[2], p[3]),
+                            p[3],
+                        ]
+                    })
+                    .collect()
+            }
+            ImageFormat::Rgb => data
+                .0
+                .chunks_exact(4)
+                .flat_map(|p| [p[0], p[1], p[2]])
+                .collect(),
+            _ => return Err(new_error(ErrorKind::NotSupported)),
+        };
+        Ok((width, height, buf))
+    }
+
     #[inline]
     fn draw_image(
         &mut self,
@@ -1,47 +1,93 @@
 //! Text functionality for Piet web backend
 
 mod grapheme;
+pub(crate) mod lines;
 
 use std::borrow::Cow;
+use std::ops::{Range, RangeBounds};
 
-use web_sys::CanvasRenderingContext2d;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, FontFace};
 
 use piet::kurbo::Point;
 
 use piet::{
-    Error, Font, FontBuilder, HitTestMetrics, HitTestPoint, HitTestTextPosition, Text, TextLayout,
-    TextLayoutBuilder,
+    font_family_name, new_error, AttributeSpans, Color, Error, ErrorKind, Font, FontBuilder,
+    FontMetrics, FontStretch, FontWeight, GenericFamily, HitTestMetrics, HitTestPoint,
+    HitTestTextPosition, LineMetric, Text, TextAlignment, TextAttribute, TextLayout,
+    TextLayoutBuilder, TextLayoutMetrics, TextStyle,
 };
 use unicode_segmentation::UnicodeSegmentation;
 
 use self::grapheme::point_x_in_grapheme;
-use crate::WebRenderContext;
+use self::lines::{align, count_spaces, is_rtl, line_ranges, trim_end, trim_newline};
+use crate::{WebRenderContext, WrapError};
 
 #[derive(Clone)]
 pub struct WebFont {
     family: String,
+    /// The family the font resolved to, or the family of a run's style.
+    resolved_family: String,
     weight: u32,
     style: FontStyle,
-    size: f64,
+    stretch: FontStretch,
+    pub(crate) size: f64,
 }
 
 pub struct WebFontBuilder(WebFont);
 
 pub struct WebTextLayout {
     ctx: CanvasRenderingContext2d,
-    // TODO like cairo, should this be pub(crate)?
-    pub font: WebFont,
-    pub text: String,
+    pub(crate) text: String,
+    pub(crate) runs: Vec<WebRun>,
+    pub(crate) lines: Vec<WebLine>,
+}
+
+/// A run of text in a single font and style.
+pub(crate) struct WebRun {
+    pub(crate) range: Range<usize>,
+    pub(crate) font: WebFont,
+    /// The offset of the start of the run from the start of the layout.
+    pub(crate) x: f64,
+    pub(crate) width: f64,
+    pub(crate) color: Option<Color>,
+    pub(crate) underline: bool,
+    pub(crate) strikethrough: bool,
+}
+
+/// A line of text, after wrapping.
+pub(crate) struct WebLine {
+    /// The text of the line, including trailing whitespace and newline.
+    pub(crate) range: Range<usize>,
+    /// The offset of the start of the line with all of the text on one line.
+    pub(crate) x0: f64,
+    /// The end of the text that counts towards the width of the line.
+    pub(crate) content_end: usize,
+    /// The width of the line, without its newline, or the whitespace where
+    /// it wraps.
+    pub(crate) width: f64,
+    /// The offset of the line from the left of the layout, from its
+    /// alignment.
+    pub(crate) offset: f64,
+    /// The extra width of each space, when the line is justified.
+    pub(crate) space_width: f64,
+    /// The offset of the baseline from the baseline of the first line.
+    pub(crate) baseline: f64,
+    pub(crate) ascent: f64,
+    pub(crate) descent: f64,
+    pub(crate) height: f64,
 }
 
 pub struct WebTextLayoutBuilder {
     ctx: CanvasRenderingContext2d,
     font: WebFont,
     text: String,
+    attributes: AttributeSpans,
+    max_width: f64,
+    alignment: TextAlignment,
 }
 
 /// https://developer.mozilla.org/en-US/docs/Web/CSS/font-style
-#[allow(dead_code)] // TODO: Remove
 #[derive(Clone)]
 enum FontStyle {
     Normal,
@@ -49,22 +95,101 @@ enum FontStyle {
     Oblique(Option<f64>),
 }
 
+impl From<piet::FontStyle> for FontStyle {
+    fn from(style: piet::FontStyle) -> FontStyle {
+        match style {
+            piet::FontStyle::Regular => FontStyle::Normal,
+            piet::FontStyle::Italic => FontStyle::Italic,
+            piet::FontStyle::Oblique => FontStyle::Oblique(None),
+        }
+    }
+}
+
 impl<'a> Text for WebRenderContext<'a> {
     type Font = WebFont;
     type FontBuilder = WebFontBuilder;
     type TextLayout = WebTextLayout;
     type TextLayoutBuilder = WebTextLayoutBuilder;
 
+    /// A family that isn't available falls back to the browser's default
+    /// family, which is usually serif.
     fn new_font_by_name(&mut self, name: &str, size: f64) -> Self::FontBuilder {
+        let resolved_family = if self.has_font_family(name) {
+            name
+        } else {
+            GenericFamily::Serif.name()
+        };
         let font = WebFont {
             family: name.to_owned(),
+            resolved_family: resolved_family.to_owned(),
             size,
             weight: 400,
             style: FontStyle::Normal,
+            stretch: FontStretch::Normal,
         };
         WebFontBuilder(font)
     }
 
+    /// The font is added to the fonts of the window's document, so it can
+    /// also be used by the page.
+    fn load_font(&mut self, data: &[u8]) -> Result<String, Error> {
+        let family = font_family_name(data, 0)?;
+        let face = FontFace::new_with_u8_array(&family, data).wrap()?;
+        let document = self
+            .window
+            .document()
+            .ok_or_else(|| new_error(ErrorKind::NotSupported))?;
+        document.fonts().add(&face).wrap()?;
+        Ok(family)
+    }
+
+    /// The families of the fonts of the window's document. Browsers don't
+    /// list installed fonts.
+    fn font_families(&mut self) -> Vec<String> {
+        let fonts = match self.window.document() {
+            Some(document) => document.fonts(),
+            None => return Vec::new(),
+        };
+        let mut families: Vec<String> = js_sys::try_iter(&fonts)
+            .ok()
+            .flatten()
+            .into_iter()
+            .flatten()
+            .filter_map(|face| face.ok()?.dyn_into::<FontFace>().ok())
+            .map(|face| face.family().trim_matches('"').to_owned())
+            .collect();
+        families.sort();
+        families.dedup();
+        families
+    }
+
+    /// Installed families are found by measuring text in them, which is
+    /// the same width as text in the fallback family when they're missing.
+    fn has_font_family(&mut self, name: &str) -> bool {
+        const SAMPLE: &str = "mmmmmmmmmmlli";
+        if GenericFamily::from_name(name).is_some()
+            || self
+                .font_families()
+                .iter()
+                .any(|family| family.eq_ignore_ascii_case(name))
+        {
+            return true;
+        }
+        self.ctx.save();
+        let found = ["monospace", "serif"].iter().any(|fallback| {
+            self.ctx.set_font(&format!("72px {}", fallback));
+            let without = measure(self.ctx, SAMPLE);
+            self.ctx.set_font(&format!(
+                "72px \"{}\", {}",
+                name.replace('"', "\\\""),
+                fallback
+            ));
+            measure(self.ctx, SAMPLE) != without
+        });
+        self.ctx.restore();
+        found
+    }
+
     fn new_text_layout(&mut self, font: &Self::Font, text: &str) -> Self::TextLayoutBuilder {
         WebTextLayoutBuilder {
             // TODO: it's very likely possible to do this without cloning ctx, but
@@ -72,6 +197,9 @@ impl<'a> Text for WebRenderContext<'a> {
             ctx: self.ctx.clone(),
             font: font.clone(),
             text: text.to_owned(),
+            attributes: AttributeSpans::new(),
+            max_width: f64::INFINITY,
+            alignment: TextAlignment::default(),
         }
     }
 }
@@ -79,12 +207,39 @@ impl<'a> Text for WebRenderContext<'a> {
 impl FontBuilder for WebFontBuilder {
     type Out = WebFont;
 
+    fn weight(mut self, weight: FontWeight) -> Self {
+        self.0.weight = weight.to_raw().into();
+        self
+    }
+
+    fn style(mut self, style: piet::FontStyle) -> Self {
+        self.0.style = style.into();
+        self
+    }
+
+    fn stretch(mut self, stretch: FontStretch) -> Self {
+        self.0.stretch = stretch;
+        self
+    }
+
     fn build(self) -> Result<Self::Out, Error> {
         Ok(self.0)
     }
 }
 
-impl Font for WebFont {}
+impl Font for WebFont {
+    /// Canvas can't measure font metrics, so these are typical proportions
+    /// of the font size.
+    fn metrics(&self) -> FontMetrics {
+        FontMetrics::approximate(self.size)
+    }
+
+    /// Browsers don't say which font they draw with, so this is the family
+    /// asked for if it's available, and serif otherwise.
+    fn family_name(&self) -> &str {
+        &self.resolved_family
+    }
+}
 
 impl WebFont {
     // TODO should this be pub(crate)?
@@ -92,79 +247,359 @@ impl WebFont {
         let style_str = match self.style {
             FontStyle::Normal => Cow::from("normal"),
             FontStyle::Italic => Cow::from("italic"),
-            FontStyle::Oblique(None) => Cow::from("italic"),
+            FontStyle::Oblique(None) => Cow::from("oblique"),
             FontStyle::Oblique(Some(angle)) => Cow::from(format!("oblique {}deg", angle)),
         };
+        // Generic families are keywords, which mean other families when quoted.
+        let family = match GenericFamily::from_name(&self.family) {
+            Some(generic) => Cow::from(generic.name()),
+            None => Cow::from(format!("\"{}\"", self.family)),
+        };
         format!(
-            "{} {} {}px \"{}\"",
-            style_str, self.weight, self.size, self.family
+            "{} {} {} {}px {}",
+            style_str,
+            self.weight,
+            self.stretch.to_css(),
+            self.size,
+            family
         )
     }
 }
 
+impl WebFont {
+    fn style(&self) -> TextStyle {
+        let mut style = TextStyle::new(self.family.as_str(), self.size);
+        style.weight = FontWeight::new(self.weight as u16);
+        style.style = match self.style {
+            FontStyle::Normal => piet::FontStyle::Regular,
+            FontStyle::Italic => piet::FontStyle::Italic,
+            FontStyle::Oblique(_) => piet::FontStyle::Oblique,
+        };
+        style.stretch = self.stretch;
+        style
+    }
+
+    fn with_style(style: &TextStyle) -> WebFont {
+        WebFont {
+            family: style.family.clone(),
+            resolved_family: style.family.clone(),
+            weight: style.weight.to_raw().into(),
+            style: style.style.into(),
+            stretch: style.stretch,
+            size: style.size,
+        }
+    }
+}
+
 impl TextLayoutBuilder for WebTextLayoutBuilder {
     type Out = WebTextLayout;
 
+    fn range_attribute(
+        mut self,
+        range: impl RangeBounds<usize>,
+        attribute: impl Into<TextAttribute>,
+    ) -> Self {
+        self.attributes.add(&self.text, range, attribute.into());
+        self
+    }
+
+    fn max_width(mut self, width: f64) -> Self {
+        self.max_width = width;
+        self
+    }
+
+    fn alignment(mut self, alignment: TextAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
     fn build(self) -> Result<Self::Out, Error> {
-        self.ctx.set_font(&self.font.get_font_string());
-        Ok(WebTextLayout {
+        let mut x = 0.0;
+        let mut runs = Vec::new();
+        for (range, style) in self.attributes.runs(&self.text, &self.font.style()) {
+            let font = WebFont::with_style(&style);
+            self.ctx.set_font(&font.get_font_string());
+            let width = measure(&self.ctx, &self.text[range.clone()]);
+            runs.push(WebRun {
+                range,
+                font,
+                x,
+                width,
+                color: style.color,
+                underline: style.underline,
+                strikethrough: style.strikethrough,
+            });
+            x += width;
+        }
+        let mut layout = WebTextLayout {
             ctx: self.ctx,
-            font: self.font,
             text: self.text,
-        })
+            runs,
+            lines: Vec::new(),
+        };
+        layout.lines = layout.break_lines(self.max_width);
+        layout.align_lines(self.max_width, self.alignment);
+        Ok(layout)
+    }
+}
+
+impl WebTextLayout {
+    /// The run containing a text position, or the last run.
+    fn run_at(&self, text_position: usize) -> &WebRun {
+        self.runs
+            .iter()
+            .find(|run| text_position < run.range.end)
+            .unwrap_or_else(|| self.runs.last().unwrap())
+    }
+
+    /// The offset of a text position from the start of the layout, with
+    /// all of the text on one line.
+    pub(crate) fn x_at(&self, text_position: usize) -> f64 {
+        let run = self.run_at(text_position);
+        if text_position >= run.range.end {
+            return run.x + run.width;
+        }
+        let end = text_position.max(run.range.start);
+        self.ctx.set_font(&run.font.get_font_string());
+        run.x + measure(&self.ctx, &self.text[run.range.start..end])
+    }
+
+    fn break_lines(&self, max_width: f64) -> Vec<WebLine> {
+        let mut baseline = 0.0;
+        let mut prev: Option<(f64, f64)> = None;
+        line_ranges(&self.text, max_width, |i| self.x_at(i))
+            .into_iter()
+            .map(|range| {
+                // Lines are spaced by their largest font.
+                let size = self
+                    .runs
+                    .iter()
+                    .filter(|run| run.range.start < range.end && range.start < run.range.end)
+                    .map(|run| run.font.size)
+                    .fold(self.run_at(range.start).font.size, f64::max);
+                let metrics = FontMetrics::approximate(size);
+                let (ascent, descent, height) =
+                    (metrics.ascent, metrics.descent, metrics.line_height());
+                if let Some((prev_ascent, prev_height)) = prev {
+                    baseline += prev_height - prev_ascent + ascent;
+                }
+                prev = Some((ascent, height));
+
+                // Whitespace at a wrapped line break hangs past the end
+                // of the line.
+                let end = trim_newline(&self.text, range.clone());
+                let end = if end == range.end && end < self.text.len() {
+                    trim_end(&self.text, range.clone())
+                } else {
+                    end
+                };
+                let x0 = self.x_at(range.start);
+                let width = self.x_at(end) - x0;
+                WebLine {
+                    range,
+                    x0,
+                    content_end: end,
+                    width,
+                    offset: 0.0,
+                    space_width: 0.0,
+                    baseline,
+                    ascent,
+                    descent,
+                    height,
+                }
+            })
+            .collect()
+    }
+
+    /// Place the lines within the width of the layout.
+    fn align_lines(&mut self, max_width: f64, alignment: TextAlignment) {
+        let width = if max_width.is_finite() {
+            max_width
+        } else {
+            self.width()
+        };
+        let mut rtl = false;
+        let mut paragraph_start = true;
+        for line in &mut self.lines {
+            if paragraph_start {
+                rtl = is_rtl(&self.text[line.range.start..]);
+            }
+            let ends_paragraph = line.range.end == self.text.len()
+                || trim_newline(&self.text, line.range.clone()) < line.range.end;
+            let spaces = count_spaces(&self.text[line.range.start..line.content_end]);
+            let (offset, space_width) =
+                align(alignment, rtl, ends_paragraph, line.width, width, spaces);
+            line.offset = offset;
+            line.space_width = space_width;
+            paragraph_start = ends_paragraph;
+        }
+    }
+
+    /// The offset of a text position in a line from the left of the layout.
+    pub(crate) fn line_x(&self, line: &WebLine, text_position: usize) -> f64 {
+        let mut x = self.x_at(text_position) - line.x0 + line.offset;
+        if line.space_width > 0.0 {
+            let end = text_position.clamp(line.range.start, line.content_end);
+            x += line.space_width * count_spaces(&self.text[line.range.start..end]) as f64;
+        }
+        x
+    }
+
+    /// The line a text position is on. A position at a line break is on the
+    /// line after it.
+    fn line_at(&self, text_position: usize) -> &WebLine {
+        self.lines
+            .iter()
+            .rev()
+            .find(|line| line.range.start <= text_position)
+            .unwrap_or(&self.lines[0])
     }
 }
 
+fn measure(ctx: &CanvasRenderingContext2d, text: &str) -> f64 {
+    ctx.measure_text(text)
+        .map(|m| m.width())
+        .expect("Text measurement failed")
+}
+
 impl TextLayout for WebTextLayout {
     fn width(&self) -> f64 {
-        //cairo:
-        //self.font.text_extents(&self.text).x_advance
-        self.ctx
-            .measure_text(&self.text)
-            .map(|m| m.width())
-            .expect("Text measurement failed")
+        self.lines.iter().map(|line| line.width).fold(0.0, f64::max)
+    }
+
+    fn metrics(&self) -> TextLayoutMetrics {
+        let first = &self.lines[0];
+        let last = self.lines.last().unwrap();
+        TextLayoutMetrics {
+            height: self.lines.iter().map(|line| line.height).sum(),
+            ascent: first.ascent,
+            descent: last.descent,
+            line_gap: first.height - first.ascent - first.descent,
+            line_count: self.lines.len(),
+        }
+    }
+
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn line_metric(&self, line_number: usize) -> Option<LineMetric> {
+        let line = self.lines.get(line_number)?;
+        Some(LineMetric {
+            start_offset: line.range.start,
+            end_offset: line.range.end,
+            baseline: line.baseline,
+            ascent: line.ascent,
+            height: line.height,
+        })
+    }
+
+    fn line_text(&self, line_number: usize) -> Option<&str> {
+        let line = self.lines.get(line_number)?;
+        Some(&self.text[line.range.clone()])
     }
 
-    // first assume one line.
-    // TODO do with lines
     fn hit_test_point(&self, point: Point) -> HitTestPoint {
         // internal logic is using grapheme clusters, but return the text position associated
         // with the border of the grapheme cluster.
 
+        // find the line, by the bottom of each line
+        let first = &self.lines[0];
+        let is_inside_y = point.y >= first.baseline - first.ascent;
+        let line = self
+            .lines
+            .iter()
+            .find(|line| point.y < line.baseline - line.ascent + line.height);
+        let (line, is_inside_y) = match line {
+            Some(line) => (line, is_inside_y),
+            None => (self.lines.last().unwrap(), false),
+        };
+        let mut hit = self.hit_test_line(line, point.x);
+        hit.is_inside &= is_inside_y;
+        hit
+    }
+
+    fn hit_test_text_position(&self, text_position: usize) -> Option<HitTestTextPosition> {
+        // Using substrings, but now with unicode grapheme awareness
+
+        let text_len = self.text.len();
+
+        if text_position as usize >= text_len {
+            let line = self.lines.last().unwrap();
+            return Some(HitTestTextPosition {
+                point: Point {
+                    x: self.line_x(line, text_len),
+                    y: line.baseline,
+                },
+                metrics: HitTestMetrics {
+                    text_position: text_len,
+                },
+            });
+        }
+
+        // If text position is not at a grapheme boundary, use the text position of current
+        // grapheme cluster. But return the original text position
+        // Use the indices (byte offset, which for our purposes = utf8 code units).
+        let grapheme_indices = UnicodeSegmentation::grapheme_indices(self.text.as_str(), true)
+            .take_while(|(byte_idx, _s)| text_position >= *byte_idx);
+        let byte_idx = grapheme_indices
+            .last()
+            .map(|(byte_idx, _s)| byte_idx)
+            .unwrap_or(0);
+        let line = self.line_at(byte_idx);
+
+        Some(HitTestTextPosition {
+            point: Point {
+                x: self.line_x(line, byte_idx),
+                y: line.baseline,
+            },
+            metrics: HitTestMetrics { text_position },
+        })
+    }
+}
+
+impl WebTextLayout {
+    /// Find the text position in a line closest to an x offset.
+    fn hit_test_line(&self, line: &WebLine, x: f64) -> HitTestPoint {
+        let line_text = &self.text[line.range.start..trim_newline(&self.text, line.range.clone())];
+
         // null case
-        if self.text.is_empty() {
-            return HitTestPoint::default();
+        if line_text.is_empty() {
+            let mut res = HitTestPoint::default();
+            res.metrics.text_position = line.range.start;
+            return res;
         }
 
         // get bounds
-        // TODO handle if string is not null yet count is 0?
-        let end = UnicodeSegmentation::graphemes(self.text.as_str(), true).count() - 1;
-        let end_bounds = match self.get_grapheme_boundaries(end) {
+        let end = UnicodeSegmentation::graphemes(line_text, true).count() - 1;
+        let end_bounds = match self.get_grapheme_boundaries(line, end) {
             Some(bounds) => bounds,
             None => return HitTestPoint::default(),
         };
 
         let start = 0;
-        let start_bounds = match self.get_grapheme_boundaries(start) {
+        let start_bounds = match self.get_grapheme_boundaries(line, start) {
             Some(bounds) => bounds,
             None => return HitTestPoint::default(),
         };
 
         // first test beyond ends
-        if point.x > end_bounds.trailing {
+        if x > end_bounds.trailing {
             let mut res = HitTestPoint::default();
-            res.metrics.text_position = self.text.len();
+            res.metrics.text_position = end_bounds.next_idx;
             return res;
         }
-        if point.x <= start_bounds.leading {
-            return HitTestPoint::default();
+        if x <= start_bounds.leading {
+            let mut res = HitTestPoint::default();
+            res.metrics.text_position = start_bounds.curr_idx;
+            return res;
         }
 
         // then test the beginning and end (common cases)
-        if let Some(hit) = point_x_in_grapheme(point.x, &start_bounds) {
+        if let Some(hit) = point_x_in_grapheme(x, &start_bounds) {
             return hit;
         }
-        if let Some(hit) = point_x_in_grapheme(point.x, &end_bounds) {
+        if let Some(hit) = point_x_in_grapheme(x, &end_bounds) {
             return hit;
         }
 
@@ -176,76 +611,24 @@ impl TextLayout for WebTextLayout {
             // pick halfway point
             let middle = left + ((right - left) / 2);
 
-            let grapheme_bounds = match self.get_grapheme_boundaries(middle) {
+            let grapheme_bounds = match self.get_grapheme_boundaries(line, middle) {
                 Some(bounds) => bounds,
                 None => return HitTestPoint::default(),
             };
 
-            if let Some(hit) = point_x_in_grapheme(point.x, &grapheme_bounds) {
+            if let Some(hit) = point_x_in_grapheme(x, &grapheme_bounds) {
                 return hit;
             }
 
             // since it's not a hit, check if closer to start or finish
             // and move the appropriate search boundary
-            if point.x < grapheme_bounds.leading {
+            if x < grapheme_bounds.leading {
                 right = middle;
-            } else if point.x > grapheme_bounds.trailing {
+            } else if x > grapheme_bounds.trailing {
                 left = middle + 1;
             } else {
                 unreachable!("hit_test_point conditional is exhaustive");
             }
         }
     }
-
-    fn hit_test_text_position(&self, text_position: usize) -> Option<HitTestTextPosition> {
-        // Using substrings, but now with unicode grapheme awareness
-
-        let text_len = self.text.len();
-
-        if text_position == 0 {
-            return Some(HitTestTextPosition::default());
-        }
-
-        if text_position as usize >= text_len {
-            let x = self.width();
-
-            return Some(HitTestTextPosition {
-                point: Point { x, y: 0.0 },
-                metrics: HitTestMetrics {
-                    text_position: text_len,
-                },
-            });
-        }
-
-        // Already checked that text_position > 0 and text_position < count.
-        // If text position is not at a grapheme boundary, use the text position of current
-        // grapheme cluster. But return the original text position
-        // Use the indices (byte offset, which for our purposes = utf8 code units).
-        let grapheme_indices = UnicodeSegmentation::grapheme_indices(self.text.as_str(), true)
-            .take_while(|(byte_idx, _s)| text_position >= *byte_idx);
-
-        if let Some((byte_idx, _s)) = grapheme_indices.last() {
-            let x = self
-                .ctx
-                .measure_text(&self.text[0..byte_idx])
-                .map(|m| m.width())
-                .expect("Text measurement failed");
-
-            Some(HitTestTextPosition {
-                point: Point { x, y: 0.0 },
-                metrics: HitTestMetrics { text_position },
-            })
-        } else {
-            // iterated to end boundary
-            Some(HitTestTextPosition {
-                point: Point {
-                    x: self.width(),
-                    y: 0.0,
-                },
-                metrics: HitTestMetrics {
-                    text_position: text_len,
-                },
-            })
-        }
-    }
 }
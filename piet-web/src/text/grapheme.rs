@@ -1,6 +1,8 @@
-use piet::{HitTestPoint, TextLayout};
+use piet::HitTestPoint;
 use unicode_segmentation::UnicodeSegmentation;
 
+use super::lines::trim_newline;
+use super::WebLine;
 use crate::WebTextLayout;
 
 // currently copied and pasted from cairo backend.
@@ -9,22 +11,27 @@ use crate::WebTextLayout;
 // code in `piet` core doesn't really make sense as it's implementation specific.
 //
 impl WebTextLayout {
+    /// The boundaries of a grapheme cluster in a line, not counting the
+    /// line's newline.
     pub(crate) fn get_grapheme_boundaries(
         &self,
+        line: &WebLine,
         grapheme_position: usize,
     ) -> Option<GraphemeBoundaries> {
-        let mut graphemes = UnicodeSegmentation::grapheme_indices(self.text.as_str(), true);
+        let line_start = line.range.start;
+        let line_text = &self.text[line_start..trim_newline(&self.text, line.range.clone())];
+        let mut graphemes = UnicodeSegmentation::grapheme_indices(line_text, true);
         let (text_position, _) = graphemes.nth(grapheme_position)?;
-        let (next_text_position, _) = graphemes.next().unwrap_or_else(|| (self.text.len(), ""));
+        let (next_text_position, _) = graphemes.next().unwrap_or_else(|| (line_text.len(), ""));
 
-        let curr_edge = self.hit_test_text_position(text_position)?;
-        let next_edge = self.hit_test_text_position(next_text_position)?;
+        let curr_idx = line_start + text_position;
+        let next_idx = line_start + next_text_position;
 
         let res = GraphemeBoundaries {
-            curr_idx: curr_edge.metrics.text_position,
-            next_idx: next_edge.metrics.text_position,
-            leading: curr_edge.point.x,
-            trailing: next_edge.point.x,
+            curr_idx,
+            next_idx,
+            leading: self.line_x(line, curr_idx),
+            trailing: self.line_x(line, next_idx),
         };
 
         Some(res)
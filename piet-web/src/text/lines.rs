@@ -0,0 +1,104 @@
+//! Breaking text into lines.
+
+// currently copied and pasted from cairo backend, like grapheme.rs.
+
+use std::ops::Range;
+
+use piet::TextAlignment;
+use unicode_bidi::{get_base_direction, Direction};
+use xi_unicode::LineBreakIterator;
+
+/// Split text into lines, at explicit newlines and wherever the text would
+/// be wider than `max_width`.
+///
+/// `x_at` gives the offset of a text position with all of the text laid out
+/// on one line. Lines break at the line break opportunities of UAX #14;
+/// trailing whitespace doesn't count towards the width of a line. A word
+/// wider than `max_width` gets a line of its own, and overflows it.
+///
+/// The ranges include trailing whitespace and newlines, so they cover all
+/// of the text. Text ending in a newline has a last line that is empty.
+pub(crate) fn line_ranges(
+    text: &str,
+    max_width: f64,
+    x_at: impl Fn(usize) -> f64,
+) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut prev_break = 0;
+    for (offset, _) in LineBreakIterator::new(text) {
+        let end = trim_end(text, line_start..offset);
+        if prev_break > line_start && x_at(end) - x_at(line_start) > max_width {
+            lines.push(line_start..prev_break);
+            line_start = prev_break;
+        }
+        // The iterator also reports the end of the text as a hard break, so
+        // check for the newline itself.
+        if text[..offset].ends_with(is_newline) {
+            lines.push(line_start..offset);
+            line_start = offset;
+        }
+        prev_break = offset;
+    }
+    if line_start < text.len() || lines.is_empty() || text.ends_with(is_newline) {
+        lines.push(line_start..text.len());
+    }
+    lines
+}
+
+/// Place a line of text within the width of a layout.
+///
+/// Returns the offset of the line from the left of the layout, and the
+/// extra width given to each of its spaces when it's justified.
+pub(crate) fn align(
+    alignment: TextAlignment,
+    rtl: bool,
+    ends_paragraph: bool,
+    line_width: f64,
+    width: f64,
+    spaces: usize,
+) -> (f64, f64) {
+    let extra = (width - line_width).max(0.0);
+    let alignment = match alignment {
+        TextAlignment::Justified if !ends_paragraph && spaces > 0 => {
+            return (0.0, extra / spaces as f64)
+        }
+        TextAlignment::Justified => TextAlignment::Start,
+        alignment => alignment,
+    };
+    let offset = match (alignment, rtl) {
+        (TextAlignment::Center, _) => extra / 2.0,
+        (TextAlignment::Start, false) | (TextAlignment::End, true) => 0.0,
+        _ => extra,
+    };
+    (offset, 0.0)
+}
+
+/// Whether a paragraph is right-to-left, from its first strong character.
+///
+/// Only the first paragraph of `text` is considered.
+pub(crate) fn is_rtl(text: &str) -> bool {
+    get_base_direction(text) == Direction::Rtl
+}
+
+/// The number of spaces in some text, which widen when it's justified.
+pub(crate) fn count_spaces(text: &str) -> usize {
+    text.matches(' ').count()
+}
+
+/// The end of a line without its trailing whitespace and newline.
+pub(crate) fn trim_end(text: &str, range: Range<usize>) -> usize {
+    range.start + text[range].trim_end().len()
+}
+
+/// The end of a line without its newline.
+pub(crate) fn trim_newline(text: &str, range: Range<usize>) -> usize {
+    range.start + text[range].trim_end_matches(is_newline).len()
+}
+
+fn is_newline(c: char) -> bool {
+    matches!(
+        c,
+        '\n' | '\r' | '\u{0b}' | '\u{0c}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+    )
+}
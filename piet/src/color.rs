@@ -1,20 +1,74 @@
 //! A simple representation of color
 
-use std::fmt::{Debug, Formatter};
+use std::fmt::{self, Debug, Formatter};
 
 /// A datatype representing color.
 ///
-/// Currently this is only a 32 bit RGBA value, but it will likely
-/// extend to some form of wide-gamut colorspace, and in the meantime
-/// is useful for giving programs proper type.
-#[derive(Clone)]
+/// A color is either a 32 bit sRGB value, or floating point components in
+/// an explicit [`ColorSpace`], which can express wide-gamut colors and keep
+/// more precision than 8 bits per sample.
+///
+/// [`ColorSpace`]: enum.ColorSpace.html
+#[derive(Clone, PartialEq)]
 pub enum Color {
+    /// An sRGB color with separate alpha, packed as `0xRRGGBBAA`.
     Rgba32(u32),
+    /// Red, green, blue and alpha in a color space, with separate alpha.
+    ///
+    /// The color components are nominally in the range 0.0 to 1.0, but
+    /// may be outside of it for colors outside the gamut of the space.
+    Float {
+        space: ColorSpace,
+        r: f64,
+        g: f64,
+        b: f64,
+        a: f64,
+    },
+}
+
+/// The color space of floating point color components.
+///
+/// All spaces share the D65 white point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// The sRGB color space, with its transfer function.
+    Srgb,
+    /// The sRGB primaries, with components linear in light intensity.
+    LinearSrgb,
+    /// The Display P3 color space, with the sRGB transfer function.
+    DisplayP3,
+}
+
+/// A color space for interpolating between colors.
+///
+/// The hue of the polar spaces is interpolated along the shorter arc, and
+/// the other components are premultiplied by alpha, as in CSS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterpolationSpace {
+    /// Interpolate in sRGB, as back-ends do for gradients.
+    Srgb,
+    /// Interpolate in linear sRGB, which mixes light physically.
+    LinearSrgb,
+    /// Interpolate the hue, saturation and lightness.
+    Hsl,
+    /// Interpolate the hue, saturation and value.
+    Hsv,
+    /// Interpolate in CIE LCh, the polar form of CIEL\*a\*b\*.
+    Lch,
+    /// Interpolate in OKLab, which is perceptually uniform.
+    OkLab,
+    /// Interpolate in OKLCh, the polar form of OKLab.
+    OkLch,
 }
 
 impl Debug for Color {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "#{:08x}", self.as_rgba_u32())
+        match *self {
+            Color::Rgba32(rgba) => write!(f, "#{:08x}", rgba),
+            Color::Float { space, r, g, b, a } => {
+                write!(f, "{:?}({} {} {} / {})", space, r, g, b, a)
+            }
+        }
     }
 }
 
@@ -62,7 +116,7 @@ impl Color {
     /// Create a color from four floating point values, each in the range 0.0 to 1.0.
     ///
     /// The interpretation is the same as rgba32, and no greater precision is
-    /// (currently) assumed.
+    /// assumed. Use [`rgba_in`](#method.rgba_in) to keep full precision.
     pub fn rgba<F: Into<f64>>(r: F, g: F, b: F, a: F) -> Color {
         let r = (r.into().max(0.0).min(1.0) * 255.0).round() as u32;
         let g = (g.into().max(0.0).min(1.0) * 255.0).round() as u32;
@@ -74,7 +128,7 @@ impl Color {
     /// Create a color from three floating point values, each in the range 0.0 to 1.0.
     ///
     /// The interpretation is the same as rgb8, and no greater precision is
-    /// (currently) assumed.
+    /// assumed. Use [`rgb_in`](#method.rgb_in) to keep full precision.
     pub fn rgb<F: Into<f64>>(r: F, g: F, b: F) -> Color {
         let r = (r.into().max(0.0).min(1.0) * 255.0).round() as u32;
         let g = (g.into().max(0.0).min(1.0) * 255.0).round() as u32;
@@ -82,6 +136,31 @@ impl Color {
         Color::from_rgba32_u32((r << 24) | (g << 16) | (b << 8) | 0xff)
     }
 
+    /// Create a color from floating point RGBA values in a color space.
+    ///
+    /// The values are kept as they are, without clamping or quantizing.
+    ///
+    /// ```
+    /// use piet::{Color, ColorSpace};
+    ///
+    /// // A red outside of the sRGB gamut.
+    /// let red = Color::rgba_in(ColorSpace::DisplayP3, 1.0, 0.0, 0.0, 1.0);
+    /// let (r, g, b, _) = red.as_rgba_in(ColorSpace::Srgb);
+    /// assert!(r > 1.0 && g < 0.0 && b < 0.0);
+    /// // The 32 bit value is clipped to the sRGB gamut.
+    /// assert_eq!(red.as_rgba_u32(), 0xff0000ff);
+    /// ```
+    pub fn rgba_in(space: ColorSpace, r: f64, g: f64, b: f64, a: f64) -> Color {
+        Color::Float { space, r, g, b, a }
+    }
+
+    /// Create an opaque color from floating point RGB values in a color space.
+    ///
+    /// The values are kept as they are, without clamping or quantizing.
+    pub fn rgb_in(space: ColorSpace, r: f64, g: f64, b: f64) -> Color {
+        Color::rgba_in(space, r, g, b, 1.0)
+    }
+
     /// Create a color from a CIEL\*a\*b\* polar (also known as CIE HCL)
     /// specification.
     ///
@@ -99,75 +178,248 @@ impl Color {
     /// Currently out-of-gamut values are clipped to the nearest sRGB color,
     /// which is perhaps not ideal (the clipping might change the hue). See
     /// https://github.com/d3/d3-color/issues/33 for discussion.
-    #[allow(non_snake_case)]
-    #[allow(clippy::many_single_char_names)]
-    #[allow(clippy::unreadable_literal)]
     pub fn hlc<F: Into<f64>>(h: F, l: F, c: F) -> Color {
-        // The reverse transformation from Lab to XYZ, see
-        // https://en.wikipedia.org/wiki/CIELAB_color_space
-        fn f_inv(t: f64) -> f64 {
-            let d = 6. / 29.;
-            if t > d {
-                t.powi(3)
-            } else {
-                3. * d * d * (t - 4. / 29.)
-            }
-        }
         let th = h.into() * (std::f64::consts::PI / 180.);
         let c = c.into();
-        let a = c * th.cos();
-        let b = c * th.sin();
-        let L = l.into();
-        let ll = (L + 16.) * (1. / 116.);
-        // Produce raw XYZ values
-        let X = f_inv(ll + a * (1. / 500.));
-        let Y = f_inv(ll);
-        let Z = f_inv(ll - b * (1. / 200.));
-        // This matrix is the concatenation of three sources.
-        // First, the white point is taken to be ICC standard D50, so
-        // the diagonal matrix of [0.9642, 1, 0.8249]. Note that there
-        // is some controversy around this value. However, it matches
-        // the other matrices, thus minimizing chroma error.
-        //
-        // Second, an adaption matrix from D50 to D65. This is the
-        // inverse of the recommended D50 to D65 adaptation matrix
-        // from the W3C sRGB spec:
-        // https://www.w3.org/Graphics/Color/srgb
-        //
-        // Finally, the conversion from XYZ to linear sRGB values,
-        // also taken from the W3C sRGB spec.
-        let r_lin = 3.02172918 * X - 1.61692294 * Y - 0.40480625 * Z;
-        let g_lin = -0.94339358 * X + 1.91584267 * Y + 0.02755094 * Z;
-        let b_lin = 0.06945666 * X - 0.22903204 * Y + 1.15957526 * Z;
-        fn gamma(u: f64) -> f64 {
-            if u <= 0.0031308 {
-                12.92 * u
-            } else {
-                1.055 * u.powf(1. / 2.4) - 0.055
-            }
-        }
-        Color::rgb(gamma(r_lin), gamma(g_lin), gamma(b_lin))
+        let [r, g, b] = lab_to_linear_srgb(l.into(), c * th.cos(), c * th.sin());
+        Color::rgb(linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b))
     }
 
     /// Create a color from a CIEL\*a\*b\* polar specification and alpha.
     ///
     /// The `a` value represents alpha in the range 0.0 to 1.0.
     pub fn hlca<F: Into<f64>>(h: F, l: F, c: F, a: impl Into<f64>) -> Color {
-        Color::hlc(h, c, l).with_alpha(a)
+        Color::hlc(h, l, c).with_alpha(a)
+    }
+
+    /// Create a color from HSL values.
+    ///
+    /// The hue `h` is an angle in degrees, with 0 red, 120 green and 240
+    /// blue. The saturation `s` and lightness `l` are in the range 0.0 to 1.0.
+    pub fn hsl<F: Into<f64>>(h: F, s: F, l: F) -> Color {
+        Color::hsla(h, s, l, 1.0)
+    }
+
+    /// Create a color from HSL values and alpha.
+    ///
+    /// The `a` value represents alpha in the range 0.0 to 1.0.
+    pub fn hsla<F: Into<f64>>(h: F, s: F, l: F, a: impl Into<f64>) -> Color {
+        let [r, g, b] = hsl_to_srgb(h.into(), s.into(), l.into());
+        Color::rgba(r, g, b, a.into())
+    }
+
+    /// Create a color from HSV values.
+    ///
+    /// The hue `h` is an angle in degrees, as for [`hsl`](#method.hsl). The
+    /// saturation `s` and value `v` are in the range 0.0 to 1.0.
+    pub fn hsv<F: Into<f64>>(h: F, s: F, v: F) -> Color {
+        Color::hsva(h, s, v, 1.0)
+    }
+
+    /// Create a color from HSV values and alpha.
+    ///
+    /// The `a` value represents alpha in the range 0.0 to 1.0.
+    pub fn hsva<F: Into<f64>>(h: F, s: F, v: F, a: impl Into<f64>) -> Color {
+        let [r, g, b] = hsv_to_srgb(h.into(), s.into(), v.into());
+        Color::rgba(r, g, b, a.into())
+    }
+
+    /// Create a color from OKLab values.
+    ///
+    /// The lightness `l` is in the range 0.0 to 1.0, and `a` and `b` are
+    /// nominally within ±0.4. The color keeps floating point precision, and
+    /// may be outside the sRGB gamut.
+    pub fn oklab<F: Into<f64>>(l: F, a: F, b: F) -> Color {
+        Color::oklaba(l, a, b, 1.0)
+    }
+
+    /// Create a color from OKLab values and alpha.
+    ///
+    /// The `alpha` value is in the range 0.0 to 1.0.
+    pub fn oklaba<F: Into<f64>>(l: F, a: F, b: F, alpha: impl Into<f64>) -> Color {
+        let [r, g, b] = oklab_to_linear_srgb(l.into(), a.into(), b.into());
+        Color::rgba_in(ColorSpace::LinearSrgb, r, g, b, alpha.into()).to_srgb()
+    }
+
+    /// The HSL components of a color, as hue, saturation, lightness and alpha.
+    ///
+    /// The hue is in degrees, from 0 up to 360, and is 0 for greys. Colors
+    /// outside the sRGB gamut are clipped to it first.
+    ///
+    /// ```
+    /// use piet::Color;
+    ///
+    /// let (h, s, l, _) = Color::rgb8(0x66, 0x33, 0x99).to_hsl();
+    /// assert_eq!((h.round(), (s * 100.).round(), (l * 100.).round()), (270., 50., 40.));
+    /// ```
+    pub fn to_hsl(&self) -> (f64, f64, f64, f64) {
+        let (r, g, b, a) = self.as_rgba();
+        let (h, max, min) = hue_of_srgb(r, g, b);
+        let l = (max + min) / 2.0;
+        let d = max - min;
+        let s = if d > 0.0 {
+            d / (1.0 - (2.0 * l - 1.0).abs())
+        } else {
+            0.0
+        };
+        (h, s, l, a)
+    }
+
+    /// The HSV components of a color, as hue, saturation, value and alpha.
+    ///
+    /// The hue is in degrees, from 0 up to 360, and is 0 for greys. Colors
+    /// outside the sRGB gamut are clipped to it first.
+    pub fn to_hsv(&self) -> (f64, f64, f64, f64) {
+        let (r, g, b, a) = self.as_rgba();
+        let (h, max, min) = hue_of_srgb(r, g, b);
+        let s = if max > 0.0 { (max - min) / max } else { 0.0 };
+        (h, s, max, a)
+    }
+
+    /// The OKLab components of a color, as lightness, a, b and alpha.
+    pub fn to_oklab(&self) -> (f64, f64, f64, f64) {
+        let (r, g, b, alpha) = self.as_rgba_in(ColorSpace::LinearSrgb);
+        let [l, a, b] = linear_srgb_to_oklab([r, g, b]);
+        (l, a, b, alpha)
+    }
+
+    /// The CIEL\*a\*b\* polar components of a color, as hue, luminance,
+    /// chrominance and alpha.
+    ///
+    /// This is the inverse of [`hlc`](#method.hlc), with the hue in degrees
+    /// from 0 up to 360.
+    pub fn to_hlc(&self) -> (f64, f64, f64, f64) {
+        let (r, g, b, alpha) = self.as_rgba_in(ColorSpace::LinearSrgb);
+        let [l, a, b] = linear_srgb_to_lab([r, g, b]);
+        let (c, h) = to_polar(a, b);
+        (h, l, c, alpha)
+    }
+
+    /// Interpolate between two colors in a color space.
+    ///
+    /// The result is `self` when `t` is 0.0, and `other` when `t` is 1.0.
+    /// Interpolating two 32 bit colors in sRGB gives a 32 bit color; other
+    /// results are floating point sRGB colors.
+    ///
+    /// ```
+    /// use piet::{Color, InterpolationSpace};
+    ///
+    /// let red = Color::rgb8(0xff, 0, 0);
+    /// let blue = Color::rgb8(0, 0, 0xff);
+    /// let purple = red.lerp(&blue, 0.5, InterpolationSpace::Srgb);
+    /// assert_eq!(purple.as_rgba_u32(), 0x800080ff);
+    /// // Going round the hue circle instead passes through magenta.
+    /// let magenta = red.lerp(&blue, 0.5, InterpolationSpace::Hsl);
+    /// assert_eq!(magenta.as_rgba_u32(), 0xff00ffff);
+    /// ```
+    pub fn lerp(&self, other: &Color, t: f64, space: InterpolationSpace) -> Color {
+        let x = space.coords(self);
+        let y = space.coords(other);
+        let hue = space.hue_index();
+        let mut x_hue = hue.map(|i| x[i]);
+        let mut y_hue = hue.map(|i| y[i]);
+        // The hue of a grey is meaningless, so it takes the other one.
+        if space.is_powerless(&x) {
+            x_hue = y_hue;
+        } else if space.is_powerless(&y) {
+            y_hue = x_hue;
+        }
+        let mix = |a: f64, b: f64| a + t * (b - a);
+        let alpha = mix(x[3], y[3]);
+        let mut out = [0.0, 0.0, 0.0, alpha];
+        for i in 0..3 {
+            out[i] = if hue == Some(i) {
+                let (a, b) = (x_hue.unwrap(), y_hue.unwrap());
+                // Go along the shorter arc.
+                let d = (b - a + 180.0).rem_euclid(360.0) - 180.0;
+                (a + t * d).rem_euclid(360.0)
+            } else if alpha > 0.0 {
+                mix(x[i] * x[3], y[i] * y[3]) / alpha
+            } else {
+                mix(x[i], y[i])
+            };
+        }
+        match (space, self, other) {
+            (InterpolationSpace::Srgb, Color::Rgba32(_), Color::Rgba32(_)) => {
+                Color::rgba(out[0], out[1], out[2], out[3])
+            }
+            _ => space.color(out),
+        }
     }
 
     /// Change just the alpha value of a color.
     ///
     /// The `a` value represents alpha in the range 0.0 to 1.0.
     pub fn with_alpha(self, a: impl Into<f64>) -> Color {
-        let a = (a.into().max(0.0).min(1.0) * 255.0).round() as u32;
-        Color::from_rgba32_u32((self.as_rgba_u32() & !0xff) | a)
+        let a = a.into().max(0.0).min(1.0);
+        match self {
+            Color::Rgba32(rgba) => {
+                let a = (a * 255.0).round() as u32;
+                Color::from_rgba32_u32((rgba & !0xff) | a)
+            }
+            Color::Float { space, r, g, b, .. } => Color::Float { space, r, g, b, a },
+        }
     }
 
     /// Convert a color value to a 32-bit rgba value.
+    ///
+    /// This is lossy for floating point colors, which are clipped to the
+    /// sRGB gamut and quantized to 8 bits per sample.
     pub fn as_rgba_u32(&self) -> u32 {
         match *self {
             Color::Rgba32(rgba) => rgba,
+            Color::Float { .. } => {
+                let (r, g, b, a) = self.as_rgba();
+                let byte = |x: f64| (x * 255.0).round() as u32;
+                (byte(r) << 24) | (byte(g) << 16) | (byte(b) << 8) | byte(a)
+            }
+        }
+    }
+
+    /// The sRGB components of a color, as red, green, blue and alpha.
+    ///
+    /// The values are in the range 0.0 to 1.0, with colors outside the sRGB
+    /// gamut clipped to it. This is for back-ends that take floating point
+    /// sRGB values, without the quantization of `as_rgba_u32`.
+    pub fn as_rgba(&self) -> (f64, f64, f64, f64) {
+        let (r, g, b, a) = self.as_rgba_in(ColorSpace::Srgb);
+        let clamp = |x: f64| x.clamp(0.0, 1.0);
+        (clamp(r), clamp(g), clamp(b), clamp(a))
+    }
+
+    /// The components of a color in a color space, as red, green, blue and alpha.
+    ///
+    /// Colors outside the gamut of `space` have components outside the range
+    /// 0.0 to 1.0.
+    pub fn as_rgba_in(&self, space: ColorSpace) -> (f64, f64, f64, f64) {
+        let (from, r, g, b, a) = match *self {
+            Color::Rgba32(rgba) => {
+                let frac = |shift: u32| ((rgba >> shift) & 0xff) as f64 * (1.0 / 255.0);
+                (ColorSpace::Srgb, frac(24), frac(16), frac(8), frac(0))
+            }
+            Color::Float { space, r, g, b, a } => (space, r, g, b, a),
+        };
+        if from == space {
+            return (r, g, b, a);
+        }
+        let [r, g, b] = from.to_linear_srgb([r, g, b]);
+        let [r, g, b] = space.encode_linear_srgb([r, g, b]);
+        (r, g, b, a)
+    }
+
+    /// Convert to a floating point sRGB color, which may be out of gamut.
+    fn to_srgb(&self) -> Color {
+        let (r, g, b, a) = self.as_rgba_in(ColorSpace::Srgb);
+        Color::rgba_in(ColorSpace::Srgb, r, g, b, a)
+    }
+
+    /// The color space of a color.
+    ///
+    /// This is `ColorSpace::Srgb` for 32 bit colors.
+    pub fn space(&self) -> ColorSpace {
+        match *self {
+            Color::Rgba32(_) => ColorSpace::Srgb,
+            Color::Float { space, .. } => space,
         }
     }
 
@@ -177,3 +429,413 @@ impl Color {
     /// Opaque black.
     pub const BLACK: Color = Color::rgb8(0, 0, 0);
 }
+
+impl fmt::Display for Color {
+    /// Format the color as a CSS color.
+    ///
+    /// 32 bit colors are written in hex, and floating point colors with the
+    /// `color()` function, which keeps their color space and precision.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Color::Rgba32(rgba) if rgba & 0xff == 0xff => write!(f, "#{:06x}", rgba >> 8),
+            Color::Rgba32(rgba) => write!(f, "#{:08x}", rgba),
+            Color::Float { space, r, g, b, a } => {
+                write!(f, "color({} {} {} {}", space.css_name(), r, g, b)?;
+                if a < 1.0 {
+                    write!(f, " / {}", a)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl InterpolationSpace {
+    /// The coordinates of a color in the space, with alpha last.
+    fn coords(self, color: &Color) -> [f64; 4] {
+        let (x, y, z, a) = match self {
+            InterpolationSpace::Srgb => color.as_rgba_in(ColorSpace::Srgb),
+            InterpolationSpace::LinearSrgb => color.as_rgba_in(ColorSpace::LinearSrgb),
+            InterpolationSpace::Hsl => color.to_hsl(),
+            InterpolationSpace::Hsv => color.to_hsv(),
+            InterpolationSpace::Lch => {
+                let (h, l, c, a) = color.to_hlc();
+                (l, c, h, a)
+            }
+            InterpolationSpace::OkLab => color.to_oklab(),
+            InterpolationSpace::OkLch => {
+                let (l, a, b, alpha) = color.to_oklab();
+                let (c, h) = to_polar(a, b);
+                (l, c, h, alpha)
+            }
+        };
+        [x, y, z, a]
+    }
+
+    /// The color with coordinates in the space.
+    fn color(self, [x, y, z, a]: [f64; 4]) -> Color {
+        let linear = match self {
+            InterpolationSpace::Srgb => return Color::rgba_in(ColorSpace::Srgb, x, y, z, a),
+            InterpolationSpace::LinearSrgb => [x, y, z],
+            InterpolationSpace::Hsl => {
+                let [r, g, b] = hsl_to_srgb(x, y, z);
+                return Color::rgba_in(ColorSpace::Srgb, r, g, b, a);
+            }
+            InterpolationSpace::Hsv => {
+                let [r, g, b] = hsv_to_srgb(x, y, z);
+                return Color::rgba_in(ColorSpace::Srgb, r, g, b, a);
+            }
+            InterpolationSpace::Lch => {
+                let (a, b) = from_polar(y, z);
+                lab_to_linear_srgb(x, a, b)
+            }
+            InterpolationSpace::OkLab => oklab_to_linear_srgb(x, y, z),
+            InterpolationSpace::OkLch => {
+                let (a, b) = from_polar(y, z);
+                oklab_to_linear_srgb(x, a, b)
+            }
+        };
+        Color::rgba_in(ColorSpace::LinearSrgb, linear[0], linear[1], linear[2], a).to_srgb()
+    }
+
+    /// The index of the hue coordinate, for polar spaces.
+    fn hue_index(self) -> Option<usize> {
+        match self {
+            InterpolationSpace::Hsl | InterpolationSpace::Hsv => Some(0),
+            InterpolationSpace::Lch | InterpolationSpace::OkLch => Some(2),
+            _ => None,
+        }
+    }
+
+    /// Whether the hue of coordinates is meaningless, as for greys.
+    fn is_powerless(self, coords: &[f64; 4]) -> bool {
+        match self {
+            InterpolationSpace::Hsl | InterpolationSpace::Hsv => coords[1] <= 0.0,
+            // These have some error for greys from the conversion matrices.
+            InterpolationSpace::Lch => coords[1] < 0.02,
+            InterpolationSpace::OkLch => coords[1] < 1e-4,
+            _ => false,
+        }
+    }
+}
+
+impl ColorSpace {
+    /// The name of the space in the CSS `color()` function.
+    pub(crate) fn css_name(self) -> &'static str {
+        match self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::LinearSrgb => "srgb-linear",
+            ColorSpace::DisplayP3 => "display-p3",
+        }
+    }
+
+    /// Convert components in this space to linear sRGB.
+    #[allow(clippy::unreadable_literal)]
+    pub(crate) fn to_linear_srgb(self, [r, g, b]: [f64; 3]) -> [f64; 3] {
+        match self {
+            ColorSpace::Srgb => [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b)],
+            ColorSpace::LinearSrgb => [r, g, b],
+            ColorSpace::DisplayP3 => {
+                let (r, g, b) = (srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b));
+                // The conversion between the primaries, through CIE XYZ. See
+                // https://www.w3.org/TR/css-color-4/#color-conversion-code
+                [
+                    1.2249401762805598 * r - 0.22494017628055996 * g,
+                    -0.04205695470968816 * r + 1.0420569547096882 * g,
+                    -0.019637554590334432 * r - 0.07863604555063176 * g + 1.0982736001409663 * b,
+                ]
+            }
+        }
+    }
+
+    /// Convert components in linear sRGB to this space.
+    #[allow(clippy::unreadable_literal)]
+    pub(crate) fn encode_linear_srgb(self, [r, g, b]: [f64; 3]) -> [f64; 3] {
+        match self {
+            ColorSpace::Srgb => [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b)],
+            ColorSpace::LinearSrgb => [r, g, b],
+            ColorSpace::DisplayP3 => {
+                let p3 = [
+                    0.8224619687143615 * r + 0.17753803128563775 * g,
+                    0.033194199771074166 * r + 0.9668058002289258 * g,
+                    0.017082630825005703 * r + 0.07239740278691985 * g + 0.9105199663880945 * b,
+                ];
+                [
+                    linear_to_srgb(p3[0]),
+                    linear_to_srgb(p3[1]),
+                    linear_to_srgb(p3[2]),
+                ]
+            }
+        }
+    }
+}
+
+/// Convert CIEL\*a\*b\* values, relative to a D50 white, to linear sRGB.
+#[allow(non_snake_case)]
+#[allow(clippy::unreadable_literal)]
+pub(crate) fn lab_to_linear_srgb(L: f64, a: f64, b: f64) -> [f64; 3] {
+    // The reverse transformation from Lab to XYZ, see
+    // https://en.wikipedia.org/wiki/CIELAB_color_space
+    fn f_inv(t: f64) -> f64 {
+        let d = 6. / 29.;
+        if t > d {
+            t.powi(3)
+        } else {
+            3. * d * d * (t - 4. / 29.)
+        }
+    }
+    let ll = (L + 16.) * (1. / 116.);
+    // Produce raw XYZ values
+    let X = f_inv(ll + a * (1. / 500.));
+    let Y = f_inv(ll);
+    let Z = f_inv(ll - b * (1. / 200.));
+    // This matrix is the concatenation of three sources.
+    // First, the white point is taken to be ICC standard D50, so
+    // the diagonal matrix of [0.9642, 1, 0.8249]. Note that there
+    // is some controversy around this value. However, it matches
+    // the other matrices, thus minimizing chroma error.
+    //
+    // Second, an adaption matrix from D50 to D65. This is the
+    // inverse of the recommended D50 to D65 adaptation matrix
+    // from the W3C sRGB spec:
+    // https://www.w3.org/Graphics/Color/srgb
+    //
+    // Finally, the conversion from XYZ to linear sRGB values,
+    // also taken from the W3C sRGB spec.
+    [
+        3.02172918 * X - 1.61692294 * Y - 0.40480625 * Z,
+        -0.94339358 * X + 1.91584267 * Y + 0.02755094 * Z,
+        0.06945666 * X - 0.22903204 * Y + 1.15957526 * Z,
+    ]
+}
+
+/// Convert linear sRGB to CIEL\*a\*b\* values, relative to a D50 white.
+///
+/// This is the inverse of [`lab_to_linear_srgb`].
+#[allow(non_snake_case)]
+#[allow(clippy::unreadable_literal)]
+fn linear_srgb_to_lab([r, g, b]: [f64; 3]) -> [f64; 3] {
+    fn f(t: f64) -> f64 {
+        let d = 6. / 29.;
+        if t > d * d * d {
+            t.cbrt()
+        } else {
+            t / (3. * d * d) + 4. / 29.
+        }
+    }
+    // The inverse of the matrix in `lab_to_linear_srgb`.
+    let X = 0.45221981 * r + 0.39940039 * g + 0.14837980 * b;
+    let Y = 0.22243847 * r + 0.71694274 * g + 0.06061878 * b;
+    let Z = 0.01684743 * r + 0.11768261 * g + 0.86547007 * b;
+    [
+        116. * f(Y) - 16.,
+        500. * (f(X) - f(Y)),
+        200. * (f(Y) - f(Z)),
+    ]
+}
+
+/// Convert linear sRGB to OKLab values.
+///
+/// See <https://bottosson.github.io/posts/oklab/>.
+#[allow(clippy::unreadable_literal)]
+fn linear_srgb_to_oklab([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+/// Convert OKLab values to linear sRGB.
+///
+/// See <https://bottosson.github.io/posts/oklab/>.
+#[allow(non_snake_case)]
+#[allow(clippy::unreadable_literal)]
+pub(crate) fn oklab_to_linear_srgb(L: f64, a: f64, b: f64) -> [f64; 3] {
+    let l = (L + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (L - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (L - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+}
+
+/// Convert HSL, with the hue in degrees, to sRGB.
+///
+/// See <https://www.w3.org/TR/css-color-4/#hsl-to-rgb>.
+pub(crate) fn hsl_to_srgb(h: f64, s: f64, l: f64) -> [f64; 3] {
+    let h = h.rem_euclid(360.0);
+    let a = s * l.min(1.0 - l);
+    let f = |n: f64| {
+        let k = (n + h / 30.0) % 12.0;
+        l - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    [f(0.0), f(8.0), f(4.0)]
+}
+
+/// Convert HSV, with the hue in degrees, to sRGB.
+fn hsv_to_srgb(h: f64, s: f64, v: f64) -> [f64; 3] {
+    let h = h.rem_euclid(360.0);
+    let f = |n: f64| {
+        let k = (n + h / 60.0) % 6.0;
+        v - v * s * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+    [f(5.0), f(3.0), f(1.0)]
+}
+
+/// The hue of sRGB values in degrees, with their largest and smallest values.
+fn hue_of_srgb(r: f64, g: f64, b: f64) -> (f64, f64, f64) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let d = max - min;
+    let h = if d <= 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / d).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    (h * 60.0, max, min)
+}
+
+/// Convert rectangular coordinates to chroma and a hue in degrees.
+fn to_polar(a: f64, b: f64) -> (f64, f64) {
+    (a.hypot(b), b.atan2(a).to_degrees().rem_euclid(360.0))
+}
+
+/// Convert chroma and a hue in degrees to rectangular coordinates.
+fn from_polar(c: f64, h: f64) -> (f64, f64) {
+    let h = h.to_radians();
+    (c * h.cos(), c * h.sin())
+}
+
+/// The sRGB transfer function, extended to negative values by symmetry.
+#[allow(clippy::unreadable_literal)]
+fn srgb_to_linear(x: f64) -> f64 {
+    let abs = x.abs();
+    if abs <= 0.04045 {
+        x / 12.92
+    } else {
+        x.signum() * ((abs + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// The inverse of the sRGB transfer function, extended to negative values by symmetry.
+#[allow(clippy::unreadable_literal)]
+pub(crate) fn linear_to_srgb(x: f64) -> f64 {
+    let abs = x.abs();
+    if abs <= 0.0031308 {
+        x * 12.92
+    } else {
+        x.signum() * (1.055 * abs.powf(1.0 / 2.4) - 0.055)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Colors in the sRGB gamut, with separate alpha.
+    fn samples() -> Vec<[f64; 4]> {
+        vec![
+            [1.0, 0.0, 0.0, 1.0],
+            [0.2, 0.6, 0.4, 1.0],
+            [0.9, 0.85, 0.1, 0.5],
+            [0.05, 0.1, 0.8, 0.25],
+            [0.5, 0.5, 0.5, 1.0],
+            [0.0, 0.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0, 0.0],
+        ]
+    }
+
+    fn assert_close(a: [f64; 4], b: [f64; 4], tolerance: f64) {
+        for (x, y) in a.iter().zip(&b) {
+            assert!((x - y).abs() < tolerance, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn srgb(color: &Color) -> [f64; 4] {
+        let (r, g, b, a) = color.as_rgba_in(ColorSpace::Srgb);
+        [r, g, b, a]
+    }
+
+    #[test]
+    fn test_round_trips() {
+        for [r, g, b, a] in samples() {
+            let color = Color::rgba_in(ColorSpace::Srgb, r, g, b, a);
+
+            let (h, s, l, alpha) = color.to_hsl();
+            let [r1, g1, b1] = hsl_to_srgb(h, s, l);
+            assert_close([r1, g1, b1, alpha], [r, g, b, a], 1e-9);
+
+            let (h, s, v, alpha) = color.to_hsv();
+            let [r1, g1, b1] = hsv_to_srgb(h, s, v);
+            assert_close([r1, g1, b1, alpha], [r, g, b, a], 1e-9);
+
+            let (l, a1, b1, alpha) = color.to_oklab();
+            assert_close(srgb(&Color::oklaba(l, a1, b1, alpha)), [r, g, b, a], 1e-6);
+
+            let (h, l, c, alpha) = color.to_hlc();
+            let (a1, b1) = from_polar(c, h);
+            let linear = lab_to_linear_srgb(l, a1, b1);
+            let [r1, g1, b1] = ColorSpace::Srgb.encode_linear_srgb(linear);
+            assert_close([r1, g1, b1, alpha], [r, g, b, a], 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_lerp_hue() {
+        // From a hue of 340 to one of 20, the short way is through red.
+        let a = Color::rgb_in(ColorSpace::Srgb, 1.0, 0.0, 1.0 / 3.0);
+        let b = Color::rgb_in(ColorSpace::Srgb, 1.0, 1.0 / 3.0, 0.0);
+        for &space in &[InterpolationSpace::Hsl, InterpolationSpace::Hsv] {
+            let mid = a.lerp(&b, 0.5, space);
+            assert_close(srgb(&mid), [1.0, 0.0, 0.0, 1.0], 1e-9);
+            let mid = b.lerp(&a, 0.5, space);
+            assert_close(srgb(&mid), [1.0, 0.0, 0.0, 1.0], 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_lerp_powerless_hue() {
+        // The hue of grey is powerless, so a ramp from grey to blue keeps
+        // the hue of blue rather than passing through other hues.
+        let grey = Color::rgb_in(ColorSpace::Srgb, 0.5, 0.5, 0.5);
+        let blue = Color::rgb_in(ColorSpace::Srgb, 0.0, 0.0, 1.0);
+        let mid = grey.lerp(&blue, 0.5, InterpolationSpace::Hsl);
+        assert!((mid.to_hsl().0 - 240.0).abs() < 1e-9);
+        let mid = blue.lerp(&grey, 0.5, InterpolationSpace::Hsv);
+        assert!((mid.to_hsv().0 - 240.0).abs() < 1e-9);
+
+        let white = Color::rgb_in(ColorSpace::Srgb, 1.0, 1.0, 1.0);
+        let [_, _, blue_hue, _] = InterpolationSpace::OkLch.coords(&blue);
+        let mid = white.lerp(&blue, 0.5, InterpolationSpace::OkLch);
+        let [_, _, hue, _] = InterpolationSpace::OkLch.coords(&mid);
+        assert!((hue - blue_hue).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_lerp_premultiplied() {
+        // A transparent color contributes nothing but its alpha.
+        let red = Color::rgb8(0xff, 0, 0);
+        let clear_blue = Color::rgba8(0, 0, 0xff, 0);
+        let mid = red.lerp(&clear_blue, 0.5, InterpolationSpace::Srgb);
+        assert_eq!(mid.as_rgba_u32(), 0xff000080);
+
+        let red = Color::rgb_in(ColorSpace::Srgb, 1.0, 0.0, 0.0);
+        let clear_blue = Color::rgba_in(ColorSpace::Srgb, 0.0, 0.0, 1.0, 0.0);
+        for &space in &[InterpolationSpace::LinearSrgb, InterpolationSpace::OkLab] {
+            let mid = red.lerp(&clear_blue, 0.25, space);
+            assert_close(srgb(&mid), [1.0, 0.0, 0.0, 0.75], 1e-6);
+        }
+        let both_clear = clear_blue.lerp(&clear_blue, 0.5, InterpolationSpace::Srgb);
+        assert_close(srgb(&both_clear), [0.0, 0.0, 1.0, 0.0], 1e-9);
+    }
+}
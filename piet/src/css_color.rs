@@ -0,0 +1,531 @@
+//! Parsing colors from CSS syntax.
+
+use std::str::FromStr;
+
+use crate::color::{hsl_to_srgb, lab_to_linear_srgb, oklab_to_linear_srgb};
+use crate::{new_error, Color, ColorSpace, Error, ErrorKind};
+
+impl FromStr for Color {
+    type Err = Error;
+
+    /// Parse a color in the syntax of [CSS Color Level 4].
+    ///
+    /// This accepts hex colors, named colors, and the `rgb()`, `rgba()`,
+    /// `hsl()`, `hsla()`, `hwb()`, `lab()`, `lch()`, `oklab()`, `oklch()` and
+    /// `color()` functions. Colors given in sRGB syntax are quantized to 32
+    /// bits, like [`Color::rgba`]; the others keep floating point precision,
+    /// and may be outside the sRGB gamut.
+    ///
+    /// ```
+    /// use piet::Color;
+    ///
+    /// let color: Color = "rgb(12 40 200 / 50%)".parse().unwrap();
+    /// assert_eq!(color.as_rgba_u32(), 0x0c28c880);
+    /// let color: Color = "rebeccapurple".parse().unwrap();
+    /// assert_eq!(color.to_string(), "#663399");
+    /// ```
+    ///
+    /// [CSS Color Level 4]: https://www.w3.org/TR/css-color-4/
+    /// [`Color::rgba`]: enum.Color.html#method.rgba
+    fn from_str(s: &str) -> Result<Color, Error> {
+        parse_color(s).ok_or_else(|| new_error(ErrorKind::InvalidInput))
+    }
+}
+
+/// A component of a color function.
+#[derive(Clone, Copy)]
+enum Component {
+    Number(f64),
+    Percent(f64),
+    /// An angle, in degrees.
+    Angle(f64),
+    /// The `none` keyword, a missing component, which is treated as zero.
+    None,
+}
+
+fn parse_color(s: &str) -> Option<Color> {
+    let s = s.trim().to_ascii_lowercase();
+    if let Some(hex) = s.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+    let open = match s.find('(') {
+        Some(open) => open,
+        None => return named_color(&s).map(Color::from_rgba32_u32),
+    };
+    let name = &s[..open];
+    let body = s[open + 1..].strip_suffix(')')?;
+    let (space, args, alpha) = parse_args(name, body)?;
+    let alpha = match alpha {
+        Some(alpha) => percent_or_number(alpha, 1.0)?.clamp(0.0, 1.0),
+        None => 1.0,
+    };
+    if name == "color" {
+        let space = match space? {
+            "srgb" => ColorSpace::Srgb,
+            "srgb-linear" => ColorSpace::LinearSrgb,
+            "display-p3" => ColorSpace::DisplayP3,
+            _ => return None,
+        };
+        let r = percent_or_number(args[0], 1.0)?;
+        let g = percent_or_number(args[1], 1.0)?;
+        let b = percent_or_number(args[2], 1.0)?;
+        return Some(Color::rgba_in(space, r, g, b, alpha));
+    }
+    match name {
+        "rgb" | "rgba" => {
+            let r = percent_or_number(args[0], 255.0)? / 255.0;
+            let g = percent_or_number(args[1], 255.0)? / 255.0;
+            let b = percent_or_number(args[2], 255.0)? / 255.0;
+            Some(Color::rgba(r, g, b, alpha))
+        }
+        "hsl" | "hsla" => {
+            let h = hue(args[0])?;
+            let s = percent_or_number(args[1], 100.0)? / 100.0;
+            let l = percent_or_number(args[2], 100.0)? / 100.0;
+            let [r, g, b] = hsl_to_srgb(h, s, l);
+            Some(Color::rgba(r, g, b, alpha))
+        }
+        "hwb" => {
+            let h = hue(args[0])?;
+            let w = percent_or_number(args[1], 100.0)? / 100.0;
+            let b = percent_or_number(args[2], 100.0)? / 100.0;
+            let [r, g, b] = hwb_to_srgb(h, w, b);
+            Some(Color::rgba(r, g, b, alpha))
+        }
+        "lab" => {
+            let l = percent_or_number(args[0], 100.0)?;
+            let a = percent_or_number(args[1], 125.0)?;
+            let b = percent_or_number(args[2], 125.0)?;
+            Some(from_linear_srgb(lab_to_linear_srgb(l, a, b), alpha))
+        }
+        "lch" => {
+            let l = percent_or_number(args[0], 100.0)?;
+            let c = percent_or_number(args[1], 150.0)?;
+            let (a, b) = polar(c, hue(args[2])?);
+            Some(from_linear_srgb(lab_to_linear_srgb(l, a, b), alpha))
+        }
+        "oklab" => {
+            let l = percent_or_number(args[0], 1.0)?;
+            let a = percent_or_number(args[1], 0.4)?;
+            let b = percent_or_number(args[2], 0.4)?;
+            Some(from_linear_srgb(oklab_to_linear_srgb(l, a, b), alpha))
+        }
+        "oklch" => {
+            let l = percent_or_number(args[0], 1.0)?;
+            let c = percent_or_number(args[1], 0.4)?;
+            let (a, b) = polar(c, hue(args[2])?);
+            Some(from_linear_srgb(oklab_to_linear_srgb(l, a, b), alpha))
+        }
+        _ => None,
+    }
+}
+
+/// Parse `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`, without the `#`.
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let x = u32::from_str_radix(hex, 16).ok()?;
+    // Repeat each digit of the short forms.
+    let expand = |x: u32| {
+        (0..8).fold(0, |acc, i| {
+            let digit = (x >> (i / 2 * 4)) & 0xf;
+            acc | (digit << (i * 4))
+        })
+    };
+    let rgba = match hex.len() {
+        3 => expand((x << 4) | 0xf),
+        4 => expand(x),
+        6 => (x << 8) | 0xff,
+        8 => x,
+        _ => return None,
+    };
+    Some(Color::from_rgba32_u32(rgba))
+}
+
+/// Split the arguments of a color function into an optional leading color
+/// space name, three components, and an optional alpha.
+fn parse_args<'a>(
+    name: &str,
+    body: &'a str,
+) -> Option<(Option<&'a str>, [Component; 3], Option<Component>)> {
+    let mut space = None;
+    let (values, alpha): (Vec<&str>, Option<&str>) = if body.contains(',') {
+        // The legacy syntax, with commas, and without `none`.
+        if !["rgb", "rgba", "hsl", "hsla"].contains(&name) || body.contains("none") {
+            return None;
+        }
+        let mut values: Vec<&str> = body.split(',').map(str::trim).collect();
+        let alpha = if values.len() == 4 {
+            values.pop()
+        } else {
+            None
+        };
+        (values, alpha)
+    } else {
+        let mut parts = body.splitn(2, '/');
+        let mut values: Vec<&str> = parts.next()?.split_whitespace().collect();
+        let alpha = parts.next().map(str::trim);
+        if name == "color" && !values.is_empty() {
+            space = Some(values.remove(0));
+        }
+        (values, alpha)
+    };
+    if values.len() != 3 {
+        return None;
+    }
+    let args = [
+        parse_component(values[0])?,
+        parse_component(values[1])?,
+        parse_component(values[2])?,
+    ];
+    let alpha = match alpha {
+        Some(alpha) => Some(parse_component(alpha)?),
+        None => None,
+    };
+    Some((space, args, alpha))
+}
+
+fn parse_component(s: &str) -> Option<Component> {
+    if s == "none" {
+        return Some(Component::None);
+    }
+    let units = [
+        ("%", 1.0),
+        ("deg", 1.0),
+        ("grad", 0.9),
+        ("rad", 180.0 / std::f64::consts::PI),
+        ("turn", 360.0),
+    ];
+    let (number, unit) = units
+        .iter()
+        .find_map(|&(unit, scale)| Some((s.strip_suffix(unit)?, Some((unit, scale)))))
+        .unwrap_or((s, None));
+    let x: f64 = number.parse().ok().filter(|x: &f64| x.is_finite())?;
+    Some(match unit {
+        None => Component::Number(x),
+        Some(("%", _)) => Component::Percent(x),
+        Some((_, scale)) => Component::Angle(x * scale),
+    })
+}
+
+/// The value of a number, or a percentage of `reference`.
+fn percent_or_number(c: Component, reference: f64) -> Option<f64> {
+    match c {
+        Component::Number(x) => Some(x),
+        Component::Percent(x) => Some(x / 100.0 * reference),
+        Component::None => Some(0.0),
+        Component::Angle(_) => None,
+    }
+}
+
+/// A hue, in degrees.
+fn hue(c: Component) -> Option<f64> {
+    match c {
+        Component::Number(x) | Component::Angle(x) => Some(x),
+        Component::None => Some(0.0),
+        Component::Percent(_) => None,
+    }
+}
+
+fn polar(chroma: f64, hue: f64) -> (f64, f64) {
+    let hue = hue.to_radians();
+    (chroma * hue.cos(), chroma * hue.sin())
+}
+
+fn from_linear_srgb(rgb: [f64; 3], alpha: f64) -> Color {
+    let [r, g, b] = ColorSpace::Srgb.encode_linear_srgb(rgb);
+    Color::rgba_in(ColorSpace::Srgb, r, g, b, alpha)
+}
+
+/// Convert HWB, with the hue in degrees, to sRGB.
+fn hwb_to_srgb(h: f64, w: f64, b: f64) -> [f64; 3] {
+    if w + b >= 1.0 {
+        let grey = w / (w + b);
+        return [grey, grey, grey];
+    }
+    let rgb = hsl_to_srgb(h, 1.0, 0.5);
+    let f = |x: f64| x * (1.0 - w - b) + w;
+    [f(rgb[0]), f(rgb[1]), f(rgb[2])]
+}
+
+/// The 32-bit RGBA value of a CSS named color.
+fn named_color(name: &str) -> Option<u32> {
+    if name == "transparent" {
+        return Some(0);
+    }
+    NAMED_COLORS
+        .binary_search_by_key(&name, |&(name, _)| name)
+        .ok()
+        .map(|i| (NAMED_COLORS[i].1 << 8) | 0xff)
+}
+
+/// The CSS named colors, sorted by name, with their RGB values.
+#[allow(clippy::unreadable_literal)]
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(s: &str) -> Option<u32> {
+        s.parse::<Color>().ok().map(|color| color.as_rgba_u32())
+    }
+
+    /// Whether two colors are the same in sRGB, within rounding error.
+    fn assert_close(a: &str, b: &str) {
+        let a = a.parse::<Color>().unwrap().as_rgba_in(ColorSpace::Srgb);
+        let b = b.parse::<Color>().unwrap().as_rgba_in(ColorSpace::Srgb);
+        let (a, b) = ([a.0, a.1, a.2, a.3], [b.0, b.1, b.2, b.3]);
+        for (x, y) in a.iter().zip(&b) {
+            assert!((x - y).abs() < 1e-9, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(parse("#f80"), Some(0xff8800ff));
+        assert_eq!(parse("#f808"), Some(0xff880088));
+        assert_eq!(parse("#12abEF"), Some(0x12abefff));
+        assert_eq!(parse("#12abef80"), Some(0x12abef80));
+        assert_eq!(parse("#"), None);
+        assert_eq!(parse("#12345"), None);
+        assert_eq!(parse("#123456789"), None);
+        assert_eq!(parse("#1234567890abcdef"), None);
+        assert_eq!(parse("#12g"), None);
+        assert_eq!(parse("#+12"), None);
+    }
+
+    #[test]
+    fn test_syntax() {
+        assert_eq!(parse("rgb(255, 128, 0)"), Some(0xff8000ff));
+        assert_eq!(parse("rgba(255, 128, 0, 0.5)"), Some(0xff800080));
+        assert_eq!(parse("rgb(255 128 0 / 50%)"), Some(0xff800080));
+        assert_eq!(parse("hsl(120, 100%, 50%)"), Some(0x00ff00ff));
+        assert_eq!(parse("hsla(120 100% 50% / 0)"), Some(0x00ff0000));
+        // `none` is only allowed in the modern syntax.
+        assert_eq!(parse("rgb(none 128 0)"), Some(0x008000ff));
+        assert_eq!(parse("rgb(none, 128, 0)"), None);
+        // The syntaxes can't be mixed, and newer functions have no legacy
+        // syntax.
+        assert_eq!(parse("rgb(255, 128 0)"), None);
+        assert_eq!(parse("rgb(255 128 0, 0.5)"), None);
+        assert_eq!(parse("hwb(0, 0%, 0%)"), None);
+        assert_eq!(parse("lab(50, 0, 0)"), None);
+        assert_eq!(parse("rgb(255 128)"), None);
+        assert_eq!(parse("rgb(255 128 0"), None);
+        assert_eq!(parse("nosuchcolor"), None);
+        assert_eq!(parse(" RebeccaPurple "), Some(0x663399ff));
+        assert_eq!(parse("transparent"), Some(0));
+    }
+
+    #[test]
+    fn test_percent_references() {
+        assert_close("lab(50% 40% -40%)", "lab(50 50 -50)");
+        assert_close("lch(50% 20% 30)", "lch(50 30 30)");
+        assert_close("oklab(50% 25% -25%)", "oklab(0.5 0.1 -0.1)");
+        assert_close("oklch(70% 50% 200)", "oklch(0.7 0.2 200)");
+        // A hue can't be a percentage.
+        assert_eq!(parse("lch(50 30 10%)"), None);
+    }
+
+    #[test]
+    fn test_hwb() {
+        assert_eq!(parse("hwb(120 0% 0%)"), Some(0x00ff00ff));
+        assert_eq!(parse("hwb(0 100% 0%)"), Some(0xffffffff));
+        // With whiteness and blackness adding up to 1 or more, the color is
+        // the grey of their ratio.
+        assert_eq!(parse("hwb(120 60% 60%)"), parse("hwb(0 50% 50%)"));
+        assert_eq!(parse("hwb(120 75% 25%)"), parse("rgb(75% 75% 75%)"));
+    }
+
+    #[test]
+    fn test_angles() {
+        let cyan = Some(0x00ffffff);
+        assert_eq!(parse("hsl(180 100% 50%)"), cyan);
+        assert_eq!(parse("hsl(180deg 100% 50%)"), cyan);
+        assert_eq!(parse("hsl(200grad 100% 50%)"), cyan);
+        assert_eq!(parse("hsl(3.141592653589793rad 100% 50%)"), cyan);
+        assert_eq!(parse("hsl(0.5turn 100% 50%)"), cyan);
+        assert_close("oklch(0.7 0.1 0.25turn)", "oklch(0.7 0.1 90)");
+        // Angles are only allowed for hues.
+        assert_eq!(parse("rgb(10deg 0 0)"), None);
+        assert_eq!(parse("hsl(180 100deg 50%)"), None);
+        assert_eq!(parse("lab(50 10deg 0)"), None);
+        assert_eq!(parse("hsl(180furlong 100% 50%)"), None);
+    }
+
+    #[test]
+    fn test_color_function() {
+        let color: Color = "color(display-p3 1 0.5 0 / 0.25)".parse().unwrap();
+        assert!(color == Color::rgba_in(ColorSpace::DisplayP3, 1.0, 0.5, 0.0, 0.25));
+        let color: Color = "color(srgb-linear 50% 0 none)".parse().unwrap();
+        assert!(color == Color::rgb_in(ColorSpace::LinearSrgb, 0.5, 0.0, 0.0));
+        assert_eq!(parse("color(rec2020 1 0 0)"), None);
+        assert_eq!(parse("color(display-p3 1 0)"), None);
+        assert_eq!(parse("color(1 0 0)"), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let colors = [
+            Color::rgb8(0x66, 0x33, 0x99),
+            Color::rgba8(0x12, 0x34, 0x56, 0x78),
+            Color::rgb_in(ColorSpace::DisplayP3, 1.0, 0.25, -0.125),
+            Color::rgba_in(ColorSpace::LinearSrgb, 0.1, 0.2, 0.3, 0.4),
+        ];
+        for color in &colors {
+            let parsed: Color = color.to_string().parse().unwrap();
+            assert!(parsed == *color, "{} didn't round trip", color);
+        }
+    }
+}
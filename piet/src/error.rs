@@ -2,45 +2,160 @@
 
 use std::fmt;
 
+use crate::ImageFormat;
+
 /// An error that can occur while rendering 2D graphics.
+///
+/// An error has an [`ErrorKind`], and optionally a description of the
+/// operation that failed. Errors can be sent between threads.
+///
+/// ```
+/// use piet::{Error, ErrorKind};
+///
+/// let err = Error::new(ErrorKind::InvalidBufferLength { expected: 16, found: 12 })
+///     .with_context("make_image");
+/// assert_eq!(err.context(), Some("make_image"));
+/// assert_eq!(err.to_string(), "make_image: Invalid buffer length: expected 16 bytes, found 12");
+///
+/// fn assert_send_sync<T: Send + Sync>() {}
+/// assert_send_sync::<Error>();
+/// ```
+///
+/// [`ErrorKind`]: enum.ErrorKind.html
+#[derive(Debug)]
+pub struct Error(Box<ErrorImpl>);
+
 #[derive(Debug)]
-pub struct Error(Box<ErrorKind>);
+struct ErrorImpl {
+    kind: ErrorKind,
+    context: Option<String>,
+}
 
 #[derive(Debug)]
 pub enum ErrorKind {
     InvalidInput,
     NotSupported,
     StackUnbalance,
-    BackendError(Box<dyn std::error::Error>),
+    BackendError(Box<dyn std::error::Error + Send + Sync>),
+    MissingFeature,
+    /// No font with the given family name is available.
+    FontNotFound(String),
+    /// Font data can't be used, for the given reason.
+    InvalidFont(String),
+    /// A file couldn't be read.
+    Io(std::io::Error),
+    /// The width or height of an image is too large, or otherwise unusable.
+    InvalidImageSize {
+        width: usize,
+        height: usize,
+    },
+    /// A pixel buffer has the wrong number of bytes for its image.
+    InvalidBufferLength {
+        expected: usize,
+        found: usize,
+    },
+    /// A gradient can't be drawn, for the given reason.
+    InvalidGradient(String),
+    /// The image format isn't supported by the operation.
+    UnsupportedFormat(ImageFormat),
     #[doc(hidden)]
     _NonExhaustive,
-    MissingFeature,
 }
 
 /// Create a new error of the given kind.
 pub fn new_error(kind: ErrorKind) -> Error {
-    Error(Box::new(kind))
+    Error::new(kind)
+}
+
+impl Error {
+    /// Create a new error of the given kind.
+    pub fn new(kind: ErrorKind) -> Error {
+        Error(Box::new(ErrorImpl {
+            kind,
+            context: None,
+        }))
+    }
+
+    /// Describe the operation that failed.
+    ///
+    /// If the error already has a context, the new one is put in front of it.
+    pub fn with_context(mut self, context: impl Into<String>) -> Error {
+        let context = context.into();
+        self.0.context = Some(match self.0.context.take() {
+            Some(inner) => format!("{}: {}", context, inner),
+            None => context,
+        });
+        self
+    }
+
+    /// The kind of error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.0.kind
+    }
+
+    /// The operation that failed, if known.
+    pub fn context(&self) -> Option<&str> {
+        self.0.context.as_deref()
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self.0 {
+        if let Some(context) = &self.0.context {
+            write!(f, "{}: ", context)?;
+        }
+        self.0.kind.fmt(f)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
             ErrorKind::InvalidInput => write!(f, "Invalid input"),
             ErrorKind::NotSupported => write!(f, "Option not supported"),
             ErrorKind::StackUnbalance => write!(f, "Stack unbalanced"),
-            ErrorKind::BackendError(ref e) => {
-                write!(f, "Backend error: ")?;
-                e.fmt(f)
+            // The errors these wrap are their `source`, so they aren't
+            // repeated here.
+            ErrorKind::BackendError(_) => write!(f, "Backend error"),
+            ErrorKind::MissingFeature => write!(f, "Missing feature"),
+            ErrorKind::FontNotFound(family) => write!(f, "Font not found: {}", family),
+            ErrorKind::InvalidFont(reason) => write!(f, "Invalid font: {}", reason),
+            ErrorKind::Io(_) => write!(f, "I/O error"),
+            ErrorKind::InvalidImageSize { width, height } => {
+                write!(f, "Invalid image size: {}x{}", width, height)
+            }
+            ErrorKind::InvalidBufferLength { expected, found } => write!(
+                f,
+                "Invalid buffer length: expected {} bytes, found {}",
+                expected, found
+            ),
+            ErrorKind::InvalidGradient(reason) => write!(f, "Invalid gradient: {}", reason),
+            ErrorKind::UnsupportedFormat(format) => {
+                write!(f, "Unsupported image format: {:?}", format)
             }
             _ => write!(f, "Unknown piet error (case not covered)"),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.0.kind {
+            ErrorKind::BackendError(e) => Some(e.as_ref()),
+            ErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error::new(kind)
+    }
+}
 
-impl From<Box<dyn std::error::Error>> for Error {
-    fn from(e: Box<dyn std::error::Error>) -> Error {
+impl From<Box<dyn std::error::Error + Send + Sync>> for Error {
+    fn from(e: Box<dyn std::error::Error + Send + Sync>) -> Error {
         new_error(ErrorKind::BackendError(e))
     }
 }
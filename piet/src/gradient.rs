@@ -1,14 +1,14 @@
 //! Gradient specifications.
 //!
-//! We provide both linear and radial gradients; and for each flavor
+//! We provide linear, radial and sweep gradients; and for each flavor
 //! we provide two representations, a 'generic' representation that uses
 //! points in the [unit square], and a 'fixed' representation that uses
 //! image-space coordinates.
 //!
-//! The generic representations ([`LinearGradient`] and [`RadialGradient`])
-//! are useful for cases such as UI, when the same gradient may be reused
-//! with different shapes. The fixed representations
-//! ([`FixedLinearGradient`] and [`FixedRadialGradient`]) may be better suited
+//! The generic representations ([`LinearGradient`], [`RadialGradient`] and
+//! [`SweepGradient`]) are useful for cases such as UI, when the same gradient may be reused
+//! with different shapes. The fixed representations ([`FixedLinearGradient`],
+//! [`FixedRadialGradient`] and [`FixedSweepGradient`]) may be better suited
 //! to working with content in existing formats such as SVG. A fixed gradient
 //! can be generated from a generic gradient by mapping points from the unit
 //! square onto any arbitrary rectangle.
@@ -20,17 +20,19 @@
 //!
 //! [`LinearGradient`]: struct.LinearGradient.html
 //! [`RadialGradient`]: struct.RadialGradient.html
+//! [`SweepGradient`]: struct.SweepGradient.html
 //! [`FixedLinearGradient`]: struct.FixedLinearGradient.html
 //! [`FixedRadialGradient`]: struct.FixedRadialGradient.html
+//! [`FixedSweepGradient`]: struct.FixedSweepGradient.html
 //! [unit square]: https://en.wikipedia.org/wiki/Unit_square
 
 use std::borrow::Cow;
 
 use kurbo::{Point, Rect, Size, Vec2};
 
-use crate::{IntoBrush, RenderContext};
+use crate::{new_error, Error, ErrorKind, IntoBrush, RenderContext};
 
-use crate::Color;
+use crate::{Color, ColorSpace, InterpolationSpace};
 
 /// Specification of a linear gradient.
 ///
@@ -49,6 +51,8 @@ pub struct FixedLinearGradient {
     ///
     /// There must be at least two for the gradient to be valid.
     pub stops: Vec<GradientStop>,
+    /// How the gradient continues beyond the end points.
+    pub extend: ExtendMode,
 }
 
 /// Specification of a radial gradient in image-space.
@@ -70,23 +74,64 @@ pub struct FixedRadialGradient {
     pub radius: f64,
     /// The stops (see similar field in [`LinearGradient`](struct.LinearGradient.html)).
     pub stops: Vec<GradientStop>,
+    /// How the gradient continues beyond the circle.
+    pub extend: ExtendMode,
+}
+
+/// Specification of a sweep gradient in image-space.
+///
+/// A sweep (or conic) gradient varies in color with the angle around its
+/// center. Angles are in radians, and increase clockwise from the positive
+/// x axis, as `y` points down. Outside the range from `start_angle` to
+/// `end_angle`, the colors of the end stops are used.
+///
+/// This specification is in terms of image-space coordinates. In many
+/// cases, it is better to specify coordinates relative to the `Rect`
+/// of the item being drawn; for these, use [`SweepGradient`] instead.
+///
+/// [`SweepGradient`]: struct.SweepGradient.html
+#[derive(Debug, Clone)]
+pub struct FixedSweepGradient {
+    /// The center.
+    pub center: Point,
+    /// The angle corresponding to pos 0.0.
+    pub start_angle: f64,
+    /// The angle corresponding to pos 1.0.
+    pub end_angle: f64,
+    /// The stops (see similar field in [`LinearGradient`](struct.LinearGradient.html)).
+    pub stops: Vec<GradientStop>,
 }
 
 /// Any fixed gradient.
 ///
 /// This is provided as a convenience, so that we can provide API that
-/// accept both [`FixedLinearGradient`] and [`FixedRadialGradient`].
-/// You should not construct this type dirctly; rather construct one of those
-/// types, both of which impl `Into<FixedGradient>`.
+/// accept any of [`FixedLinearGradient`], [`FixedRadialGradient`] and
+/// [`FixedSweepGradient`]. You should not construct this type dirctly; rather
+/// construct one of those types, all of which impl `Into<FixedGradient>`.
 ///
 /// [`FixedLinearGradient`]: struct.FixedLinearGradient.html
 /// [`FixedRadialGradient`]: struct.FixedRadialGradient.html
+/// [`FixedSweepGradient`]: struct.FixedSweepGradient.html
 #[derive(Debug, Clone)]
 pub enum FixedGradient {
     /// A linear gradient.
     Linear(FixedLinearGradient),
     /// A radial gradient.
     Radial(FixedRadialGradient),
+    /// A sweep gradient.
+    Sweep(FixedSweepGradient),
+}
+
+/// How a gradient is extended beyond its end stops.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ExtendMode {
+    /// The colors of the end stops continue indefinitely.
+    #[default]
+    Pad,
+    /// The gradient repeats, starting again from pos 0.0 after pos 1.0.
+    Repeat,
+    /// The gradient repeats, alternating between forward and reversed.
+    Reflect,
 }
 
 /// Specification of a gradient stop.
@@ -101,6 +146,37 @@ pub struct GradientStop {
 /// A flexible, ergonomic way to describe gradient stops.
 pub trait GradientStops {
     fn to_vec(self) -> Vec<GradientStop>;
+
+    /// Interpolate between the stops in a color space other than sRGB.
+    ///
+    /// Back-ends interpolate gradients in sRGB, so this adds stops in between
+    /// the given ones wherever the ramp would otherwise differ visibly.
+    ///
+    /// ```
+    /// use piet::{Color, GradientStops, InterpolationSpace};
+    ///
+    /// let stops = (Color::rgb8(0xff, 0, 0), Color::rgb8(0, 0, 0xff))
+    ///     .interpolated(InterpolationSpace::OkLab)
+    ///     .to_vec();
+    /// assert!(stops.len() > 2);
+    /// ```
+    fn interpolated(self, space: InterpolationSpace) -> InterpolatedStops<Self>
+    where
+        Self: Sized,
+    {
+        InterpolatedStops { stops: self, space }
+    }
+}
+
+/// Gradient stops interpolated in a color space.
+///
+/// This is created by [`GradientStops::interpolated`].
+///
+/// [`GradientStops::interpolated`]: trait.GradientStops.html#method.interpolated
+#[derive(Debug, Clone)]
+pub struct InterpolatedStops<S> {
+    stops: S,
+    space: InterpolationSpace,
 }
 
 /// A description of a linear gradient in the unit rect, which can be resolved
@@ -118,6 +194,7 @@ pub struct LinearGradient {
     start: UnitPoint,
     end: UnitPoint,
     stops: Vec<GradientStop>,
+    extend: ExtendMode,
 }
 
 /// A description of a radial gradient in the unit rect, which can be resolved
@@ -141,12 +218,16 @@ pub struct LinearGradient {
 /// rectangle; by default this will expand on the longest axis, but this can
 /// be changed with the [`with_scale_mode`] builder method.
 ///
+/// Beyond the radius, the end color is used; this can be changed with the
+/// [`with_extend`] builder method.
+///
 /// [config]: https://docs.microsoft.com/en-us/windows/win32/direct2d/direct2d-brushes-overview#configuring-a-radial-gradient
 /// [`UnitPoint`]: struct.UnitPoint.html
 /// [`ScaleMode`]: enum.ScaleMode.html
 /// [`with_center`]: struct.RadialGradient.html#method.with_center
 /// [`with_origin`]: struct.RadialGradient.html#method.with_origin
 /// [`with_scale_mode`]: struct.RadialGradient.html#method.with_scale_mode
+/// [`with_extend`]: struct.RadialGradient.html#method.with_extend
 #[derive(Debug, Clone)]
 pub struct RadialGradient {
     center: UnitPoint,
@@ -154,6 +235,26 @@ pub struct RadialGradient {
     radius: f64,
     stops: Vec<GradientStop>,
     scale_mode: ScaleMode,
+    extend: ExtendMode,
+}
+
+/// A description of a sweep gradient in the unit rect, which can be resolved
+/// to a fixed gradient.
+///
+/// The `center` is given in [`UnitPoint`] coordinates, and is (0.5, 0.5) by
+/// default; this can be changed with the [`with_center`] builder method.
+/// The angles are the same as for [`FixedSweepGradient`], and are not affected
+/// by the aspect ratio of the rectangle.
+///
+/// [`UnitPoint`]: struct.UnitPoint.html
+/// [`with_center`]: struct.SweepGradient.html#method.with_center
+/// [`FixedSweepGradient`]: struct.FixedSweepGradient.html
+#[derive(Debug, Clone)]
+pub struct SweepGradient {
+    center: UnitPoint,
+    start_angle: f64,
+    end_angle: f64,
+    stops: Vec<GradientStop>,
 }
 
 /// Mappings from the unit square into a non-square rectangle.
@@ -204,6 +305,12 @@ impl<'a> GradientStops for &'a [Color] {
     }
 }
 
+impl<S: GradientStops> GradientStops for InterpolatedStops<S> {
+    fn to_vec(self) -> Vec<GradientStop> {
+        expand_stops(self.stops.to_vec(), self.space)
+    }
+}
+
 impl<'a> GradientStops for (Color, Color) {
     fn to_vec(self) -> Vec<GradientStop> {
         let stops: &[Color] = &[self.0, self.1];
@@ -295,9 +402,31 @@ impl LinearGradient {
             start,
             end,
             stops: stops.to_vec(),
+            extend: ExtendMode::Pad,
         }
     }
 
+    /// A builder-style method for changing how the gradient continues beyond
+    /// its end points.
+    ///
+    /// By default the end colors are padded out; see [`ExtendMode`].
+    ///
+    /// [`ExtendMode`]: enum.ExtendMode.html
+    pub fn with_extend(mut self, extend: ExtendMode) -> Self {
+        self.extend = extend;
+        self
+    }
+
+    /// A builder-style method for interpolating the gradient in a color space.
+    ///
+    /// See [`GradientStops::interpolated`].
+    ///
+    /// [`GradientStops::interpolated`]: trait.GradientStops.html#method.interpolated
+    pub fn with_interpolation(mut self, space: InterpolationSpace) -> Self {
+        self.stops = expand_stops(self.stops, space);
+        self
+    }
+
     // maybe these should be public API? that was my original intention but I'm not
     // sure there's a clear use, so keeping them private for now.
     /// Generate a [`FixedLinearGradient`] by mapping points in the unit square
@@ -309,6 +438,7 @@ impl LinearGradient {
             start: self.start.resolve(rect),
             end: self.end.resolve(rect),
             stops: self.stops.clone(),
+            extend: self.extend,
         }
     }
 }
@@ -330,6 +460,7 @@ impl RadialGradient {
             radius,
             stops: stops.to_vec(),
             scale_mode: ScaleMode::Fill,
+            extend: ExtendMode::Pad,
         }
     }
 
@@ -363,6 +494,27 @@ impl RadialGradient {
         self
     }
 
+    /// A builder-style method for changing how the gradient continues beyond
+    /// its radius.
+    ///
+    /// By default the end color is padded out; see [`ExtendMode`].
+    ///
+    /// [`ExtendMode`]: enum.ExtendMode.html
+    pub fn with_extend(mut self, extend: ExtendMode) -> Self {
+        self.extend = extend;
+        self
+    }
+
+    /// A builder-style method for interpolating the gradient in a color space.
+    ///
+    /// See [`GradientStops::interpolated`].
+    ///
+    /// [`GradientStops::interpolated`]: trait.GradientStops.html#method.interpolated
+    pub fn with_interpolation(mut self, space: InterpolationSpace) -> Self {
+        self.stops = expand_stops(self.stops, space);
+        self
+    }
+
     /// Generate a [`FixedRadialGradient`] by mapping points in the unit square
     /// onto points in `rect`.
     ///
@@ -383,10 +535,151 @@ impl RadialGradient {
             origin_offset,
             radius,
             stops: self.stops.clone(),
+            extend: self.extend,
+        }
+    }
+}
+
+impl SweepGradient {
+    /// Create a new sweep gradient, centered in the rectangle.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::f64::consts::PI;
+    ///
+    /// use piet::{Color, RenderContext, SweepGradient};
+    /// use piet::kurbo::{Circle, Point};
+    ///
+    /// # let mut render_ctx = piet::NullRenderContext::new();
+    /// let circle = Circle::new(Point::new(100.0, 100.0), 50.0);
+    /// let gradient = SweepGradient::new(
+    ///     -0.5 * PI,
+    ///     1.5 * PI,
+    ///     (Color::WHITE, Color::BLACK)
+    /// );
+    /// render_ctx.fill(circle, &gradient);
+    /// ```
+    pub fn new(start_angle: f64, end_angle: f64, stops: impl GradientStops) -> Self {
+        SweepGradient {
+            center: UnitPoint::CENTER,
+            start_angle,
+            end_angle,
+            stops: stops.to_vec(),
+        }
+    }
+
+    /// A builder-style method for changing the center of the gradient.
+    pub fn with_center(mut self, center: UnitPoint) -> Self {
+        self.center = center;
+        self
+    }
+
+    /// A builder-style method for interpolating the gradient in a color space.
+    ///
+    /// See [`GradientStops::interpolated`].
+    ///
+    /// [`GradientStops::interpolated`]: trait.GradientStops.html#method.interpolated
+    pub fn with_interpolation(mut self, space: InterpolationSpace) -> Self {
+        self.stops = expand_stops(self.stops, space);
+        self
+    }
+
+    /// Generate a [`FixedSweepGradient`] by mapping points in the unit square
+    /// onto points in `rect`.
+    ///
+    /// [`FixedSweepGradient`]: struct.FixedSweepGradient.html
+    fn resolve(&self, rect: Rect) -> FixedSweepGradient {
+        FixedSweepGradient {
+            center: self.center.resolve(rect),
+            start_angle: self.start_angle,
+            end_angle: self.end_angle,
+            stops: self.stops.clone(),
+        }
+    }
+}
+
+impl FixedSweepGradient {
+    /// The color at a position along the gradient.
+    ///
+    /// Positions beyond the end stops take the color of the nearest stop.
+    /// This is for backends that have no native sweep gradient, and have to
+    /// approximate one from solid colors or simpler gradients.
+    pub fn color_at(&self, pos: f64) -> Color {
+        let pos = pos as f32;
+        let next = self.stops.iter().position(|stop| stop.pos > pos);
+        match next {
+            None => self.stops.last().map(|stop| stop.color.clone()),
+            Some(0) => Some(self.stops[0].color.clone()),
+            Some(i) => {
+                let (a, b) = (&self.stops[i - 1], &self.stops[i]);
+                let t = (pos - a.pos) / (b.pos - a.pos);
+                Some(lerp_color(&a.color, &b.color, t.into()))
+            }
         }
+        .unwrap_or(Color::Rgba32(0))
     }
 }
 
+impl FixedGradient {
+    /// Check that the gradient can be drawn.
+    ///
+    /// A gradient is invalid if it has no stops, if a stop or coordinate
+    /// isn't finite, or if the radius of a radial gradient is negative. A
+    /// gradient with a single stop is drawn in the color of that stop.
+    ///
+    /// ```
+    /// use piet::{Color, ErrorKind, FixedGradient, FixedLinearGradient, GradientStops};
+    ///
+    /// let gradient = FixedGradient::Linear(FixedLinearGradient {
+    ///     start: (0.0, 0.0).into(),
+    ///     end: (f64::NAN, 10.0).into(),
+    ///     stops: (Color::WHITE, Color::BLACK).to_vec(),
+    ///     extend: Default::default(),
+    /// });
+    /// let err = gradient.validate().unwrap_err();
+    /// assert!(matches!(err.kind(), ErrorKind::InvalidGradient(_)));
+    /// ```
+    pub fn validate(&self) -> Result<(), Error> {
+        let (stops, finite) = match self {
+            FixedGradient::Linear(g) => (&g.stops, is_finite(g.start) && is_finite(g.end)),
+            FixedGradient::Radial(g) => {
+                if g.radius < 0.0 {
+                    return Err(invalid_gradient("negative radius"));
+                }
+                let finite = is_finite(g.center)
+                    && g.origin_offset.x.is_finite()
+                    && g.origin_offset.y.is_finite()
+                    && g.radius.is_finite();
+                (&g.stops, finite)
+            }
+            FixedGradient::Sweep(g) => {
+                let finite =
+                    is_finite(g.center) && g.start_angle.is_finite() && g.end_angle.is_finite();
+                (&g.stops, finite)
+            }
+        };
+        if !finite {
+            return Err(invalid_gradient("non-finite coordinates"));
+        }
+        if stops.is_empty() {
+            return Err(invalid_gradient("no stops"));
+        }
+        if stops.iter().any(|stop| !stop.pos.is_finite()) {
+            return Err(invalid_gradient("non-finite stop position"));
+        }
+        Ok(())
+    }
+}
+
+fn is_finite(p: Point) -> bool {
+    p.x.is_finite() && p.y.is_finite()
+}
+
+fn invalid_gradient(reason: &str) -> Error {
+    new_error(ErrorKind::InvalidGradient(reason.into()))
+}
+
 impl From<FixedLinearGradient> for FixedGradient {
     fn from(src: FixedLinearGradient) -> FixedGradient {
         FixedGradient::Linear(src)
@@ -399,6 +692,12 @@ impl From<FixedRadialGradient> for FixedGradient {
     }
 }
 
+impl From<FixedSweepGradient> for FixedGradient {
+    fn from(src: FixedSweepGradient) -> FixedGradient {
+        FixedGradient::Sweep(src)
+    }
+}
+
 impl<P: RenderContext> IntoBrush<P> for FixedGradient {
     fn make_brush<'a>(&'a self, piet: &mut P, _bbox: impl FnOnce() -> Rect) -> Cow<'a, P::Brush> {
         // Also, at some point we might want to be smarter about the extra clone here.
@@ -427,8 +726,161 @@ impl<P: RenderContext> IntoBrush<P> for RadialGradient {
     }
 }
 
+impl<P: RenderContext> IntoBrush<P> for SweepGradient {
+    fn make_brush<'a>(&'a self, piet: &mut P, bbox: impl FnOnce() -> Rect) -> Cow<'a, P::Brush> {
+        let rect = bbox();
+        let gradient = self.resolve(rect);
+        // Perhaps the make_brush method should be fallible instead of panicking.
+        Cow::Owned(piet.gradient(gradient).expect("error creating gradient"))
+    }
+}
+
+/// Interpolate between two colors, componentwise in sRGB.
+fn lerp_color(a: &Color, b: &Color, t: f64) -> Color {
+    let (a, b) = match (a, b) {
+        (Color::Rgba32(a), Color::Rgba32(b)) => (*a, *b),
+        _ => {
+            let a = a.as_rgba_in(ColorSpace::Srgb);
+            let b = b.as_rgba_in(ColorSpace::Srgb);
+            let mix = |x: f64, y: f64| x + t * (y - x);
+            return Color::rgba_in(
+                ColorSpace::Srgb,
+                mix(a.0, b.0),
+                mix(a.1, b.1),
+                mix(a.2, b.2),
+                mix(a.3, b.3),
+            );
+        }
+    };
+    let mix = |shift: u32| {
+        let x = ((a >> shift) & 0xff) as f64;
+        let y = ((b >> shift) & 0xff) as f64;
+        (x + t * (y - x)).round() as u32
+    };
+    Color::from_rgba32_u32((mix(24) << 24) | (mix(16) << 16) | (mix(8) << 8) | mix(0))
+}
+
+/// Add stops so that interpolating them in sRGB follows the ramp in `space`.
+fn expand_stops(stops: Vec<GradientStop>, space: InterpolationSpace) -> Vec<GradientStop> {
+    if space == InterpolationSpace::Srgb || stops.len() < 2 {
+        return stops;
+    }
+    let mut result = Vec::with_capacity(stops.len());
+    for pair in stops.windows(2) {
+        result.push(pair[0].clone());
+        if pair[0].pos < pair[1].pos {
+            subdivide(&pair[0], &pair[1], 0.0, 1.0, space, 0, &mut result);
+        }
+    }
+    result.extend(stops.last().cloned());
+    result
+}
+
+/// Recursively add the stops between `t0` and `t1` of the ramp from `a` to `b`.
+fn subdivide(
+    a: &GradientStop,
+    b: &GradientStop,
+    t0: f64,
+    t1: f64,
+    space: InterpolationSpace,
+    depth: usize,
+    out: &mut Vec<GradientStop>,
+) {
+    const MAX_DEPTH: usize = 6;
+    let t = (t0 + t1) / 2.0;
+    let mid = a.color.lerp(&b.color, t, space);
+    let approx = lerp_color(
+        &a.color.lerp(&b.color, t0, space),
+        &a.color.lerp(&b.color, t1, space),
+        0.5,
+    );
+    let (r0, g0, b0, a0) = mid.as_rgba();
+    let (r1, g1, b1, a1) = approx.as_rgba();
+    let error = (r0 - r1)
+        .abs()
+        .max((g0 - g1).abs())
+        .max((b0 - b1).abs())
+        .max((a0 - a1).abs());
+    if depth >= MAX_DEPTH || error <= 1.0 / 255.0 {
+        return;
+    }
+    subdivide(a, b, t0, t, space, depth + 1, out);
+    out.push(GradientStop {
+        pos: a.pos + (b.pos - a.pos) * t as f32,
+        color: mid,
+    });
+    subdivide(a, b, t, t1, space, depth + 1, out);
+}
+
 fn equalize_sides_preserving_center(rect: Rect, new_len: f64) -> Rect {
     let size = Size::new(new_len, new_len);
     let origin = rect.center() - size.to_vec2() / 2.;
     Rect::from_origin_size(origin, size)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn linear(stops: Vec<GradientStop>) -> FixedGradient {
+        FixedGradient::Linear(FixedLinearGradient {
+            start: Point::new(0.0, 0.0),
+            end: Point::new(10.0, 0.0),
+            stops,
+            extend: ExtendMode::Pad,
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        let stop = GradientStop {
+            pos: 0.0,
+            color: Color::WHITE,
+        };
+        assert!(linear(vec![stop.clone()]).validate().is_ok());
+        assert!(linear(vec![]).validate().is_err());
+        let nan = GradientStop {
+            pos: f32::NAN,
+            ..stop.clone()
+        };
+        assert!(linear(vec![stop, nan]).validate().is_err());
+    }
+
+    #[test]
+    fn test_srgb_interpolation_keeps_stops() {
+        let stops = vec![
+            GradientStop {
+                pos: 0.0,
+                color: Color::rgb8(0xff, 0, 0),
+            },
+            GradientStop {
+                pos: 0.3,
+                color: Color::rgba8(0, 0xff, 0, 0x80),
+            },
+            GradientStop {
+                pos: 1.0,
+                color: Color::rgb_in(ColorSpace::DisplayP3, 0.0, 0.0, 1.0),
+            },
+        ];
+        let interpolated = stops
+            .clone()
+            .interpolated(InterpolationSpace::Srgb)
+            .to_vec();
+        assert_eq!(interpolated.len(), stops.len());
+        for (a, b) in interpolated.iter().zip(&stops) {
+            assert_eq!(a.pos, b.pos);
+            assert!(a.color == b.color);
+        }
+    }
+
+    #[test]
+    fn test_interpolation_adds_stops() {
+        let stops = (Color::rgb8(0xff, 0, 0), Color::rgb8(0, 0, 0xff))
+            .interpolated(InterpolationSpace::OkLab)
+            .to_vec();
+        assert!(stops.len() > 2);
+        assert_eq!(stops[0].color.as_rgba_u32(), 0xff0000ff);
+        assert_eq!(stops.last().unwrap().color.as_rgba_u32(), 0x0000ffff);
+        assert!(stops.windows(2).all(|pair| pair[0].pos < pair[1].pos));
+    }
+}
@@ -2,20 +2,27 @@
 
 pub use kurbo;
 
+mod blur;
 mod color;
 mod conv;
+mod css_color;
 mod error;
 mod gradient;
 mod null_renderer;
+mod pattern;
+mod recording;
 mod render_context;
 mod shapes;
 mod text;
 
+pub use crate::blur::*;
 pub use crate::color::*;
 pub use crate::conv::*;
 pub use crate::error::*;
 pub use crate::gradient::*;
 pub use crate::null_renderer::*;
+pub use crate::pattern::*;
+pub use crate::recording::*;
 pub use crate::render_context::*;
 pub use crate::shapes::*;
 pub use crate::text::*;
@@ -1,12 +1,15 @@
 //! A render context that does nothing.
 
 use std::borrow::Cow;
+use std::ops::RangeBounds;
 
-use kurbo::{Affine, Point, Rect, Shape};
+use kurbo::{Affine, Point, Rect, Shape, Size};
 
 use crate::{
-    Color, Error, FixedGradient, Font, FontBuilder, HitTestPoint, HitTestTextPosition, ImageFormat,
-    InterpolationMode, IntoBrush, RenderContext, StrokeStyle, Text, TextLayout, TextLayoutBuilder,
+    font_family_name, BlendMode, Color, Error, ExtendMode, FixedGradient, Font, FontBuilder,
+    FontMetrics, FontStretch, FontStyle, FontWeight, HitTestPoint, HitTestTextPosition,
+    ImageFormat, InterpolationMode, IntoBrush, LineMetric, RenderContext, StrokeStyle, Text,
+    TextAlignment, TextAttribute, TextLayout, TextLayoutBuilder, TextLayoutMetrics,
 };
 
 /// A render context that doesn't render.
@@ -52,6 +55,8 @@ impl RenderContext for NullRenderContext {
         Ok(())
     }
 
+    fn report_error(&mut self, _err: Error) {}
+
     fn solid_brush(&mut self, _color: Color) -> Self::Brush {
         NullBrush
     }
@@ -60,6 +65,15 @@ impl RenderContext for NullRenderContext {
         Ok(NullBrush)
     }
 
+    fn image_pattern(
+        &mut self,
+        _image: &Self::Image,
+        _transform: Affine,
+        _extend: ExtendMode,
+    ) -> Result<Self::Brush, Error> {
+        Ok(NullBrush)
+    }
+
     fn clear(&mut self, _color: Color) {}
 
     fn stroke(&mut self, _shape: impl Shape, _brush: &impl IntoBrush<Self>, _width: f64) {}
@@ -77,6 +91,8 @@ impl RenderContext for NullRenderContext {
 
     fn fill_even_odd(&mut self, _shape: impl Shape, _brush: &impl IntoBrush<Self>) {}
 
+    fn blurred_rect(&mut self, _rect: Rect, _blur_radius: f64, _brush: &impl IntoBrush<Self>) {}
+
     fn clip(&mut self, _shape: impl Shape) {}
 
     fn text(&mut self) -> &mut Self::Text {
@@ -97,11 +113,28 @@ impl RenderContext for NullRenderContext {
     fn restore(&mut self) -> Result<(), Error> {
         Ok(())
     }
+    fn push_layer(&mut self, _opacity: f64, _clip: Option<Rect>) -> Result<(), Error> {
+        Ok(())
+    }
+    fn pop_layer(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn push_image_mask(&mut self, _image: &Self::Image, _dst_rect: Rect) -> Result<(), Error> {
+        Ok(())
+    }
+    fn push_mask(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn pop_mask(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
     fn finish(&mut self) -> Result<(), Error> {
         Ok(())
     }
     fn transform(&mut self, _transform: Affine) {}
 
+    fn set_blend_mode(&mut self, _mode: BlendMode) {}
+
     fn make_image(
         &mut self,
         _width: usize,
@@ -111,6 +144,24 @@ impl RenderContext for NullRenderContext {
     ) -> Result<Self::Image, Error> {
         Ok(NullImage)
     }
+    fn create_offscreen(
+        &mut self,
+        _size: Size,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<Self::Image, Error> {
+        f(self)?;
+        Ok(NullImage)
+    }
+    fn capture_image_area(&mut self, _src_rect: impl Into<Rect>) -> Result<Self::Image, Error> {
+        Ok(NullImage)
+    }
+    fn read_image(
+        &mut self,
+        _image: &Self::Image,
+        _format: ImageFormat,
+    ) -> Result<(usize, usize, Vec<u8>), Error> {
+        Ok((0, 0, Vec::new()))
+    }
     fn draw_image(
         &mut self,
         _image: &Self::Image,
@@ -142,16 +193,44 @@ impl Text for NullText {
         NullFontBuilder
     }
 
+    fn load_font(&mut self, data: &[u8]) -> Result<String, Error> {
+        font_family_name(data, 0)
+    }
+
+    fn font_families(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+
     fn new_text_layout(&mut self, _font: &Self::Font, _text: &str) -> Self::TextLayoutBuilder {
         NullTextLayoutBuilder
     }
 }
 
-impl Font for NullFont {}
+impl Font for NullFont {
+    fn metrics(&self) -> FontMetrics {
+        FontMetrics::default()
+    }
+
+    fn family_name(&self) -> &str {
+        ""
+    }
+}
 
 impl FontBuilder for NullFontBuilder {
     type Out = NullFont;
 
+    fn weight(self, _weight: FontWeight) -> Self {
+        self
+    }
+
+    fn style(self, _style: FontStyle) -> Self {
+        self
+    }
+
+    fn stretch(self, _stretch: FontStretch) -> Self {
+        self
+    }
+
     fn build(self) -> Result<Self::Out, Error> {
         Ok(NullFont)
     }
@@ -160,6 +239,22 @@ impl FontBuilder for NullFontBuilder {
 impl TextLayoutBuilder for NullTextLayoutBuilder {
     type Out = NullTextLayout;
 
+    fn range_attribute(
+        self,
+        _range: impl RangeBounds<usize>,
+        _attribute: impl Into<TextAttribute>,
+    ) -> Self {
+        self
+    }
+
+    fn max_width(self, _width: f64) -> Self {
+        self
+    }
+
+    fn alignment(self, _alignment: TextAlignment) -> Self {
+        self
+    }
+
     fn build(self) -> Result<Self::Out, Error> {
         Ok(NullTextLayout)
     }
@@ -170,6 +265,22 @@ impl TextLayout for NullTextLayout {
         42.0
     }
 
+    fn metrics(&self) -> TextLayoutMetrics {
+        TextLayoutMetrics::default()
+    }
+
+    fn line_count(&self) -> usize {
+        0
+    }
+
+    fn line_metric(&self, _line_number: usize) -> Option<LineMetric> {
+        None
+    }
+
+    fn line_text(&self, _line_number: usize) -> Option<&str> {
+        None
+    }
+
     fn hit_test_point(&self, _point: Point) -> HitTestPoint {
         HitTestPoint::default()
     }
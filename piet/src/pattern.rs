@@ -0,0 +1,99 @@
+//! Image pattern brushes.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use kurbo::{Affine, Rect};
+
+use crate::{Color, ExtendMode, ImageFormat, IntoBrush, RenderContext};
+
+/// A brush that fills with an image, usually tiled.
+///
+/// This holds the pixels themselves rather than a back-end image, so that it
+/// can be stored without reference to any particular `RenderContext`, in the
+/// same way as the gradient types. An image is created from the pixels each
+/// time the pattern is used as a brush; to draw with the same pattern many
+/// times, make a brush once with
+/// [`RenderContext::image_pattern`](trait.RenderContext.html#tymethod.image_pattern)
+/// instead.
+///
+/// On a backend that can't make the image, the pattern draws nothing, and
+/// the error is reported by
+/// [`RenderContext::status`](trait.RenderContext.html#tymethod.status).
+///
+/// # Examples
+///
+/// ```
+/// use piet::{ImageFormat, ImagePattern, RenderContext};
+/// use piet::kurbo::{Affine, Rect};
+///
+/// # let mut render_ctx = piet::NullRenderContext::new();
+/// let checkers = [0, 0, 0, 255, 255, 255, 255, 255, 255, 0, 0, 0];
+/// let pattern = ImagePattern::new(2, 2, &checkers[..], ImageFormat::Rgb)
+///     .with_transform(Affine::scale(10.0));
+/// render_ctx.fill(Rect::new(0.0, 0.0, 100.0, 100.0), &pattern);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ImagePattern {
+    width: usize,
+    height: usize,
+    buf: Arc<[u8]>,
+    format: ImageFormat,
+    transform: Affine,
+    extend: ExtendMode,
+}
+
+impl ImagePattern {
+    /// Create a new image pattern from a pixel buffer.
+    ///
+    /// The buffer is in the same layout as for
+    /// [`RenderContext::make_image`](trait.RenderContext.html#tymethod.make_image).
+    /// By default, the image is drawn at its pixel size with its top left
+    /// corner at the origin, and repeated in both directions.
+    pub fn new(
+        width: usize,
+        height: usize,
+        buf: impl Into<Arc<[u8]>>,
+        format: ImageFormat,
+    ) -> Self {
+        ImagePattern {
+            width,
+            height,
+            buf: buf.into(),
+            format,
+            transform: Affine::default(),
+            extend: ExtendMode::Repeat,
+        }
+    }
+
+    /// A builder-style method for changing the pattern transform.
+    ///
+    /// The transform maps pixel coordinates of the image into the user space
+    /// of the shape being drawn.
+    pub fn with_transform(mut self, transform: Affine) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// A builder-style method for changing how the image is extended beyond
+    /// its bounds.
+    pub fn with_extend(mut self, extend: ExtendMode) -> Self {
+        self.extend = extend;
+        self
+    }
+}
+
+impl<P: RenderContext> IntoBrush<P> for ImagePattern {
+    fn make_brush<'a>(&'a self, piet: &mut P, _bbox: impl FnOnce() -> Rect) -> Cow<'a, P::Brush> {
+        let brush = piet
+            .make_image(self.width, self.height, &self.buf, self.format)
+            .and_then(|image| piet.image_pattern(&image, self.transform, self.extend));
+        match brush {
+            Ok(brush) => Cow::Owned(brush),
+            Err(err) => {
+                piet.report_error(err);
+                Cow::Owned(piet.solid_brush(Color::rgba8(0, 0, 0, 0)))
+            }
+        }
+    }
+}
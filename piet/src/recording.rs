@@ -0,0 +1,909 @@
+//! A render context that records drawing operations for playback later.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+
+use kurbo::{Affine, BezPath, Circle, Line, Point, Rect, RoundedRect, Shape, Size};
+
+use crate::{
+    font_family_name, new_error, AttributeSpans, BlendMode, Color, Error, ErrorKind, ExtendMode,
+    FixedGradient, Font, FontBuilder, FontMetrics, FontStretch, FontStyle, FontWeight,
+    HitTestPoint, HitTestTextPosition, ImageFormat, InterpolationMode, IntoBrush, LineMetric,
+    RenderContext, StrokeStyle, Text, TextAlignment, TextAttribute, TextLayout, TextLayoutBuilder,
+    TextLayoutMetrics,
+};
+
+/// A render context that records drawing operations into a [`DisplayList`].
+///
+/// Every operation is captured, with brushes, images and text layouts stored
+/// in a back-end independent form, so that the display list can be played
+/// back into any other `RenderContext`, any number of times.
+///
+/// Text is not laid out while recording, so the metrics of a recorded text
+/// layout are not available: its width is zero, and hit testing finds
+/// nothing.
+///
+/// [`DisplayList`]: struct.DisplayList.html
+pub struct RecordingRenderContext {
+    ops: Vec<Op>,
+    text: RecordingText,
+    xf: Affine,
+    stack: Vec<Affine>,
+    err: Result<(), Error>,
+}
+
+/// An owned list of drawing operations, made by a [`RecordingRenderContext`].
+///
+/// # Examples
+///
+/// ```
+/// use piet::{Color, RecordingRenderContext, RenderContext};
+/// use piet::kurbo::Rect;
+///
+/// let mut recording = RecordingRenderContext::new();
+/// recording.fill(Rect::new(0.0, 0.0, 10.0, 10.0), &Color::BLACK);
+/// let display_list = recording.into_display_list();
+///
+/// let mut render_ctx = piet::NullRenderContext::new();
+/// display_list.play(&mut render_ctx).unwrap();
+/// ```
+///
+/// [`RecordingRenderContext`]: struct.RecordingRenderContext.html
+#[derive(Clone, Debug, Default)]
+pub struct DisplayList {
+    ops: Vec<Op>,
+    /// The data of the fonts loaded while recording.
+    fonts: Vec<Arc<[u8]>>,
+}
+
+/// A brush in a recording.
+#[derive(Clone, Debug)]
+pub enum RecordedBrush {
+    /// A solid color.
+    Solid(Color),
+    /// A linear or radial gradient.
+    Gradient(FixedGradient),
+    /// An image pattern, as made by `image_pattern`.
+    Image {
+        /// The image that is repeated.
+        image: RecordedImage,
+        /// The transform from the image's space to the user space.
+        transform: Affine,
+        /// How the image is extended beyond its bounds.
+        extend: ExtendMode,
+    },
+}
+
+/// An image in a recording.
+///
+/// This keeps a copy of the pixels, or the drawing of an offscreen image,
+/// which are shared by clones.
+#[derive(Clone, Debug)]
+pub struct RecordedImage {
+    width: usize,
+    height: usize,
+    source: ImageSource,
+}
+
+#[derive(Clone, Debug)]
+enum ImageSource {
+    Pixels { buf: Arc<[u8]>, format: ImageFormat },
+    Offscreen(Arc<DisplayList>),
+}
+
+/// The text factory of a [`RecordingRenderContext`].
+///
+/// Fonts loaded with `load_font` are kept with the display list, and are
+/// loaded into the render context it is played back into.
+///
+/// [`RecordingRenderContext`]: struct.RecordingRenderContext.html
+pub struct RecordingText {
+    fonts: Vec<Arc<[u8]>>,
+}
+
+/// A font in a recording, which is resolved when it is played back.
+#[derive(Clone, Debug)]
+pub struct RecordedFont {
+    name: String,
+    size: f64,
+    weight: FontWeight,
+    style: FontStyle,
+    stretch: FontStretch,
+}
+
+/// The font builder of a [`RecordingText`].
+///
+/// [`RecordingText`]: struct.RecordingText.html
+pub struct RecordedFontBuilder(RecordedFont);
+
+/// A text layout in a recording, which is laid out when it is played back.
+#[derive(Clone, Debug)]
+pub struct RecordedTextLayout {
+    font: RecordedFont,
+    text: String,
+    attributes: AttributeSpans,
+    max_width: Option<f64>,
+    alignment: TextAlignment,
+}
+
+/// The text layout builder of a [`RecordingText`].
+///
+/// [`RecordingText`]: struct.RecordingText.html
+pub struct RecordedTextLayoutBuilder(RecordedTextLayout);
+
+/// A shape, keeping the kinds of shape that back-ends may draw specially.
+#[derive(Clone, Debug)]
+enum RecordedShape {
+    Line(Line),
+    Rect(Rect),
+    RoundedRect(RoundedRect),
+    Circle(Circle),
+    Path(BezPath),
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Clear(Color),
+    Stroke {
+        shape: RecordedShape,
+        brush: RecordedBrush,
+        width: f64,
+        style: Option<StrokeStyle>,
+    },
+    Fill {
+        shape: RecordedShape,
+        brush: RecordedBrush,
+        even_odd: bool,
+    },
+    BlurredRect {
+        rect: Rect,
+        blur_radius: f64,
+        brush: RecordedBrush,
+    },
+    Clip(RecordedShape),
+    DrawText {
+        layout: RecordedTextLayout,
+        pos: Point,
+        brush: RecordedBrush,
+    },
+    Save,
+    Restore,
+    PushLayer {
+        opacity: f64,
+        clip: Option<Rect>,
+    },
+    PopLayer,
+    PushImageMask {
+        image: RecordedImage,
+        dst_rect: Rect,
+    },
+    PushMask,
+    PopMask,
+    Transform(Affine),
+    SetBlendMode(BlendMode),
+    DrawImage {
+        image: RecordedImage,
+        src_rect: Option<Rect>,
+        dst_rect: Rect,
+        interp: InterpolationMode,
+    },
+}
+
+impl RecordingRenderContext {
+    /// Create a render context that records nothing yet.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> RecordingRenderContext {
+        RecordingRenderContext {
+            ops: Vec::new(),
+            text: RecordingText { fonts: Vec::new() },
+            xf: Affine::default(),
+            stack: Vec::new(),
+            err: Ok(()),
+        }
+    }
+
+    /// Finish recording, returning the operations recorded so far.
+    pub fn into_display_list(self) -> DisplayList {
+        DisplayList {
+            ops: self.ops,
+            fonts: self.text.fonts,
+        }
+    }
+
+    fn save_xf(&mut self) {
+        self.stack.push(self.xf);
+    }
+
+    fn restore_xf(&mut self) -> Result<(), Error> {
+        match self.stack.pop() {
+            Some(xf) => {
+                self.xf = xf;
+                Ok(())
+            }
+            None => Err(new_error(ErrorKind::StackUnbalance)),
+        }
+    }
+}
+
+impl RenderContext for RecordingRenderContext {
+    type Brush = RecordedBrush;
+    type Image = RecordedImage;
+    type Text = RecordingText;
+    type TextLayout = RecordedTextLayout;
+
+    fn status(&mut self) -> Result<(), Error> {
+        std::mem::replace(&mut self.err, Ok(()))
+    }
+
+    fn report_error(&mut self, err: Error) {
+        self.err = Err(err);
+    }
+
+    fn solid_brush(&mut self, color: Color) -> Self::Brush {
+        RecordedBrush::Solid(color)
+    }
+
+    fn gradient(&mut self, gradient: impl Into<FixedGradient>) -> Result<Self::Brush, Error> {
+        Ok(RecordedBrush::Gradient(gradient.into()))
+    }
+
+    fn image_pattern(
+        &mut self,
+        image: &Self::Image,
+        transform: Affine,
+        extend: ExtendMode,
+    ) -> Result<Self::Brush, Error> {
+        Ok(RecordedBrush::Image {
+            image: image.clone(),
+            transform,
+            extend,
+        })
+    }
+
+    fn clear(&mut self, color: Color) {
+        self.ops.push(Op::Clear(color));
+    }
+
+    fn stroke(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>, width: f64) {
+        let brush = brush.make_brush(self, || shape.bounding_box()).into_owned();
+        self.ops.push(Op::Stroke {
+            shape: RecordedShape::new(shape),
+            brush,
+            width,
+            style: None,
+        });
+    }
+
+    fn stroke_styled(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        width: f64,
+        style: &StrokeStyle,
+    ) {
+        let brush = brush.make_brush(self, || shape.bounding_box()).into_owned();
+        self.ops.push(Op::Stroke {
+            shape: RecordedShape::new(shape),
+            brush,
+            width,
+            style: Some(style.clone()),
+        });
+    }
+
+    fn fill(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
+        let brush = brush.make_brush(self, || shape.bounding_box()).into_owned();
+        self.ops.push(Op::Fill {
+            shape: RecordedShape::new(shape),
+            brush,
+            even_odd: false,
+        });
+    }
+
+    fn fill_even_odd(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
+        let brush = brush.make_brush(self, || shape.bounding_box()).into_owned();
+        self.ops.push(Op::Fill {
+            shape: RecordedShape::new(shape),
+            brush,
+            even_odd: true,
+        });
+    }
+
+    fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &impl IntoBrush<Self>) {
+        let brush = brush.make_brush(self, || rect).into_owned();
+        self.ops.push(Op::BlurredRect {
+            rect,
+            blur_radius,
+            brush,
+        });
+    }
+
+    fn clip(&mut self, shape: impl Shape) {
+        self.ops.push(Op::Clip(RecordedShape::new(shape)));
+    }
+
+    fn text(&mut self) -> &mut Self::Text {
+        &mut self.text
+    }
+
+    fn draw_text(
+        &mut self,
+        layout: &Self::TextLayout,
+        pos: impl Into<Point>,
+        brush: &impl IntoBrush<Self>,
+    ) {
+        let pos = pos.into();
+        // As with the other back-ends, the bbox is approximate.
+        let brush = brush
+            .make_brush(self, || Rect::from_origin_size(pos, (0.0, 0.0)))
+            .into_owned();
+        self.ops.push(Op::DrawText {
+            layout: layout.clone(),
+            pos,
+            brush,
+        });
+    }
+
+    fn save(&mut self) -> Result<(), Error> {
+        self.save_xf();
+        self.ops.push(Op::Save);
+        Ok(())
+    }
+
+    fn restore(&mut self) -> Result<(), Error> {
+        self.restore_xf()?;
+        self.ops.push(Op::Restore);
+        Ok(())
+    }
+
+    fn push_layer(&mut self, opacity: f64, clip: Option<Rect>) -> Result<(), Error> {
+        self.save_xf();
+        self.ops.push(Op::PushLayer { opacity, clip });
+        Ok(())
+    }
+
+    fn pop_layer(&mut self) -> Result<(), Error> {
+        self.restore_xf()?;
+        self.ops.push(Op::PopLayer);
+        Ok(())
+    }
+
+    fn push_image_mask(&mut self, image: &Self::Image, dst_rect: Rect) -> Result<(), Error> {
+        self.save_xf();
+        self.ops.push(Op::PushImageMask {
+            image: image.clone(),
+            dst_rect,
+        });
+        Ok(())
+    }
+
+    fn push_mask(&mut self) -> Result<(), Error> {
+        self.save_xf();
+        self.ops.push(Op::PushMask);
+        Ok(())
+    }
+
+    fn pop_mask(&mut self) -> Result<(), Error> {
+        // The mask's state is restored, and saved again for the masked layer.
+        self.restore_xf()?;
+        self.save_xf();
+        self.ops.push(Op::PopMask);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn transform(&mut self, transform: Affine) {
+        self.xf *= transform;
+        self.ops.push(Op::Transform(transform));
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.ops.push(Op::SetBlendMode(mode));
+    }
+
+    fn make_image(
+        &mut self,
+        width: usize,
+        height: usize,
+        buf: &[u8],
+        format: ImageFormat,
+    ) -> Result<Self::Image, Error> {
+        let len = width * height * format.bytes_per_pixel();
+        if buf.len() < len {
+            let kind = ErrorKind::InvalidBufferLength {
+                expected: len,
+                found: buf.len(),
+            };
+            return Err(new_error(kind).with_context("make_image"));
+        }
+        Ok(RecordedImage {
+            width,
+            height,
+            source: ImageSource::Pixels {
+                buf: buf[..len].into(),
+                format,
+            },
+        })
+    }
+
+    fn create_offscreen(
+        &mut self,
+        size: Size,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<Self::Image, Error> {
+        let ops = std::mem::take(&mut self.ops);
+        let xf = std::mem::take(&mut self.xf);
+        let stack = std::mem::take(&mut self.stack);
+        let result = f(self);
+        let balanced = self.stack.is_empty();
+        let list = DisplayList {
+            ops: std::mem::replace(&mut self.ops, ops),
+            fonts: self.text.fonts.clone(),
+        };
+        self.xf = xf;
+        self.stack = stack;
+        result?;
+        if !balanced {
+            return Err(new_error(ErrorKind::StackUnbalance));
+        }
+        Ok(RecordedImage {
+            width: size.width.ceil() as usize,
+            height: size.height.ceil() as usize,
+            source: ImageSource::Offscreen(Arc::new(list)),
+        })
+    }
+
+    fn capture_image_area(&mut self, _src_rect: impl Into<Rect>) -> Result<Self::Image, Error> {
+        Err(new_error(ErrorKind::NotSupported))
+    }
+
+    fn read_image(
+        &mut self,
+        image: &Self::Image,
+        format: ImageFormat,
+    ) -> Result<(usize, usize, Vec<u8>), Error> {
+        match &image.source {
+            ImageSource::Pixels { buf, format: from } => {
+                let buf = convert_pixels(buf, *from, format)?;
+                Ok((image.width, image.height, buf))
+            }
+            // The drawing is only rendered when it is played back.
+            ImageSource::Offscreen(_) => Err(new_error(ErrorKind::NotSupported)),
+        }
+    }
+
+    fn draw_image(
+        &mut self,
+        image: &Self::Image,
+        dst_rect: impl Into<Rect>,
+        interp: InterpolationMode,
+    ) {
+        self.ops.push(Op::DrawImage {
+            image: image.clone(),
+            src_rect: None,
+            dst_rect: dst_rect.into(),
+            interp,
+        });
+    }
+
+    fn draw_image_area(
+        &mut self,
+        image: &Self::Image,
+        src_rect: impl Into<Rect>,
+        dst_rect: impl Into<Rect>,
+        interp: InterpolationMode,
+    ) {
+        self.ops.push(Op::DrawImage {
+            image: image.clone(),
+            src_rect: Some(src_rect.into()),
+            dst_rect: dst_rect.into(),
+            interp,
+        });
+    }
+
+    fn current_transform(&self) -> Affine {
+        self.xf
+    }
+}
+
+impl DisplayList {
+    /// Play the recorded operations back into a render context.
+    ///
+    /// Fonts loaded while recording are loaded into `rc` first, then
+    /// brushes, images and text layouts are created on it as they are
+    /// needed. A back-end that can't load fonts still plays the drawing,
+    /// and its text falls back to installed fonts. Playback stops at the
+    /// first other error.
+    pub fn play<R: RenderContext>(&self, rc: &mut R) -> Result<(), Error> {
+        for data in &self.fonts {
+            match rc.text().load_font(data) {
+                Ok(_) => (),
+                Err(e) if matches!(e.kind(), ErrorKind::NotSupported) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        self.play_ops(rc)
+    }
+
+    /// Play the operations without loading the fonts, for offscreen images,
+    /// which share the text factory of the context their list is played
+    /// into.
+    fn play_ops<R: RenderContext>(&self, rc: &mut R) -> Result<(), Error> {
+        let mut player = Player {
+            rc,
+            images: HashMap::new(),
+        };
+        for op in &self.ops {
+            player.play_op(op)?;
+        }
+        Ok(())
+    }
+
+    /// Whether nothing was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+/// Call a function generic over shapes with a recorded shape.
+macro_rules! with_shape {
+    ($shape:expr, |$s:ident| $body:expr) => {
+        match $shape {
+            RecordedShape::Line($s) => $body,
+            RecordedShape::Rect($s) => $body,
+            RecordedShape::RoundedRect($s) => $body,
+            RecordedShape::Circle($s) => $body,
+            RecordedShape::Path($s) => $body,
+        }
+    };
+}
+
+/// The state of a playback.
+struct Player<'a, R: RenderContext> {
+    rc: &'a mut R,
+    /// Images created so far, by the address of their pixels or drawing.
+    images: HashMap<*const (), R::Image>,
+}
+
+impl<'a, R: RenderContext> Player<'a, R> {
+    fn play_op(&mut self, op: &Op) -> Result<(), Error> {
+        match op {
+            Op::Clear(color) => self.rc.clear(color.clone()),
+            Op::Stroke {
+                shape,
+                brush,
+                width,
+                style,
+            } => {
+                let brush = self.brush(brush)?;
+                match style {
+                    Some(style) => {
+                        with_shape!(shape, |s| self.rc.stroke_styled(s, &brush, *width, style))
+                    }
+                    None => with_shape!(shape, |s| self.rc.stroke(s, &brush, *width)),
+                }
+            }
+            Op::Fill {
+                shape,
+                brush,
+                even_odd,
+            } => {
+                let brush = self.brush(brush)?;
+                if *even_odd {
+                    with_shape!(shape, |s| self.rc.fill_even_odd(s, &brush))
+                } else {
+                    with_shape!(shape, |s| self.rc.fill(s, &brush))
+                }
+            }
+            Op::BlurredRect {
+                rect,
+                blur_radius,
+                brush,
+            } => {
+                let brush = self.brush(brush)?;
+                self.rc.blurred_rect(*rect, *blur_radius, &brush);
+            }
+            Op::Clip(shape) => with_shape!(shape, |s| self.rc.clip(s)),
+            Op::DrawText { layout, pos, brush } => {
+                let brush = self.brush(brush)?;
+                let text = self.rc.text();
+                let font = text
+                    .new_font_by_name(&layout.font.name, layout.font.size)
+                    .weight(layout.font.weight)
+                    .style(layout.font.style)
+                    .stretch(layout.font.stretch)
+                    .build()?;
+                let mut builder = text.new_text_layout(&font, &layout.text);
+                for (range, attribute) in layout.attributes.iter() {
+                    builder = builder.range_attribute(range, attribute.clone());
+                }
+                if let Some(width) = layout.max_width {
+                    builder = builder.max_width(width);
+                }
+                builder = builder.alignment(layout.alignment);
+                self.rc.draw_text(&builder.build()?, *pos, &brush);
+            }
+            Op::Save => self.rc.save()?,
+            Op::Restore => self.rc.restore()?,
+            Op::PushLayer { opacity, clip } => self.rc.push_layer(*opacity, *clip)?,
+            Op::PopLayer => self.rc.pop_layer()?,
+            Op::PushImageMask { image, dst_rect } => {
+                self.make_image(image)?;
+                let image = &self.images[&image.key()];
+                self.rc.push_image_mask(image, *dst_rect)?;
+            }
+            Op::PushMask => self.rc.push_mask()?,
+            Op::PopMask => self.rc.pop_mask()?,
+            Op::Transform(transform) => self.rc.transform(*transform),
+            Op::SetBlendMode(mode) => self.rc.set_blend_mode(*mode),
+            Op::DrawImage {
+                image,
+                src_rect,
+                dst_rect,
+                interp,
+            } => {
+                self.make_image(image)?;
+                let image = &self.images[&image.key()];
+                match src_rect {
+                    Some(src_rect) => self
+                        .rc
+                        .draw_image_area(image, *src_rect, *dst_rect, *interp),
+                    None => self.rc.draw_image(image, *dst_rect, *interp),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a back-end image for a recorded one, if it doesn't exist yet.
+    fn make_image(&mut self, image: &RecordedImage) -> Result<(), Error> {
+        let key = image.key();
+        if !self.images.contains_key(&key) {
+            let made = match &image.source {
+                ImageSource::Pixels { buf, format } => {
+                    self.rc
+                        .make_image(image.width, image.height, buf, *format)?
+                }
+                ImageSource::Offscreen(list) => {
+                    let size = Size::new(image.width as f64, image.height as f64);
+                    self.rc.create_offscreen(size, |rc| list.play_ops(rc))?
+                }
+            };
+            self.images.insert(key, made);
+        }
+        Ok(())
+    }
+
+    fn brush(&mut self, brush: &RecordedBrush) -> Result<R::Brush, Error> {
+        match brush {
+            RecordedBrush::Solid(color) => Ok(self.rc.solid_brush(color.clone())),
+            RecordedBrush::Gradient(gradient) => self.rc.gradient(gradient.clone()),
+            RecordedBrush::Image {
+                image,
+                transform,
+                extend,
+            } => {
+                self.make_image(image)?;
+                let image = &self.images[&image.key()];
+                self.rc.image_pattern(image, *transform, *extend)
+            }
+        }
+    }
+}
+
+impl RecordedImage {
+    /// The address of the shared contents, which identifies the image.
+    fn key(&self) -> *const () {
+        match &self.source {
+            ImageSource::Pixels { buf, .. } => Arc::as_ptr(buf) as *const (),
+            ImageSource::Offscreen(list) => Arc::as_ptr(list) as *const (),
+        }
+    }
+}
+
+/// Convert pixels from one format to another.
+fn convert_pixels(buf: &[u8], from: ImageFormat, to: ImageFormat) -> Result<Vec<u8>, Error> {
+    if from == to {
+        return Ok(buf.to_vec());
+    }
+    let mul = |x: u8, a: u8| ((x as u32 * a as u32 + 127) / 255) as u8;
+    let div = |x: u8, a: u8| match a {
+        0 => 0,
+        a => ((x as u32 * 255 + a as u32 / 2) / a as u32).min(255) as u8,
+    };
+    // Go through separate alpha, which every format can be made from.
+    let pixels: Vec<[u8; 4]> = match from {
+        ImageFormat::Rgb => buf
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        ImageFormat::RgbaSeparate => buf
+            .chunks_exact(4)
+            .map(|p| [p[0], p[1], p[2], p[3]])
+            .collect(),
+        ImageFormat::RgbaPremul => buf
+            .chunks_exact(4)
+            .map(|p| [div(p[0], p[3]), div(p[1], p[3]), div(p[2], p[3]), p[3]])
+            .collect(),
+        _ => return Err(new_error(ErrorKind::UnsupportedFormat(from))),
+    };
+    let out = match to {
+        ImageFormat::Rgb => pixels.iter().flat_map(|p| [p[0], p[1], p[2]]).collect(),
+        ImageFormat::RgbaSeparate => pixels.iter().flat_map(|p| *p).collect(),
+        ImageFormat::RgbaPremul => pixels
+            .iter()
+            .flat_map(|p| [mul(p[0], p[3]), mul(p[1], p[3]), mul(p[2], p[3]), p[3]])
+            .collect(),
+        _ => return Err(new_error(ErrorKind::UnsupportedFormat(to))),
+    };
+    Ok(out)
+}
+
+impl RecordedShape {
+    fn new(shape: impl Shape) -> RecordedShape {
+        if let Some(line) = shape.as_line() {
+            RecordedShape::Line(line)
+        } else if let Some(rect) = shape.as_rect() {
+            RecordedShape::Rect(rect)
+        } else if let Some(rect) = shape.as_rounded_rect() {
+            RecordedShape::RoundedRect(rect)
+        } else if let Some(circle) = shape.as_circle() {
+            RecordedShape::Circle(circle)
+        } else {
+            RecordedShape::Path(shape.into_bez_path(1e-3))
+        }
+    }
+}
+
+impl Text for RecordingText {
+    type Font = RecordedFont;
+    type FontBuilder = RecordedFontBuilder;
+    type TextLayout = RecordedTextLayout;
+    type TextLayoutBuilder = RecordedTextLayoutBuilder;
+
+    fn new_font_by_name(&mut self, name: &str, size: f64) -> Self::FontBuilder {
+        RecordedFontBuilder(RecordedFont {
+            name: name.to_owned(),
+            size,
+            weight: FontWeight::default(),
+            style: FontStyle::default(),
+            stretch: FontStretch::default(),
+        })
+    }
+
+    fn load_font(&mut self, data: &[u8]) -> Result<String, Error> {
+        let family = font_family_name(data, 0)?;
+        if !self.fonts.iter().any(|font| **font == *data) {
+            self.fonts.push(data.into());
+        }
+        Ok(family)
+    }
+
+    /// The families of the loaded fonts, as the render context that plays
+    /// back a display list may not have the same installed fonts.
+    fn font_families(&mut self) -> Vec<String> {
+        let mut families: Vec<_> = self
+            .fonts
+            .iter()
+            .filter_map(|data| font_family_name(data, 0).ok())
+            .collect();
+        families.sort();
+        families.dedup();
+        families
+    }
+
+    fn new_text_layout(&mut self, font: &Self::Font, text: &str) -> Self::TextLayoutBuilder {
+        RecordedTextLayoutBuilder(RecordedTextLayout {
+            font: font.clone(),
+            text: text.to_owned(),
+            attributes: AttributeSpans::new(),
+            max_width: None,
+            alignment: TextAlignment::default(),
+        })
+    }
+}
+
+impl Font for RecordedFont {
+    fn metrics(&self) -> FontMetrics {
+        FontMetrics::default()
+    }
+
+    fn family_name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl FontBuilder for RecordedFontBuilder {
+    type Out = RecordedFont;
+
+    fn weight(mut self, weight: FontWeight) -> Self {
+        self.0.weight = weight;
+        self
+    }
+
+    fn style(mut self, style: FontStyle) -> Self {
+        self.0.style = style;
+        self
+    }
+
+    fn stretch(mut self, stretch: FontStretch) -> Self {
+        self.0.stretch = stretch;
+        self
+    }
+
+    fn build(self) -> Result<Self::Out, Error> {
+        Ok(self.0)
+    }
+}
+
+impl TextLayoutBuilder for RecordedTextLayoutBuilder {
+    type Out = RecordedTextLayout;
+
+    fn range_attribute(
+        mut self,
+        range: impl RangeBounds<usize>,
+        attribute: impl Into<TextAttribute>,
+    ) -> Self {
+        let layout = &mut self.0;
+        layout.attributes.add(&layout.text, range, attribute.into());
+        self
+    }
+
+    fn max_width(mut self, width: f64) -> Self {
+        self.0.max_width = Some(width);
+        self
+    }
+
+    fn alignment(mut self, alignment: TextAlignment) -> Self {
+        self.0.alignment = alignment;
+        self
+    }
+
+    fn build(self) -> Result<Self::Out, Error> {
+        Ok(self.0)
+    }
+}
+
+impl TextLayout for RecordedTextLayout {
+    fn width(&self) -> f64 {
+        0.0
+    }
+
+    fn metrics(&self) -> TextLayoutMetrics {
+        TextLayoutMetrics::default()
+    }
+
+    fn line_count(&self) -> usize {
+        0
+    }
+
+    fn line_metric(&self, _line_number: usize) -> Option<LineMetric> {
+        None
+    }
+
+    fn line_text(&self, _line_number: usize) -> Option<&str> {
+        None
+    }
+
+    fn hit_test_point(&self, _point: Point) -> HitTestPoint {
+        HitTestPoint::default()
+    }
+
+    fn hit_test_text_position(&self, _text_position: usize) -> Option<HitTestTextPosition> {
+        None
+    }
+}
+
+impl IntoBrush<RecordingRenderContext> for RecordedBrush {
+    fn make_brush<'b>(
+        &'b self,
+        _piet: &mut RecordingRenderContext,
+        _bbox: impl FnOnce() -> Rect,
+    ) -> Cow<'b, RecordedBrush> {
+        Cow::Borrowed(self)
+    }
+}
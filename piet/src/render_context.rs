@@ -2,15 +2,16 @@
 
 use std::borrow::Cow;
 
-use kurbo::{Affine, Point, Rect, Shape};
+use kurbo::{Affine, Point, Rect, Shape, Size};
 
 use crate::{
-    Color, Error, FixedGradient, FixedLinearGradient, FixedRadialGradient, LinearGradient,
-    RadialGradient, StrokeStyle, Text, TextLayout,
+    Color, Error, ExtendMode, FixedGradient, FixedLinearGradient, FixedRadialGradient,
+    FixedSweepGradient, ImagePattern, LinearGradient, RadialGradient, StrokeStyle, SweepGradient,
+    Text, TextLayout,
 };
 
 /// A requested interpolation mode for drawing images.
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum InterpolationMode {
     /// Don't interpolate, use nearest neighbor.
     NearestNeighbor,
@@ -18,6 +19,72 @@ pub enum InterpolationMode {
     Bilinear,
 }
 
+/// How drawing operations are composited with the existing contents of the surface.
+///
+/// The first group of modes are the Porter-Duff compositing operators, and
+/// the rest are the blend modes from the [W3C Compositing and Blending]
+/// spec, which are composited with source-over after blending.
+///
+/// [W3C Compositing and Blending]: https://www.w3.org/TR/compositing-1/
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BlendMode {
+    /// Clear the destination, regardless of the source.
+    Clear,
+    /// Replace the destination with the source.
+    Copy,
+    /// Draw the source over the destination. This is the default.
+    #[default]
+    SourceOver,
+    /// Draw the source where the destination is present.
+    SourceIn,
+    /// Draw the source where the destination is absent.
+    SourceOut,
+    /// Draw the source over the destination, only where the destination is present.
+    SourceAtop,
+    /// Draw the destination over the source.
+    DestOver,
+    /// Keep the destination where the source is present.
+    DestIn,
+    /// Keep the destination where the source is absent.
+    DestOut,
+    /// Keep the destination over the source, only where the source is present.
+    DestAtop,
+    /// Keep the source and destination only where they don't overlap.
+    Xor,
+    /// Add the source and destination.
+    Add,
+    /// Multiply the source and destination colors.
+    Multiply,
+    /// The complement of multiplying the complements of the colors.
+    Screen,
+    /// Multiply or screen, depending on the destination color.
+    Overlay,
+    /// Keep the darker of the source and destination colors.
+    Darken,
+    /// Keep the lighter of the source and destination colors.
+    Lighten,
+    /// Brighten the destination to reflect the source.
+    ColorDodge,
+    /// Darken the destination to reflect the source.
+    ColorBurn,
+    /// Multiply or screen, depending on the source color.
+    HardLight,
+    /// Darken or lighten, depending on the source color.
+    SoftLight,
+    /// The absolute difference of the source and destination colors.
+    Difference,
+    /// Like `Difference`, but with lower contrast.
+    Exclusion,
+    /// The hue of the source with the saturation and luminosity of the destination.
+    Hue,
+    /// The saturation of the source with the hue and luminosity of the destination.
+    Saturation,
+    /// The hue and saturation of the source with the luminosity of the destination.
+    Color,
+    /// The luminosity of the source with the hue and saturation of the destination.
+    Luminosity,
+}
+
 /// The pixel format for bitmap images.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ImageFormat {
@@ -59,7 +126,7 @@ where
 {
     /// The type of a "brush".
     ///
-    /// Represents solid colors and gradients.
+    /// Represents solid colors, gradients and image patterns.
     type Brush: Clone;
 
     /// An associated factory for creating text layouts and related resources.
@@ -76,6 +143,10 @@ where
     /// any such error that has been detected.
     fn status(&mut self) -> Result<(), Error>;
 
+    /// Record an error from an operation that has no way to return it, such
+    /// as making a brush while drawing, so that `status` reports it.
+    fn report_error(&mut self, err: Error);
+
     /// Create a new brush resource.
     ///
     /// TODO: figure out how to document lifetime and rebuilding requirements. Should
@@ -88,9 +159,25 @@ where
     /// Create a new gradient brush.
     fn gradient(&mut self, gradient: impl Into<FixedGradient>) -> Result<Self::Brush, Error>;
 
+    /// Create a new brush that fills with an image.
+    ///
+    /// The `transform` maps pixel coordinates of the image into the user space
+    /// in effect when the brush is used, and `extend` determines how the image
+    /// is repeated beyond its bounds.
+    ///
+    /// See [`ImagePattern`](struct.ImagePattern.html) for a brush that can be
+    /// stored independently of the back-end.
+    fn image_pattern(
+        &mut self,
+        image: &Self::Image,
+        transform: Affine,
+        extend: ExtendMode,
+    ) -> Result<Self::Brush, Error>;
+
     /// Clear the canvas with the given color.
     ///
-    /// Note: only opaque colors are meaningful.
+    /// Note: only opaque colors are meaningful. The current blend mode is
+    /// not applied.
     fn clear(&mut self, color: Color);
 
     /// Stroke a shape.
@@ -111,6 +198,12 @@ where
     /// Fill a shape, using even-odd fill rule
     fn fill_even_odd(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>);
 
+    /// Fill a rectangle, blurred with a Gaussian kernel.
+    ///
+    /// This is the primitive for drop shadows. The `blur_radius` is the
+    /// standard deviation of the blur.
+    fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &impl IntoBrush<Self>);
+
     /// Clip to a shape.
     ///
     /// All subsequent drawing operations up to the next [`restore`](#method.restore)
@@ -138,8 +231,8 @@ where
     /// Prefer [`with_save`](#method.with_save) if possible, as that statically
     /// enforces balance of save/restore pairs.
     ///
-    /// The context state currently consists of a clip region and an affine
-    /// transform, but is expected to grow in the near future.
+    /// The context state currently consists of a clip region, an affine
+    /// transform and a blend mode, but is expected to grow in the near future.
     fn save(&mut self) -> Result<(), Error>;
 
     /// Restore the context state.
@@ -158,6 +251,104 @@ where
         f(self).and(self.restore())
     }
 
+    /// Push a layer onto the context state stack.
+    ///
+    /// Drawing operations up to the matching [`pop_layer`](#method.pop_layer)
+    /// are rendered into a separate group, which is then composited onto the
+    /// underlying surface as a single unit with the given `opacity`. If a
+    /// `clip` rectangle is given, the layer is also clipped to it.
+    ///
+    /// This saves the context state like [`save`](#method.save) does, so any
+    /// changes to the transform and clip made inside the layer are undone when
+    /// it is popped. It is an error to [`restore`](#method.restore) a state
+    /// saved outside the layer before popping the layer.
+    ///
+    /// Prefer [`with_layer`](#method.with_layer) if possible, as that statically
+    /// enforces balance of push/pop pairs.
+    fn push_layer(&mut self, opacity: f64, clip: Option<Rect>) -> Result<(), Error>;
+
+    /// Pop a layer and composite it.
+    ///
+    /// Pop a layer that was pushed by [`push_layer`](#method.push_layer),
+    /// [`push_image_mask`](#method.push_image_mask) or
+    /// [`pop_mask`](#method.pop_mask). See those methods for details.
+    fn pop_layer(&mut self) -> Result<(), Error>;
+
+    /// Do graphics operations inside a layer.
+    ///
+    /// Equivalent to [`push_layer`](#method.push_layer), calling `f`, then
+    /// [`pop_layer`](#method.pop_layer). See those methods for more details.
+    fn with_layer(
+        &mut self,
+        opacity: f64,
+        clip: Option<Rect>,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.push_layer(opacity, clip)?;
+        // Always try to pop the layer, even if `f` errored.
+        f(self).and(self.pop_layer())
+    }
+
+    /// Push a layer that is masked by the alpha channel of an image.
+    ///
+    /// This works like [`push_layer`](#method.push_layer), but rather than
+    /// with a uniform opacity, the layer is composited through the alpha
+    /// channel of `image`, scaled to `dst_rect`. Nothing in the layer is
+    /// drawn outside of `dst_rect`.
+    ///
+    /// The layer is popped by [`pop_layer`](#method.pop_layer).
+    fn push_image_mask(&mut self, image: &Self::Image, dst_rect: Rect) -> Result<(), Error>;
+
+    /// Begin drawing a mask.
+    ///
+    /// Drawing operations up to the matching [`pop_mask`](#method.pop_mask)
+    /// are rendered into a mask rather than onto the surface. Only the alpha
+    /// channel of what is drawn is significant. As with
+    /// [`push_layer`](#method.push_layer), the context state is saved, and
+    /// restored by `pop_mask`.
+    ///
+    /// Prefer [`with_mask`](#method.with_mask) if possible.
+    fn push_mask(&mut self) -> Result<(), Error>;
+
+    /// Finish drawing a mask, and push a layer that is masked by it.
+    ///
+    /// Drawing operations up to the matching [`pop_layer`](#method.pop_layer)
+    /// are composited through the alpha channel of the mask drawn since the
+    /// matching [`push_mask`](#method.push_mask).
+    fn pop_mask(&mut self) -> Result<(), Error>;
+
+    /// Do graphics operations masked by drawn content.
+    ///
+    /// Draws the mask with `mask`, then does the operations in `f` in a layer
+    /// that is masked by it. See [`push_mask`](#method.push_mask) and
+    /// [`pop_mask`](#method.pop_mask) for more details.
+    fn with_mask(
+        &mut self,
+        mask: impl FnOnce(&mut Self) -> Result<(), Error>,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.push_mask()?;
+        // Always try to balance the stack, even if `mask` or `f` errored.
+        let result = mask(self);
+        self.pop_mask()?;
+        let result = result.and_then(|_| f(self));
+        result.and(self.pop_layer())
+    }
+
+    /// Do graphics operations masked by the alpha channel of an image.
+    ///
+    /// Equivalent to [`push_image_mask`](#method.push_image_mask), calling
+    /// `f`, then [`pop_layer`](#method.pop_layer).
+    fn with_image_mask(
+        &mut self,
+        image: &Self::Image,
+        dst_rect: Rect,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.push_image_mask(image, dst_rect)?;
+        f(self).and(self.pop_layer())
+    }
+
     /// Finish any pending operations.
     ///
     /// This will generally be called by a shell after all user drawing
@@ -180,6 +371,46 @@ where
         format: ImageFormat,
     ) -> Result<Self::Image, Error>;
 
+    /// Create a new image by drawing into it.
+    ///
+    /// The closure draws into an offscreen target of `size` pixels, rounded
+    /// up. Inside it, the context starts with an identity transform, no clip
+    /// and the default blend mode; the previous target and state are restored
+    /// afterwards. The result can be drawn any number of times with
+    /// [`draw_image`](#method.draw_image), which avoids repeating the drawing
+    /// commands.
+    ///
+    /// Every `save` and `push_layer` made by the closure must be matched
+    /// before it returns. Back-ends that can detect a mismatch return
+    /// `ErrorKind::StackUnbalance`.
+    fn create_offscreen(
+        &mut self,
+        size: Size,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<Self::Image, Error>;
+
+    /// Create a new image from an area of what has been drawn so far.
+    ///
+    /// The `src_rect` is in the current user space. The image covers its
+    /// bounding box on the target, rounded out to whole pixels, at the
+    /// target's resolution, so that drawing it back into `src_rect` with the
+    /// same transform reproduces the area. Drawing inside layers that haven't
+    /// been popped yet is not included.
+    ///
+    /// Back-ends that can't read back their target return
+    /// `ErrorKind::NotSupported`.
+    fn capture_image_area(&mut self, src_rect: impl Into<Rect>) -> Result<Self::Image, Error>;
+
+    /// Read the pixels of an image.
+    ///
+    /// Returns the width and height of the image, and its pixels in `format`,
+    /// row by row without padding. Alpha is discarded for `ImageFormat::Rgb`.
+    fn read_image(
+        &mut self,
+        image: &Self::Image,
+        format: ImageFormat,
+    ) -> Result<(usize, usize, Vec<u8>), Error>;
+
     /// Draw an image.
     ///
     /// The `image` is scaled to the provided `dst_rect`.
@@ -203,6 +434,18 @@ where
         interp: InterpolationMode,
     );
 
+    /// Set the blend mode.
+    ///
+    /// The blend mode determines how subsequent drawing operations are
+    /// composited with the existing contents of the surface. It remains in
+    /// effect until the next [`restore`](#method.restore) operation. Layers
+    /// are composited with the blend mode that was current when they were
+    /// pushed.
+    ///
+    /// Back-ends that can't support the requested mode keep the current one,
+    /// and report `ErrorKind::NotSupported` through [`status`](#method.status).
+    fn set_blend_mode(&mut self, mode: BlendMode);
+
     /// Returns the transformations currently applied to the context.
     fn current_transform(&self) -> Affine;
 }
@@ -225,7 +468,7 @@ impl<P: RenderContext> IntoBrush<P> for Color {
     }
 }
 
-/// A color or a gradient.
+/// A color, a gradient or an image pattern.
 ///
 /// This type is provided as a convenience, so that library consumers can
 /// easily write methods and types that use or reference *something* that can
@@ -259,7 +502,9 @@ pub enum PaintBrush {
     Color(Color),
     Linear(LinearGradient),
     Radial(RadialGradient),
+    Sweep(SweepGradient),
     Fixed(FixedGradient),
+    Image(ImagePattern),
 }
 
 impl<P: RenderContext> IntoBrush<P> for PaintBrush {
@@ -268,7 +513,9 @@ impl<P: RenderContext> IntoBrush<P> for PaintBrush {
             PaintBrush::Color(color) => color.make_brush(piet, bbox),
             PaintBrush::Linear(linear) => linear.make_brush(piet, bbox),
             PaintBrush::Radial(radial) => radial.make_brush(piet, bbox),
+            PaintBrush::Sweep(sweep) => sweep.make_brush(piet, bbox),
             PaintBrush::Fixed(fixed) => fixed.make_brush(piet, bbox),
+            PaintBrush::Image(image) => image.make_brush(piet, bbox),
         }
     }
 }
@@ -291,6 +538,12 @@ impl From<RadialGradient> for PaintBrush {
     }
 }
 
+impl From<SweepGradient> for PaintBrush {
+    fn from(src: SweepGradient) -> PaintBrush {
+        PaintBrush::Sweep(src)
+    }
+}
+
 impl From<FixedGradient> for PaintBrush {
     fn from(src: FixedGradient) -> PaintBrush {
         PaintBrush::Fixed(src)
@@ -308,3 +561,15 @@ impl From<FixedRadialGradient> for PaintBrush {
         PaintBrush::Fixed(src.into())
     }
 }
+
+impl From<FixedSweepGradient> for PaintBrush {
+    fn from(src: FixedSweepGradient) -> PaintBrush {
+        PaintBrush::Fixed(src.into())
+    }
+}
+
+impl From<ImagePattern> for PaintBrush {
+    fn from(src: ImagePattern) -> PaintBrush {
+        PaintBrush::Image(src)
+    }
+}
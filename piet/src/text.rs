@@ -1,7 +1,12 @@
 //! Traits for fonts and text handling.
 
+use std::ops::{Bound, Range, RangeBounds};
+use std::path::Path;
+
+use ttf_parser::{name_id, Face};
+
 use crate::kurbo::Point;
-use crate::Error;
+use crate::{Color, Error, ErrorKind};
 
 pub trait Text {
     type FontBuilder: FontBuilder<Out = Self::Font>;
@@ -12,23 +17,600 @@ pub trait Text {
 
     fn new_font_by_name(&mut self, name: &str, size: f64) -> Self::FontBuilder;
 
+    /// Make the fonts in the data of a TTF, OTF or TTC file available to
+    /// [`new_font_by_name`], with the family names they give themselves.
+    ///
+    /// Returns the family name of the font, or of the first font in a
+    /// collection. Loaded fonts are preferred over installed fonts of the
+    /// same family.
+    ///
+    /// ```no_run
+    /// use piet::{FontBuilder, RenderContext, Text};
+    ///
+    /// # let mut render_ctx = piet::NullRenderContext::new();
+    /// let text = render_ctx.text();
+    /// let family = text.load_font_file("fonts/MyFont.ttf").unwrap();
+    /// let font = text.new_font_by_name(&family, 12.0).build().unwrap();
+    /// ```
+    ///
+    /// [`new_font_by_name`]: #tymethod.new_font_by_name
+    fn load_font(&mut self, data: &[u8]) -> Result<String, Error>;
+
+    /// Load the fonts in a TTF, OTF or TTC file, as with [`load_font`].
+    ///
+    /// [`load_font`]: #tymethod.load_font
+    fn load_font_file(&mut self, path: impl AsRef<Path>) -> Result<String, Error> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .map_err(|e| Error::new(ErrorKind::Io(e)).with_context(path.display().to_string()))?;
+        self.load_font(&data)
+    }
+
+    /// The names of the font families fonts can be built from, sorted and
+    /// without duplicates.
+    ///
+    /// These are the loaded families and the installed families the back-end
+    /// can list. Generic families aren't included.
+    fn font_families(&mut self) -> Vec<String>;
+
+    /// Whether fonts can be built from a family, rather than from the family
+    /// the back-end falls back to. Names are matched case-insensitively, and
+    /// generic families are always available.
+    ///
+    /// ```
+    /// use piet::{GenericFamily, RenderContext, Text};
+    ///
+    /// # let mut render_ctx = piet::NullRenderContext::new();
+    /// let text = render_ctx.text();
+    /// let family = if text.has_font_family("Segoe UI") {
+    ///     "Segoe UI"
+    /// } else {
+    ///     GenericFamily::SystemUi.name()
+    /// };
+    /// ```
+    fn has_font_family(&mut self, name: &str) -> bool {
+        GenericFamily::from_name(name).is_some()
+            || self
+                .font_families()
+                .iter()
+                .any(|family| family.eq_ignore_ascii_case(name))
+    }
+
     fn new_text_layout(&mut self, font: &Self::Font, text: &str) -> Self::TextLayoutBuilder;
 }
 
+/// The family name of a font in the data of a TTF, OTF or TTC file.
+///
+/// `index` is the index of the font in a collection, and is 0 for other
+/// files. This is the name a back-end gives the font in [`Text::load_font`].
+///
+/// [`Text::load_font`]: trait.Text.html#tymethod.load_font
+pub fn font_family_name(data: &[u8], index: u32) -> Result<String, Error> {
+    let face = Face::from_slice(data, index)
+        .map_err(|e| Error::new(ErrorKind::InvalidFont(e.to_string())))?;
+    // The typographic family groups more faces than the legacy family,
+    // which only has four styles.
+    [name_id::TYPOGRAPHIC_FAMILY, name_id::FAMILY]
+        .iter()
+        .find_map(|&id| {
+            face.names()
+                .into_iter()
+                .filter(|name| name.name_id == id)
+                .find_map(|name| name.to_string())
+        })
+        .ok_or_else(|| Error::new(ErrorKind::InvalidFont("no family name".into())))
+}
+
+/// A builder for a [`Font`], which can choose a face of its family.
+///
+/// ```
+/// use piet::{FontBuilder, FontStretch, FontStyle, FontWeight, RenderContext, Text};
+///
+/// # let mut render_ctx = piet::NullRenderContext::new();
+/// let text = render_ctx.text();
+/// let font = text
+///     .new_font_by_name("sans-serif", 14.0)
+///     .weight(FontWeight::SEMI_BOLD)
+///     .style(FontStyle::Italic)
+///     .stretch(FontStretch::Condensed)
+///     .build()
+///     .unwrap();
+/// ```
+///
+/// A back-end uses the face of the family closest to the properties, and
+/// may ignore properties its fonts don't have.
+///
+/// [`Font`]: trait.Font.html
 pub trait FontBuilder {
     type Out: Font;
 
+    /// Set the weight of the font. The default is `FontWeight::REGULAR`.
+    fn weight(self, weight: FontWeight) -> Self;
+
+    /// Set the slant of the font. The default is `FontStyle::Regular`.
+    fn style(self, style: FontStyle) -> Self;
+
+    /// Set the width of the font. The default is `FontStretch::Normal`.
+    fn stretch(self, stretch: FontStretch) -> Self;
+
     fn build(self) -> Result<Self::Out, Error>;
 }
 
-pub trait Font {}
+pub trait Font {
+    /// Measurements of the font, in the same units as its size.
+    fn metrics(&self) -> FontMetrics;
+
+    /// The name of the family the font resolved to.
+    ///
+    /// This is the loaded or installed family the font's glyphs come from,
+    /// which is another family when the one asked for isn't available, or
+    /// is generic. Back-ends that can't tell report the family asked for.
+    fn family_name(&self) -> &str;
+}
+
+/// Measurements of a font, for placing and decorating text.
+///
+/// Distances are measured from the baseline: the ascent, x-height and cap
+/// height upwards, and the descent and underline position downwards.
+///
+/// ```
+/// use piet::FontMetrics;
+///
+/// let metrics = FontMetrics::approximate(10.0);
+/// assert_eq!(metrics.line_height(), 12.0);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FontMetrics {
+    /// The height of the tallest glyphs above the baseline.
+    pub ascent: f64,
+    /// The depth of the lowest glyphs below the baseline.
+    pub descent: f64,
+    /// The space between lines of text, beyond the ascent and descent.
+    pub line_gap: f64,
+    /// The height of lowercase letters such as "x".
+    pub x_height: f64,
+    /// The height of capital letters.
+    pub cap_height: f64,
+    /// The distance from the baseline down to the top of an underline.
+    pub underline_position: f64,
+    /// The thickness of an underline.
+    pub underline_thickness: f64,
+}
+
+impl FontMetrics {
+    /// Typical metrics for a font of the given size.
+    ///
+    /// This is for back-ends that can't measure fonts.
+    pub fn approximate(size: f64) -> FontMetrics {
+        FontMetrics {
+            ascent: size * 0.9,
+            descent: size * 0.25,
+            line_gap: size * 0.05,
+            x_height: size * 0.5,
+            cap_height: size * 0.7,
+            underline_position: size * 0.1,
+            underline_thickness: size / 16.0,
+        }
+    }
+
+    /// The distance between the baselines of lines of text in the font.
+    pub fn line_height(&self) -> f64 {
+        self.ascent + self.descent + self.line_gap
+    }
+}
 
+/// A builder for a [`TextLayout`], which can style ranges of the text.
+///
+/// ```
+/// use piet::{Color, FontBuilder, FontWeight, RenderContext, Text, TextAttribute, TextLayoutBuilder};
+///
+/// # let mut render_ctx = piet::NullRenderContext::new();
+/// let text = render_ctx.text();
+/// let font = text.new_font_by_name("sans-serif", 14.0).build().unwrap();
+/// let layout = text
+///     .new_text_layout(&font, "Some bold, red text")
+///     .range_attribute(5..9, FontWeight::BOLD)
+///     .range_attribute(11..14, Color::rgb8(0xc0, 0, 0))
+///     .range_attribute(11.., TextAttribute::Underline(true))
+///     .build()
+///     .unwrap();
+/// ```
+///
+/// [`TextLayout`]: trait.TextLayout.html
 pub trait TextLayoutBuilder {
     type Out: TextLayout;
 
+    /// Set an attribute for a range of the text.
+    ///
+    /// The range is in utf-8 code units, and is clamped to the text. Where
+    /// ranges with the same kind of attribute overlap, the one added last
+    /// is used.
+    fn range_attribute(
+        self,
+        range: impl RangeBounds<usize>,
+        attribute: impl Into<TextAttribute>,
+    ) -> Self;
+
+    /// Set an attribute for all of the text.
+    ///
+    /// Attributes for ranges added after this take precedence.
+    fn default_attribute(self, attribute: impl Into<TextAttribute>) -> Self
+    where
+        Self: Sized,
+    {
+        self.range_attribute(.., attribute)
+    }
+
+    /// Set the width at which lines of text wrap.
+    ///
+    /// Lines are broken at the line break opportunities of [UAX #14]. A
+    /// word that is wider than `width` is not broken, and overflows its
+    /// line. Explicit newlines always start a new line, whether or not a
+    /// width is set.
+    ///
+    /// [UAX #14]: https://www.unicode.org/reports/tr14/
+    fn max_width(self, width: f64) -> Self;
+
+    /// Set how lines are placed within the width of the layout.
+    ///
+    /// The width is the one set with [`max_width`], or else the width of
+    /// the widest line. The direction of each paragraph, for the start and
+    /// end of its lines, is that of its first strong character, as in the
+    /// Unicode Bidirectional Algorithm.
+    ///
+    /// [`max_width`]: #tymethod.max_width
+    fn alignment(self, alignment: TextAlignment) -> Self;
+
     fn build(self) -> Result<Self::Out, Error>;
 }
 
+/// The weight of a font, from 1 to 1000, as in CSS and OpenType.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FontWeight(u16);
+
+impl FontWeight {
+    pub const THIN: FontWeight = FontWeight(100);
+    pub const EXTRA_LIGHT: FontWeight = FontWeight(200);
+    pub const LIGHT: FontWeight = FontWeight(300);
+    pub const REGULAR: FontWeight = FontWeight(400);
+    pub const MEDIUM: FontWeight = FontWeight(500);
+    pub const SEMI_BOLD: FontWeight = FontWeight(600);
+    pub const BOLD: FontWeight = FontWeight(700);
+    pub const EXTRA_BOLD: FontWeight = FontWeight(800);
+    pub const BLACK: FontWeight = FontWeight(900);
+
+    /// Create a weight, clamped to the range from 1 to 1000.
+    pub fn new(raw: u16) -> FontWeight {
+        FontWeight(raw.clamp(1, 1000))
+    }
+
+    /// The numeric value of the weight.
+    pub fn to_raw(self) -> u16 {
+        self.0
+    }
+}
+
+impl Default for FontWeight {
+    fn default() -> Self {
+        FontWeight::REGULAR
+    }
+}
+
+/// The slant of a font.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FontStyle {
+    /// Upright glyphs.
+    #[default]
+    Regular,
+    /// Italic glyphs, or slanted ones if the font has no italic.
+    Italic,
+    /// Slanted glyphs, or italic ones if the font has no slanted face.
+    Oblique,
+}
+
+/// The width of a font, relative to its normal width, as in CSS and OpenType.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FontStretch {
+    UltraCondensed,
+    ExtraCondensed,
+    Condensed,
+    SemiCondensed,
+    #[default]
+    Normal,
+    SemiExpanded,
+    Expanded,
+    ExtraExpanded,
+    UltraExpanded,
+}
+
+impl FontStretch {
+    /// The width as a percentage of the normal width, as with the CSS
+    /// `font-stretch` property.
+    ///
+    /// ```
+    /// use piet::FontStretch;
+    ///
+    /// assert_eq!(FontStretch::Condensed.to_percentage(), 75.0);
+    /// ```
+    pub fn to_percentage(self) -> f64 {
+        match self {
+            FontStretch::UltraCondensed => 50.0,
+            FontStretch::ExtraCondensed => 62.5,
+            FontStretch::Condensed => 75.0,
+            FontStretch::SemiCondensed => 87.5,
+            FontStretch::Normal => 100.0,
+            FontStretch::SemiExpanded => 112.5,
+            FontStretch::Expanded => 125.0,
+            FontStretch::ExtraExpanded => 150.0,
+            FontStretch::UltraExpanded => 200.0,
+        }
+    }
+
+    /// The name of the width in CSS.
+    pub fn to_css(self) -> &'static str {
+        match self {
+            FontStretch::UltraCondensed => "ultra-condensed",
+            FontStretch::ExtraCondensed => "extra-condensed",
+            FontStretch::Condensed => "condensed",
+            FontStretch::SemiCondensed => "semi-condensed",
+            FontStretch::Normal => "normal",
+            FontStretch::SemiExpanded => "semi-expanded",
+            FontStretch::Expanded => "expanded",
+            FontStretch::ExtraExpanded => "extra-expanded",
+            FontStretch::UltraExpanded => "ultra-expanded",
+        }
+    }
+}
+
+/// A generic font family, which each back-end resolves to a family it has.
+///
+/// The name of a generic family can be used wherever a family name can.
+///
+/// ```
+/// use piet::GenericFamily;
+///
+/// assert_eq!(GenericFamily::SansSerif.name(), "sans-serif");
+/// assert_eq!(GenericFamily::from_name("Monospace"), Some(GenericFamily::Monospace));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GenericFamily {
+    Serif,
+    SansSerif,
+    Monospace,
+    /// The family of the platform's user interface.
+    SystemUi,
+}
+
+impl GenericFamily {
+    pub const ALL: [GenericFamily; 4] = [
+        GenericFamily::Serif,
+        GenericFamily::SansSerif,
+        GenericFamily::Monospace,
+        GenericFamily::SystemUi,
+    ];
+
+    /// The name of the family, as in CSS.
+    pub fn name(self) -> &'static str {
+        match self {
+            GenericFamily::Serif => "serif",
+            GenericFamily::SansSerif => "sans-serif",
+            GenericFamily::Monospace => "monospace",
+            GenericFamily::SystemUi => "system-ui",
+        }
+    }
+
+    /// The generic family with a name, matched case-insensitively.
+    pub fn from_name(name: &str) -> Option<GenericFamily> {
+        GenericFamily::ALL
+            .iter()
+            .copied()
+            .find(|family| family.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// How lines of text are placed within the width of a layout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TextAlignment {
+    /// At the start of the line: the left for left-to-right text, and the
+    /// right for right-to-left text.
+    #[default]
+    Start,
+    /// At the end of the line.
+    End,
+    /// In the center.
+    Center,
+    /// Stretched to fill the width, by widening the spaces between words.
+    /// The last line of a paragraph is aligned to its start.
+    Justified,
+}
+
+/// An attribute of a range of text in a layout.
+///
+/// See [`TextLayoutBuilder::range_attribute`].
+///
+/// [`TextLayoutBuilder::range_attribute`]: trait.TextLayoutBuilder.html#tymethod.range_attribute
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextAttribute {
+    /// The name of the font family.
+    FontFamily(String),
+    /// The size of the font, in the same units as for `new_font_by_name`.
+    FontSize(f64),
+    /// The weight of the font.
+    Weight(FontWeight),
+    /// The slant of the font.
+    Style(FontStyle),
+    /// The color of the text, used instead of the brush passed to `draw_text`.
+    ///
+    /// This is only a solid color: a range of text can't be painted with a
+    /// gradient or image brush of its own.
+    ForegroundColor(Color),
+    /// Whether the text is underlined.
+    Underline(bool),
+    /// Whether the text is struck through.
+    Strikethrough(bool),
+}
+
+impl From<FontWeight> for TextAttribute {
+    fn from(weight: FontWeight) -> TextAttribute {
+        TextAttribute::Weight(weight)
+    }
+}
+
+impl From<FontStyle> for TextAttribute {
+    fn from(style: FontStyle) -> TextAttribute {
+        TextAttribute::Style(style)
+    }
+}
+
+impl From<Color> for TextAttribute {
+    fn from(color: Color) -> TextAttribute {
+        TextAttribute::ForegroundColor(color)
+    }
+}
+
+/// The style of a run of text, with all of its attributes resolved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    pub family: String,
+    pub size: f64,
+    pub weight: FontWeight,
+    pub style: FontStyle,
+    pub stretch: FontStretch,
+    /// The color of the text, if it isn't drawn with the brush passed to
+    /// `draw_text`.
+    pub color: Option<Color>,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl TextStyle {
+    /// The style of text in a font, with no other attributes.
+    pub fn new(family: impl Into<String>, size: f64) -> TextStyle {
+        TextStyle {
+            family: family.into(),
+            size,
+            weight: FontWeight::default(),
+            style: FontStyle::default(),
+            stretch: FontStretch::default(),
+            color: None,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+
+    fn apply(&mut self, attribute: &TextAttribute) {
+        match attribute {
+            TextAttribute::FontFamily(family) => self.family = family.clone(),
+            TextAttribute::FontSize(size) => self.size = *size,
+            TextAttribute::Weight(weight) => self.weight = *weight,
+            TextAttribute::Style(style) => self.style = *style,
+            TextAttribute::ForegroundColor(color) => self.color = Some(color.clone()),
+            TextAttribute::Underline(underline) => self.underline = *underline,
+            TextAttribute::Strikethrough(strikethrough) => self.strikethrough = *strikethrough,
+        }
+    }
+}
+
+/// The attributes of ranges of text, in the order they were added.
+///
+/// This is for back-ends, to split the text of a layout into runs of the
+/// same style.
+///
+/// ```
+/// use piet::{AttributeSpans, FontWeight, TextStyle};
+///
+/// let text = "regular bold";
+/// let mut spans = AttributeSpans::new();
+/// spans.add(text, 8.., FontWeight::BOLD.into());
+/// let runs = spans.runs(text, &TextStyle::new("serif", 12.0));
+/// assert_eq!(runs.len(), 2);
+/// assert_eq!(runs[1].0, 8..12);
+/// assert_eq!(runs[1].1.weight, FontWeight::BOLD);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AttributeSpans {
+    spans: Vec<(Range<usize>, TextAttribute)>,
+}
+
+impl AttributeSpans {
+    pub fn new() -> AttributeSpans {
+        AttributeSpans::default()
+    }
+
+    /// Add an attribute for a range of `text`.
+    ///
+    /// The range is clamped to the text, and its ends are moved back to
+    /// character boundaries.
+    pub fn add(&mut self, text: &str, range: impl RangeBounds<usize>, attribute: TextAttribute) {
+        let range = resolve_range(text, range);
+        if !range.is_empty() {
+            self.spans.push((range, attribute));
+        }
+    }
+
+    /// The attributes, with their ranges.
+    pub fn iter(&self) -> impl Iterator<Item = (Range<usize>, &TextAttribute)> {
+        self.spans
+            .iter()
+            .map(|(range, attribute)| (range.clone(), attribute))
+    }
+
+    /// Split `text` into runs, each with a single style.
+    ///
+    /// The style of each run is `defaults`, with the attributes covering the
+    /// run applied in order. There is always at least one run, which is empty
+    /// if the text is.
+    pub fn runs(&self, text: &str, defaults: &TextStyle) -> Vec<(Range<usize>, TextStyle)> {
+        let mut bounds = vec![0, text.len()];
+        for (range, _) in &self.spans {
+            bounds.push(range.start);
+            bounds.push(range.end);
+        }
+        bounds.sort_unstable();
+        bounds.dedup();
+        let mut runs: Vec<(Range<usize>, TextStyle)> = Vec::new();
+        for pair in bounds.windows(2) {
+            let range = pair[0]..pair[1];
+            let mut style = defaults.clone();
+            for (span, attribute) in &self.spans {
+                if span.start <= range.start && range.end <= span.end {
+                    style.apply(attribute);
+                }
+            }
+            match runs.last_mut() {
+                Some(last) if last.1 == style => last.0.end = range.end,
+                _ => runs.push((range, style)),
+            }
+        }
+        if runs.is_empty() {
+            runs.push((0..0, defaults.clone()));
+        }
+        runs
+    }
+}
+
+/// Clamp a range to `text`, moving its ends back to character boundaries.
+fn resolve_range(text: &str, range: impl RangeBounds<usize>) -> Range<usize> {
+    let boundary = |mut i: usize| {
+        i = i.min(text.len());
+        while !text.is_char_boundary(i) {
+            i -= 1;
+        }
+        i
+    };
+    let start = match range.start_bound() {
+        Bound::Included(&i) => boundary(i),
+        Bound::Excluded(&i) => boundary(i.saturating_add(1)),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&i) => boundary(i.saturating_add(1)),
+        Bound::Excluded(&i) => boundary(i),
+        Bound::Unbounded => text.len(),
+    };
+    start..end.max(start)
+}
+
 /// # Text Layout
 ///
 /// ## Text Position
@@ -41,10 +623,36 @@ pub trait TextLayoutBuilder {
 /// - If the text position is not at a code point or grapheme boundary, undesirable behavior may
 /// occur.
 ///
+/// ## Lines
+///
+/// A layout has one or more lines, from explicit newlines and from wrapping at the width set with
+/// [`TextLayoutBuilder::max_width`]. The point passed to `draw_text` is on the baseline of the
+/// first line, and the `y` of a point in hit testing is relative to it.
+///
+/// [`TextLayoutBuilder::max_width`]: trait.TextLayoutBuilder.html#tymethod.max_width
 pub trait TextLayout {
     /// Measure the advance width of the text.
+    ///
+    /// For text on several lines, this is the width of the widest line,
+    /// not including the whitespace at the end of a wrapped line.
     fn width(&self) -> f64;
 
+    /// Measurements of the whole layout.
+    fn metrics(&self) -> TextLayoutMetrics;
+
+    /// The number of lines in the layout.
+    fn line_count(&self) -> usize;
+
+    /// Measurements of a line.
+    ///
+    /// Returns `None` if there's no line with that number.
+    fn line_metric(&self, line_number: usize) -> Option<LineMetric>;
+
+    /// The text of a line, including any trailing whitespace and newline.
+    ///
+    /// Returns `None` if there's no line with that number.
+    fn line_text(&self, line_number: usize) -> Option<&str>;
+
     /// Given a `Point`, determine the corresponding text position.
     ///
     /// ## Return value:
@@ -57,8 +665,11 @@ pub trait TextLayout {
     ///
     /// ## Notes:
     ///
+    /// The line is chosen by `point.y`; a point above the first line or below the last one is tested
+    /// against that line.
+    ///
     /// Some text position will always be returned; if the tested point is inside, it returns the appropriate text
-    /// position; if it's outside, it will return the nearest text position (either `0` or `text.len()`).
+    /// position; if it's outside, it will return the nearest text position on the line.
     ///
     /// For more on text positions, see docs for the [`TextLayout`](../piet/trait.TextLayout.html)
     /// trait.
@@ -68,13 +679,13 @@ pub trait TextLayout {
     fn hit_test_point(&self, point: Point) -> HitTestPoint;
 
     /// Given a text position, determine the corresponding pixel location.
-    /// (currently consider the text layout just one line)
     ///
     /// ## Return value:
     /// Returns a [`HitTestTextPosition`][] describing the results of the test.
     ///
     /// [`HitTestTextPosition`][] field `point` is the point offset of the boundary of the
-    /// grapheme cluster that the text position is a part of.
+    /// grapheme cluster that the text position is a part of. Its `y` is the baseline of the
+    /// line the text position is on. A position at a wrapped line break is on the following line.
     ///
     /// [`HitTestTextPosition`][] field `metrics` is a [`HitTestMetrics`][] struct. [`HitTestMetrics`][] field `text_position` is the original text position (unless out of bounds).
     ///
@@ -94,6 +705,42 @@ pub trait TextLayout {
     fn hit_test_text_position(&self, text_position: usize) -> Option<HitTestTextPosition>;
 }
 
+/// Measurements of a [`TextLayout`].
+///
+/// [`TextLayout`]: trait.TextLayout.html
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextLayoutMetrics {
+    /// The height of the layout, the sum of the heights of its lines.
+    pub height: f64,
+    /// The distance from the top of the layout down to the first baseline.
+    pub ascent: f64,
+    /// The depth of the last line below its baseline, not counting its
+    /// line gap.
+    pub descent: f64,
+    /// The space between the first line and the next, beyond their ascent
+    /// and descent.
+    pub line_gap: f64,
+    pub line_count: usize,
+}
+
+/// Measurements of a line in a [`TextLayout`].
+///
+/// [`TextLayout`]: trait.TextLayout.html
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineMetric {
+    /// The text position of the start of the line.
+    pub start_offset: usize,
+    /// The text position of the end of the line, after any trailing
+    /// whitespace and newline.
+    pub end_offset: usize,
+    /// The offset of the baseline from the baseline of the first line.
+    pub baseline: f64,
+    /// The distance from the top of the line down to its baseline.
+    pub ascent: f64,
+    /// The height of the line, including its line gap.
+    pub height: f64,
+}
+
 /// return values for [`hit_test_point`](../piet/trait.TextLayout.html#tymethod.hit_test_point).
 #[derive(Debug, Default, PartialEq)]
 pub struct HitTestPoint {
@@ -108,7 +755,8 @@ pub struct HitTestPoint {
 /// return values for [`hit_test_text_position`](../piet/trait.TextLayout.html#tymethod.hit_test_text_position).
 #[derive(Debug, Default)]
 pub struct HitTestTextPosition {
-    /// the `point`'s `x` value is the position of the leading edge of the grapheme cluster containing the text position.
+    /// the `point`'s `x` value is the position of the leading edge of the grapheme cluster containing the text position,
+    /// and its `y` value is the baseline of its line.
     pub point: Point,
     /// `metrics.text_position` will give you the text position.
     pub metrics: HitTestMetrics,